@@ -22,14 +22,33 @@ fn main() {
         .out_dir(current_dir.clone())
         .file("src/stdlib/c/test.c")
         .compile("test");
+    //JITで実行する時だけ使うランタイム。AOTの出力にはリンクしない
+    cc::Build::new()
+        .file("src/stdlib/c/jit.c")
+        .compile("ruscall_jit");
+
+    //コンパイラがランタイムライブラリを探せるように場所を埋め込む
+    println!("cargo:rustc-env=RUSCALL_RUNTIME_DIR={}", current_dir);
 
-    //windowsのみcompile.batファイルを親プロジェクトにコピー
-    if cfg!(target_os = "windows") {
-        use std::fs;
-        let lib_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
-        if lib_dir != current_dir {
-            fs::copy(lib_dir + "\\compile.bat", current_dir + "\\compile.bat")
-                .expect("compile.batのコピーに失敗しました");
-        }
+    //LLVMのC APIにないデバッグ情報生成とmusttailをC++で補う
+    let mut shim = cc::Build::new();
+    shim.cpp(true).file("src/llvm_shim/debug_info.cpp").file("src/llvm_shim/tail_call.cpp");
+    for flag in llvm_config("--cxxflags").split_whitespace() {
+        shim.flag(flag);
     }
+    shim.compile("ruscall_llvm_shim");
+}
+
+//llvm-sysと同じくLLVM_SYS_60_PREFIXがあればその下のllvm-configを使う
+fn llvm_config(arg: &str) -> String {
+    use std::env;
+    use std::path::Path;
+    use std::process::Command;
+    let llvm_config = match env::var("LLVM_SYS_60_PREFIX") {
+        Ok(prefix) => Path::new(&prefix).join("bin").join("llvm-config").to_str().unwrap().to_string(),
+        Err(_) => "llvm-config".to_string(),
+    };
+    let output = Command::new(&llvm_config).arg(arg).output()
+        .expect(&format!("failed to execute {}", llvm_config));
+    String::from_utf8(output.stdout).unwrap()
 }
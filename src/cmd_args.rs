@@ -1,6 +1,12 @@
 use super::compile;
+use super::compile::compile_option::CompileOption;
+use super::compile::doc::DocFormat;
 use super::hello;
+use super::lsp;
+use super::repl;
 use std::env;
+use std::io::{self, BufRead, Read};
+use std::process;
 
 //コマンドラインオプションの種類
 #[derive(Debug, PartialEq)]
@@ -9,7 +15,14 @@ pub enum CmdArgsKind {
     Version,
     Error,
     Hello,
-    Compile(String, String),
+    Compile(String, Option<String>, CompileOption),
+    Check(String, bool),
+    Doc(String, DocFormat),
+    Fmt(Vec<String>, bool),
+    Run(String, Vec<String>),
+    Demangle(Vec<String>),
+    Repl,
+    Lsp,
 }
 
 impl CmdArgsKind {
@@ -25,14 +38,132 @@ impl CmdArgsKind {
                 println!("-hello                build hello world ");
                 println!("-build [SOURCE_FILE]  build source file");
                 println!("-build [SOURCE_FILE] [OUTPUT_FILE]  build source file and set output file name");
+                println!("\nBUILD OPTIONS:");
+                println!("-O0 -O1 -O2 -O3 -Os   set optimization level (default -O0)");
+                println!("--linker=[cc|clang|ld|link|wasm-ld]  select linker (default $RUSCALL_LINKER or cc, wasm-ld for wasm32)");
+                println!("-static -dynamic      select static or dynamic linking (default -dynamic)");
+                println!("--emit=[llvm-ir|llvm-bc|asm|obj|staticlib|cdylib|exe]  select output kind (default exe)");
+                println!("--crate-type=[bin|staticlib|cdylib]  build executable, static library or shared library with C header");
+                println!("--save-temps          keep intermediate files next to the output file");
+                println!("-g                    generate DWARF debug information");
+                println!("--target [TRIPLE]     generate code for the target triple (e.g. aarch64-unknown-linux-gnu)");
+                println!("--target-cpu [CPU]    generate code for the target cpu (default generic)");
+                println!("--target-feature [FEATURES]  enable or disable target features (e.g. +neon,-sse)");
+                println!();
+                println!("-check [SOURCE_FILE]  type check source file without code generation");
+                println!("-check [SOURCE_FILE] -types  type check source file and print inferred types");
+                println!("-doc [SOURCE_FILE]    print Markdown document generated from /// comments and inferred types");
+                println!("-doc [SOURCE_FILE] -html  print HTML document");
+                println!("-fmt [SOURCE_FILE]... format source files in place (read stdin and write stdout if no file is given)");
+                println!("-fmt --check [SOURCE_FILE]...  exit with 1 if formatting would change the files");
+                println!("-repl                 start interactive REPL");
+                println!("-lsp                  start language server on stdin and stdout");
+                println!("-run [SOURCE_FILE] [ARGS]...  run source file with JIT and exit with the return value of main");
+                println!("-demangle [SYMBOL]... demangle symbols (read from stdin if no symbol is given)");
             }
             CmdArgsKind::Version => println!("\nRuscall version 0.6.0\n"),
             CmdArgsKind::Hello => hello::hello(),
-            CmdArgsKind::Compile(ref input_file_name, ref output_file_name) => {
-                if let Err(err) = compile::compile(input_file_name, output_file_name) {
+            CmdArgsKind::Compile(ref input_file_name, ref output_file_name, ref option) => {
+                if let Err(err) = compile::compile(input_file_name, output_file_name.as_ref().map(|x| x as &str), option) {
                     eprintln!("{}", err);
+                    process::exit(1);
                 }
             }
+            CmdArgsKind::Check(ref input_file_name, show_types) => {
+                compile::set_verbose(false);
+                match compile::check(input_file_name) {
+                    Ok(assump) => if show_types {
+                        use super::compile::types::ShowType;
+                        assump.global_list()
+                            .into_iter()
+                            .for_each(|(name, scheme)| println!("{} :: {}", name, scheme.show()));
+                    },
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        process::exit(1);
+                    }
+                }
+            }
+            CmdArgsKind::Doc(ref input_file_name, format) => {
+                compile::set_verbose(false);
+                match compile::doc(input_file_name, format) {
+                    Ok(doc) => print!("{}", doc),
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        process::exit(1);
+                    }
+                }
+            }
+            CmdArgsKind::Fmt(ref file_names, check) => {
+                use super::compile::formatter::format_src;
+                if file_names.len() == 0 {
+                    let mut src = String::new();
+                    io::stdin().read_to_string(&mut src).unwrap();
+                    match format_src(&src) {
+                        Ok(formatted) => if !check {
+                            print!("{}", formatted);
+                        } else if formatted != src {
+                            process::exit(1);
+                        },
+                        Err(err) => {
+                            eprintln!("{}", err);
+                            process::exit(1);
+                        }
+                    }
+                    return;
+                }
+                //エラーがあっても残りのファイルは整形する
+                let mut failed = false;
+                for file_name in file_names {
+                    match compile::fmt(file_name, check) {
+                        Ok(true) if check => {
+                            println!("{}: not formatted", file_name);
+                            failed = true;
+                        }
+                        Ok(_) => (),
+                        Err(err) => {
+                            eprintln!("{}", err);
+                            failed = true;
+                        }
+                    }
+                }
+                if failed {
+                    process::exit(1);
+                }
+            }
+            CmdArgsKind::Run(ref input_file_name, ref program_args) => {
+                compile::set_verbose(false);
+                if program_args.len() != 0 {
+                    eprintln!("warning: main takes (), so program arguments are ignored: {:?}", program_args);
+                }
+                match compile::run(input_file_name) {
+                    Ok(code) => process::exit(code),
+                    //メッセージはパニックした時に表示している
+                    Err(ref err) if err == compile::jit::PANIC_ERROR => process::exit(compile::jit::PANIC_EXIT_CODE),
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        process::exit(1);
+                    }
+                }
+            }
+            CmdArgsKind::Demangle(ref symbols) => {
+                use super::compile::mangling::{demangle, demangle_text};
+                if symbols.len() == 0 {
+                    //nmやスタックトレースの出力を通すフィルタとして使う
+                    let stdin = io::stdin();
+                    for line in stdin.lock().lines() {
+                        println!("{}", demangle_text(&line.unwrap()));
+                    }
+                }
+                for symbol in symbols {
+                    println!("{}", demangle(symbol).unwrap_or(symbol.to_string()));
+                }
+            }
+            CmdArgsKind::Repl => {
+                compile::set_verbose(false);
+                repl::Repl::new().run();
+            }
+            CmdArgsKind::Lsp => process::exit(lsp::run()),
             CmdArgsKind::Error => println!("\nerror cmd args\n"),
         }
     }
@@ -45,22 +176,73 @@ pub fn get_cmd_args() -> Vec<String> {
 
 //コマンドライン引数からCmdArgsKindを生成
 pub fn parse_cmd_args(args: Vec<String>) -> CmdArgsKind {
+    if args.len() >= 3 && args[1] == "-run" {
+        return CmdArgsKind::Run(args[2].to_string(), args[3..].to_vec());
+    }
+    if args.len() >= 2 && args[1] == "-demangle" {
+        return CmdArgsKind::Demangle(args[2..].to_vec());
+    }
+    if args.len() >= 2 && args[1] == "-fmt" {
+        let check = args[2..].iter().any(|x| x == "--check");
+        let file_names = args[2..].iter().filter(|x| *x != "--check").cloned().collect::<Vec<_>>();
+        if file_names.iter().any(|x| x.starts_with("-")) {
+            return CmdArgsKind::Error;
+        }
+        return CmdArgsKind::Fmt(file_names, check);
+    }
+    if args.len() >= 3 && args[1] == "-build" {
+        return parse_build_args(&args[2..]);
+    }
     match args.len() {
         1 => CmdArgsKind::Help,
         2 => match &*(args[1]) {
             "-h" => CmdArgsKind::Help,
             "-v" => CmdArgsKind::Version,
             "-hello" => CmdArgsKind::Hello,
+            "-repl" => CmdArgsKind::Repl,
+            "-lsp" => CmdArgsKind::Lsp,
             _ => CmdArgsKind::Error,
         },
         3 => match (&*(args[1]), &*(args[2])) {
-            ("-build", file_name) => CmdArgsKind::Compile(file_name.to_string(), file_name.to_string()),
+            ("-check", file_name) => CmdArgsKind::Check(file_name.to_string(), false),
+            ("-doc", file_name) => CmdArgsKind::Doc(file_name.to_string(), DocFormat::Markdown),
             _ => CmdArgsKind::Error,
         },
         4 => match (&*(args[1]), &*(args[2]), &*(args[3])) {
-            ("-build", input_file_name, output_file_name) => CmdArgsKind::Compile(input_file_name.to_string(), output_file_name.to_string()),
+            ("-check", file_name, "-types") => CmdArgsKind::Check(file_name.to_string(), true),
+            ("-doc", file_name, "-html") => CmdArgsKind::Doc(file_name.to_string(), DocFormat::Html),
             _ => CmdArgsKind::Error,
         }
         _ => CmdArgsKind::Error,
     }
 }
+
+//-buildの引数を解釈する。ファイル名以外はビルドオプションとして扱う
+fn parse_build_args(args: &[String]) -> CmdArgsKind {
+    let mut option = CompileOption::new();
+    let mut file_names = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg.starts_with("-") {
+            //値を取るオプションは次の引数と合わせて解釈する
+            let arg = if CompileOption::takes_value(arg) {
+                match args.next() {
+                    Some(value) => format!("{}={}", arg, value),
+                    None => return CmdArgsKind::Error,
+                }
+            } else {
+                arg.to_string()
+            };
+            if !option.parse_arg(&arg) {
+                return CmdArgsKind::Error;
+            }
+        } else {
+            file_names.push(arg.to_string());
+        }
+    }
+    match file_names.len() {
+        1 => CmdArgsKind::Compile(file_names[0].clone(), None, option),
+        2 => CmdArgsKind::Compile(file_names[0].clone(), Some(file_names[1].clone()), option),
+        _ => CmdArgsKind::Error,
+    }
+}
@@ -0,0 +1,44 @@
+use super::ir::mir::*;
+use std::collections::HashMap;
+
+//カリー化された関数が本来いくつの引数を取るかを解析する
+//f x y z = ... はカリー化で f = \x -> \y -> \z -> ... になっているので、本体がラムダ式である限りたどる
+pub fn analyze_arity(func_list: &mut HashMap<String, FuncMir>) {
+    let arity_list = func_list.values()
+        //ラムダ式の関数は#から始まる。mainはCの関数なので対象外
+        .filter(|x| !x.name.starts_with('#') && x.name != "main")
+        .filter_map(|x| arity(x, func_list).map(|arity| (x.name.clone(), arity)))
+        .collect::<Vec<_>>();
+    for (name, arity) in arity_list {
+        func_list.get_mut(&name).unwrap().arity = Some(arity);
+    }
+}
+
+fn arity(func: &FuncMir, func_list: &HashMap<String, FuncMir>) -> Option<ArityMir> {
+    if func.params_len != 1 {
+        return None;
+    }
+    let name = func.name.clone();
+    let mut func = func;
+    //今の関数の引数(環境と引数)がそれぞれ何番目の引数か
+    let mut arg_indices = vec![0];
+    let mut arity = 1;
+    while let ExprMir::LambdaMir(ref lambda) = func.body {
+        let inner = &func_list[&lambda.func_name];
+        if inner.params_len != lambda.env.len() + 1 {
+            break;
+        }
+        //環境の変数は外側の関数の引数をde bruijn indexで指している
+        let mut inner_arg_indices: Vec<usize> = lambda.env.iter()
+            .map(|x| arg_indices[arg_indices.len() - x.id - 1])
+            .collect();
+        inner_arg_indices.push(arity);
+        arg_indices = inner_arg_indices;
+        arity += 1;
+        func = inner;
+    }
+    if arity == 1 {
+        return None;
+    }
+    Some(ArityMir { name, func_name: func.name.clone(), arity, arg_indices })
+}
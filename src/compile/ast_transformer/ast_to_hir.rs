@@ -9,48 +9,99 @@ type AstToHirResult<T> = Result<T, Error>;
 impl ProgramAST {
     //ASTをHIRに変換
     pub fn to_hir(self) -> AstToHirResult<ProgramHir> {
-        let mut program_hir = ProgramHir {
+        let program_hir = self.to_lib_hir()?;
+        if program_hir.def_func_list.contains_key("main") {
+            Ok(program_hir)
+        } else {
+            Err(Error::new(SourcePosition::new(), "not found main function!"))
+        }
+    }
+
+    //mainの存在チェックをせずにHIRに変換
+    pub fn to_lib_hir(self) -> AstToHirResult<ProgramHir> {
+        let mut program_hir = ProgramHir::new();
+        let mut last_func_name = None;
+        for stmt in self.stmt_list.into_iter() {
+            match stmt {
+                //同じ名前の関数の節は続けて書く
+                StmtAST::DefFuncAST(ref x) => {
+                    if program_hir.def_func_list.contains_key(&x.name) && last_func_name.as_ref() != Some(&x.name) {
+                        return Err(Error::new(x.pos, "Duplicate function"));
+                    }
+                    last_func_name = Some(x.name.clone());
+                }
+                StmtAST::NoneAST => (),
+                _ => last_func_name = None,
+            }
+            program_hir.add_stmt(stmt)?;
+        };
+        Ok(program_hir)
+    }
+}
+
+impl ProgramHir {
+    pub fn new() -> ProgramHir {
+        ProgramHir {
             infix_list: HashMap::new(),
             dec_func_list: HashMap::new(),
             def_func_list: HashMap::new(),
             ex_dec_func_list: HashMap::new(),
             struct_list: HashMap::new(),
-        };
-        for stmt in self.stmt_list.into_iter() {
-            match stmt {
-                StmtAST::InfixAST(x) => { program_hir.infix_list.insert(x.op.clone(), x); }
-                StmtAST::DefFuncAST(x) => {
-                    if program_hir.def_func_list.contains_key(&x.name) {
-                        return Err(Error::new(x.pos, &"Duplicate function"));
+            warning_list: vec![],
+        }
+    }
+
+    //文をHIRに登録する
+    pub fn add_stmt(&mut self, stmt: StmtAST) -> AstToHirResult<()> {
+        match stmt {
+            StmtAST::InfixAST(x) => { self.infix_list.insert(x.op.clone(), x); }
+            StmtAST::DefFuncAST(x) => {
+                //同じ名前の関数は節として1つの関数にまとめる
+                let f = match self.def_func_list.remove(&x.name) {
+                    Some(f) => f.add_clauses(x)?,
+                    None => x
+                };
+                self.def_func_list.insert(f.name.clone(), f);
+            }
+            StmtAST::DecFuncAST(x) => {
+                if x.extern_flag {
+                    if self.ex_dec_func_list.contains_key(&x.name) {
+                        return Err(Error::new(x.pos, &"Duplicate function declare"));
                     }
-                    program_hir.def_func_list.insert(x.name.clone(), x);
-                }
-                StmtAST::DecFuncAST(x) => {
-                    if x.extern_flag {
-                        if program_hir.ex_dec_func_list.contains_key(&x.name) {
-                            return Err(Error::new(x.pos, &"Duplicate function declare"));
-                        }
-                        program_hir.ex_dec_func_list.insert(x.name.clone(), x);
-                    } else {
-                        if program_hir.dec_func_list.contains_key(&x.name) {
-                            return Err(Error::new(x.pos, "Duplicate function declare"));
-                        }
-                        program_hir.dec_func_list.insert(x.name.clone(), x);
+                    self.ex_dec_func_list.insert(x.name.clone(), x);
+                } else {
+                    if self.dec_func_list.contains_key(&x.name) {
+                        return Err(Error::new(x.pos, "Duplicate function declare"));
                     }
+                    self.dec_func_list.insert(x.name.clone(), x);
                 }
-                StmtAST::NoneAST => (),
-                StmtAST::DecStructAST(x) => {
-                    if program_hir.struct_list.contains_key(&x.ty.name) {
-                        return Err(Error::new(x.pos, &"Duplicate struct declare"));
-                    }
-                    program_hir.struct_list.insert(x.ty.name.clone(), x);
+            }
+            StmtAST::NoneAST => (),
+            StmtAST::DecStructAST(x) => {
+                if self.struct_list.contains_key(&x.ty.name) {
+                    return Err(Error::new(x.pos, &"Duplicate struct declare"));
                 }
-            };
+                self.struct_list.insert(x.ty.name.clone(), x);
+            }
         };
-        if program_hir.def_func_list.contains_key("main") {
-            Ok(program_hir)
-        } else {
-            Err(Error::new(SourcePosition::new(), "not found main function!"))
+        Ok(())
+    }
+}
+
+impl DefFuncAST {
+    //同じ名前の関数の定義を後ろの節として追加する
+    pub fn add_clauses(self, x: DefFuncAST) -> AstToHirResult<DefFuncAST> {
+        if self.params.len() == 0 {
+            return Err(Error::new(x.pos, &"Duplicate function"));
+        }
+        if self.params.len() != x.params.len() {
+            return Err(Error::new(x.pos, "clauses have different numbers of parameters"));
         }
+        let (name, params_len, pos) = (self.name.clone(), self.params.len(), self.pos);
+        //ドキュメントコメントは最初に書かれたものを使う
+        let doc = self.doc.clone().or(x.doc.clone());
+        let mut clauses = self.into_clauses();
+        clauses.append(&mut x.into_clauses());
+        Ok(DefFuncAST { doc, ..DefFuncAST::from_clauses(name, params_len, clauses, pos) })
     }
-}
\ No newline at end of file
+}
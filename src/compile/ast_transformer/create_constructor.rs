@@ -24,7 +24,9 @@ impl ProgramHir {
                                 name: k.clone(),
                                 pos: SourcePosition::new(),
                                 params: params.clone(),
+                                patterns: vec![],
                                 body: ExprAST::create_tuple_struct_ast(params.into_iter().map(|x| ExprAST::VariableAST(x)).collect(), v.ty.clone()),
+                                doc: None,
                             },
                         )
                     });
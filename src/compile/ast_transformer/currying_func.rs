@@ -21,23 +21,23 @@ impl DefFuncHir {
             self.params.push(VariableAST { pos: self.pos, id: "_".to_string() });
         }
         let first_param = self.params[0].clone();
-        self.body = self.body.currying(self.params.into_iter().skip(1), vec![first_param.clone()]);
+        self.body = self.body.currying(self.params.into_iter().skip(1), None);
         self.params = vec![first_param];
         self
     }
 }
 
 impl ExprAST {
-    fn currying<I: Iterator<Item=VariableAST>>(self, mut iter: I, env: Vec<VariableAST>) -> ExprAST {
+    //生成したラムダ式の環境はhir_to_mirで自由変数から求める
+    fn currying<I: Iterator<Item=VariableAST>>(self, mut iter: I, env: Option<Vec<VariableAST>>) -> ExprAST {
         match iter.next() {
             Some(v) => {
-                let mut next_env = env.clone();
-                next_env.push(v.clone());
                 ExprAST::LambdaAST(Box::new(LambdaAST {
                     pos: self.get_pos(),
-                    body: self.currying(iter, next_env),
+                    body: self.currying(iter, None),
                     env,
                     params: vec![v],
+                    patterns: vec![],
                 }))
             }
             _ => match self {
@@ -47,48 +47,66 @@ impl ExprAST {
                 }
                 ExprAST::TupleAST(mut x) => {
                     x.as_mut().elements = x.to_owned().elements.into_iter()
-                        .map(|x| x.currying(vec![].into_iter(), vec![]))
+                        .map(|x| x.currying(vec![].into_iter(), None))
                         .collect();
                     ExprAST::TupleAST(x)
                 }
                 ExprAST::FuncCallAST(x) => {
                     let mut x = *x;
-                    x.func = x.func.currying(vec![].into_iter(), vec![]);
-                    x.param = x.param.currying(vec![].into_iter(), vec![]);
+                    x.func = x.func.currying(vec![].into_iter(), None);
+                    x.param = x.param.currying(vec![].into_iter(), None);
                     ExprAST::FuncCallAST(Box::new(x))
                 }
                 ExprAST::OpAST(x) => {
                     let mut x = *x;
-                    x.l_expr = x.l_expr.currying(vec![].into_iter(), vec![]);
-                    x.r_expr = x.r_expr.currying(vec![].into_iter(), vec![]);
+                    x.l_expr = x.l_expr.currying(vec![].into_iter(), None);
+                    x.r_expr = x.r_expr.currying(vec![].into_iter(), None);
                     ExprAST::OpAST(Box::new(x))
                 }
-                ExprAST::ParenAST(x) => x.expr.currying(vec![].into_iter(), vec![]),
+                ExprAST::ParenAST(x) => x.expr.currying(vec![].into_iter(), None),
                 ExprAST::IfAST(x) => {
                     let mut x = *x;
-                    x.cond = x.cond.currying(vec![].into_iter(), vec![]);
-                    x.t_expr = x.t_expr.currying(vec![].into_iter(), vec![]);
-                    x.f_expr = x.f_expr.currying(vec![].into_iter(), vec![]);
+                    x.cond = x.cond.currying(vec![].into_iter(), None);
+                    x.t_expr = x.t_expr.currying(vec![].into_iter(), None);
+                    x.f_expr = x.f_expr.currying(vec![].into_iter(), None);
                     ExprAST::IfAST(Box::new(x))
                 }
                 ExprAST::NamedParamsConstructorCallAST(_) => panic!("bug"),
+                ExprAST::RecordUpdateAST(x) => {
+                    let mut x = *x;
+                    x.expr = x.expr.currying(vec![].into_iter(), None);
+                    x.params = x.params.into_iter()
+                        .map(|(name, e)| (name, e.currying(vec![].into_iter(), None)))
+                        .collect();
+                    ExprAST::RecordUpdateAST(Box::new(x))
+                }
+                ExprAST::MatchAST(_) => panic!("bug"),
+                ExprAST::PatternCheckAST(x) => {
+                    let mut x = *x;
+                    x.checks = x.checks.into_iter()
+                        .map(|(e, shape)| (e.currying(vec![].into_iter(), None), shape))
+                        .collect();
+                    x.body = x.body.currying(vec![].into_iter(), None);
+                    ExprAST::PatternCheckAST(Box::new(x))
+                }
                 ExprAST::TupleStructAST(mut x) => {
                     x.tuple.elements = x.to_owned().tuple.elements.into_iter()
-                        .map(|x| x.currying(vec![].into_iter(), vec![]))
+                        .map(|x| x.currying(vec![].into_iter(), None))
                         .collect();
                     ExprAST::TupleStructAST(x)
                 }
                 ExprAST::IndexPropertyAST(mut x) => {
-                    x.expr = x.to_owned().expr.currying(vec![].into_iter(), vec![]);
+                    x.expr = x.to_owned().expr.currying(vec![].into_iter(), None);
                     ExprAST::IndexPropertyAST(x)
                 }
                 ExprAST::NamePropertyAST(mut x) => {
-                    x.expr = x.to_owned().expr.currying(vec![].into_iter(), vec![]);
+                    x.expr = x.to_owned().expr.currying(vec![].into_iter(), None);
                     ExprAST::NamePropertyAST(x)
                 }
                 ExprAST::NumAST(_) |
                 ExprAST::BoolAST(_) |
-                ExprAST::VariableAST(_) => self
+                ExprAST::VariableAST(_) |
+                ExprAST::PanicAST(_) => self
             }
         }
     }
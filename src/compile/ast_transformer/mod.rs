@@ -2,6 +2,8 @@ mod resolve_op;
 mod currying_func;
 mod ast_to_hir;
 mod resolve_named_params_constructor_call;
+mod resolve_pattern_params;
+mod resolve_where;
 mod create_constructor;
 
 use super::ir::ast::ProgramAST;
@@ -10,6 +12,12 @@ use super::error::Error;
 
 impl ProgramAST {
     pub fn ast_transformer(self) -> Result<ProgramHir, Error> {
-        Ok(self.to_hir()?.resolve_op()?.resolve_named_params_constructor_call()?.create_constructor().currying())
+        self.to_hir()?.hir_transformer()
+    }
+}
+
+impl ProgramHir {
+    pub fn hir_transformer(self) -> Result<ProgramHir, Error> {
+        Ok(self.resolve_where()?.resolve_op()?.resolve_named_params_constructor_call()?.resolve_pattern_params()?.create_constructor().currying())
     }
 }
\ No newline at end of file
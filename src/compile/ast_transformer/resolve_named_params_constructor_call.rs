@@ -8,6 +8,9 @@ type ResolveResult<T> = Result<T, Error>;
 //struct RGB{ b:Int32 , g:Int32 , r:Int32 }
 //RGB {r:5,g:3,b:2}のようなレコード構造体生成関数呼び出しを
 //RGB 2 3 5のように脱糖衣する
+//
+//c { g = 0 }のようなレコード更新式は、cの型が型推論で決まるまで構造体が分からないので
+//フィールド名のままhir_to_mirに渡す
 
 
 impl ProgramHir {
@@ -69,7 +72,36 @@ impl ExprAST {
                     }
                 }
             }
-            ExprAST::BoolAST(_) | ExprAST::NumAST(_) | ExprAST::VariableAST(_) => (),
+            ExprAST::RecordUpdateAST(x) => {
+                let mut x = *x;
+                x.expr = x.expr.resolve_named_params_constructor_call(struct_list)?;
+                x.params = x.params.into_iter()
+                    .map(|(name, e)| Ok((name, e.resolve_named_params_constructor_call(struct_list)?)))
+                    .collect::<ResolveResult<Vec<_>>>()?;
+                for (idx, (name, _)) in x.params.iter().enumerate() {
+                    if x.params[..idx].iter().any(|(x, _)| x == name) {
+                        return Err(Error::new(x.pos, &format!("record field `{}` is updated twice", name)));
+                    }
+                }
+                self = ExprAST::RecordUpdateAST(Box::new(x));
+            }
+            ExprAST::MatchAST(x) => {
+                let mut x = *x;
+                x.clauses = x.clauses.into_iter()
+                    .map(|mut clause| {
+                        clause.guards = clause.guards.into_iter()
+                            .map(|(guard, e)| Ok((
+                                guard.resolve_named_params_constructor_call(struct_list)?,
+                                e.resolve_named_params_constructor_call(struct_list)?
+                            )))
+                            .collect::<ResolveResult<Vec<_>>>()?;
+                        Ok(clause)
+                    })
+                    .collect::<ResolveResult<Vec<_>>>()?;
+                self = ExprAST::MatchAST(Box::new(x));
+            }
+            ExprAST::BoolAST(_) | ExprAST::NumAST(_) | ExprAST::VariableAST(_) | ExprAST::PanicAST(_) => (),
+            ExprAST::PatternCheckAST(_) => panic!("bug"),
             ExprAST::FuncCallAST(x) => {
                 let mut x = *x;
                 x.func = x.func.resolve_named_params_constructor_call(struct_list)?;
@@ -134,6 +134,30 @@ impl ExprAST {
                     .collect::<ResolveResult<Vec<(String, ExprAST)>>>()?;
                 Resolved::OtherExprAST(ExprAST::NamedParamsConstructorCallAST(Box::new(x)))
             }
+            ExprAST::RecordUpdateAST(x) => {
+                let mut x = *x;
+                x.expr = x.expr.resolve_op(infix_hash)?.get_expr_ast();
+                x.params = x.params.into_iter()
+                    .map(|(name, e)|
+                        Ok((name, e.resolve_op(infix_hash)?.get_expr_ast()))
+                    )
+                    .collect::<ResolveResult<Vec<(String, ExprAST)>>>()?;
+                Resolved::OtherExprAST(ExprAST::RecordUpdateAST(Box::new(x)))
+            }
+            ExprAST::MatchAST(x) => {
+                let mut x = *x;
+                x.clauses = x.clauses.into_iter()
+                    .map(|mut clause| {
+                        clause.guards = clause.guards.into_iter()
+                            .map(|(guard, e)|
+                                Ok((guard.resolve_op(infix_hash)?.get_expr_ast(), e.resolve_op(infix_hash)?.get_expr_ast()))
+                            )
+                            .collect::<ResolveResult<Vec<_>>>()?;
+                        Ok(clause)
+                    })
+                    .collect::<ResolveResult<Vec<_>>>()?;
+                Resolved::OtherExprAST(ExprAST::MatchAST(Box::new(x)))
+            }
             ExprAST::IndexPropertyAST(x) => {
                 let mut x = *x;
                 x.expr = x.expr.resolve_op(infix_hash)?.get_expr_ast();
@@ -144,7 +168,7 @@ impl ExprAST {
                 x.expr = x.expr.resolve_op(infix_hash)?.get_expr_ast();
                 Resolved::OtherExprAST(ExprAST::NamePropertyAST(Box::new(x)))
             }
-            ExprAST::NumAST(_) | ExprAST::BoolAST(_) | ExprAST::VariableAST(_) => Resolved::OtherExprAST(self),
+            ExprAST::NumAST(_) | ExprAST::BoolAST(_) | ExprAST::VariableAST(_) | ExprAST::PanicAST(_) => Resolved::OtherExprAST(self),
             _ => panic!("undefined")
         };
         Ok(resolved)
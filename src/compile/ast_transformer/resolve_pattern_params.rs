@@ -0,0 +1,374 @@
+use super::super::ir::ast::*;
+use super::super::ir::hir::*;
+use super::super::Error;
+use std::collections::HashMap;
+use combine::stream::state::SourcePosition;
+
+type ResolveResult<T> = Result<T, Error>;
+//パターンの変数名 => 値を取り出す式
+type Bindings = HashMap<String, ExprAST>;
+
+//swap (a, b) = (b, a)のような引数のパターンを
+//swap #pattern0 = (#pattern0.1, #pattern0.0)
+//のようにプロパティアクセスに脱糖衣する
+//引数の型はPatternCheckASTでパターンと同じ要素数のタプルや構造体に固定する
+//fact 0 = 1のようなリテラルのパターンやガードは、上の節から順に調べるifの連鎖にする
+//全ての値に合うとは限らないパターンは警告する
+
+impl ProgramHir {
+    pub fn resolve_pattern_params(mut self) -> ResolveResult<ProgramHir> {
+        let struct_list = self.struct_list;
+        let mut pattern_count = 0;
+        let warning_list = &mut self.warning_list;
+        self.def_func_list =
+            self.def_func_list.into_iter()
+                .map(|(k, f)| Ok((k, f.resolve_pattern_params(&struct_list, &mut pattern_count, warning_list)?)))
+                .collect::<ResolveResult<HashMap<String, DefFuncHir>>>()?;
+        self.struct_list = struct_list;
+        Ok(self)
+    }
+}
+
+impl DefFuncHir {
+    fn resolve_pattern_params(mut self, struct_list: &HashMap<String, DecStructHir>, pattern_count: &mut usize, warning_list: &mut Vec<Error>) -> ResolveResult<DefFuncHir> {
+        let body = self.body.resolve_pattern_params(struct_list, pattern_count, warning_list)?;
+        let patterns = self.patterns;
+        self.patterns = vec![];
+        self.body = bind_patterns(&mut self.params, patterns, body, struct_list, pattern_count, warning_list)?;
+        Ok(self)
+    }
+}
+
+//パターンの引数に名前を付けて、本体の中のパターンの変数を置き換える
+fn bind_patterns(
+    params: &mut Vec<VariableAST>,
+    patterns: Vec<(usize, PatternAST)>,
+    body: ExprAST,
+    struct_list: &HashMap<String, DecStructHir>,
+    pattern_count: &mut usize,
+    warning_list: &mut Vec<Error>,
+) -> ResolveResult<ExprAST> {
+    if patterns.is_empty() {
+        return Ok(body);
+    }
+    let pos = patterns[0].1.get_pos();
+    let mut bindings = HashMap::new();
+    let mut checks = vec![];
+    let mut tests = vec![];
+    for (idx, pattern) in patterns {
+        let name = format!("#pattern{}", pattern_count);
+        *pattern_count += 1;
+        params[idx].id = name.clone();
+        let expr = ExprAST::create_variable_ast(name, params[idx].pos);
+        pattern.bind(expr, struct_list, &mut bindings, &mut checks, &mut tests)?;
+    }
+    if let Some(param) = params.iter().find(|x| bindings.contains_key(&x.id)) {
+        return Err(Error::new(param.pos, &format!("duplicate pattern variable `{}`", param.id)));
+    }
+    if tests.is_empty() == false {
+        warning_list.push(Error::new(pos, "non-exhaustive patterns"));
+    }
+    let body = body.substitute(&bindings)?;
+    let body = match and_all(tests, pos) {
+        Some(cond) => ExprAST::create_if_ast(cond, body, non_exhaustive(pos), pos),
+        None => body
+    };
+    Ok(with_checks(body, checks, pos))
+}
+
+//節を上から順に調べるifの連鎖を作る
+fn resolve_match(
+    x: MatchAST,
+    struct_list: &HashMap<String, DecStructHir>,
+    pattern_count: &mut usize,
+    warning_list: &mut Vec<Error>,
+) -> ResolveResult<ExprAST> {
+    let mut checks = vec![];
+    //(条件, 式) 条件がNoneなら必ず合う
+    let mut branches = vec![];
+    //ガードが必ず成り立つ節のパターン
+    let rows = x.clauses.iter()
+        .filter(|clause| clause.guards.iter().any(|(guard, _)| is_true(guard)))
+        .map(|clause| clause.patterns.iter().map(|pattern| Cover::new(pattern, struct_list)).collect())
+        .collect();
+    if is_exhaustive(rows) == false {
+        warning_list.push(Error::new(x.pos, "non-exhaustive patterns"));
+    }
+    for clause in x.clauses {
+        let mut bindings = HashMap::new();
+        let mut tests = vec![];
+        for (pattern, expr) in clause.patterns.into_iter().zip(x.exprs.iter()) {
+            pattern.bind(expr.clone(), struct_list, &mut bindings, &mut checks, &mut tests)?;
+        }
+        let cond = and_all(tests, clause.pos);
+        for (guard, body) in clause.guards {
+            let guard = guard.resolve_pattern_params(struct_list, pattern_count, warning_list)?.substitute(&bindings)?;
+            let body = body.resolve_pattern_params(struct_list, pattern_count, warning_list)?.substitute(&bindings)?;
+            let cond = match guard {
+                ref guard if is_true(guard) => cond.clone(),
+                guard => and_all(cond.clone().into_iter().chain(vec![guard]).collect(), clause.pos),
+            };
+            branches.push((cond, body));
+        }
+    }
+    let expr = branches.into_iter().rev()
+        .fold(non_exhaustive(x.pos), |acc, (cond, body)| match cond {
+            Some(cond) => ExprAST::create_if_ast(cond, body, acc, x.pos),
+            None => body
+        });
+    Ok(with_checks(expr, checks, x.pos))
+}
+
+fn is_true(guard: &ExprAST) -> bool {
+    match guard {
+        ExprAST::BoolAST(x) => x.bool,
+        _ => false
+    }
+}
+
+//網羅性を調べるためのパターンの形
+#[derive(Clone)]
+enum Cover {
+    Any,
+    Num(i32),
+    Bool(bool),
+    //構造体はフィールドを宣言の順に並べたタプルとして扱う
+    Tuple(Vec<Cover>),
+}
+
+impl Cover {
+    fn new(pattern: &PatternAST, struct_list: &HashMap<String, DecStructHir>) -> Cover {
+        match pattern {
+            PatternAST::VariableAST(_) => Cover::Any,
+            PatternAST::NumAST(x) => Cover::Num(x.num),
+            PatternAST::BoolAST(x) => Cover::Bool(x.bool),
+            PatternAST::TupleAST(x) => Cover::Tuple(x.elements.iter().map(|x| Cover::new(x, struct_list)).collect()),
+            PatternAST::RecordAST(x) => match struct_list.get(&x.constructor_name).map(|y| &y.ty.ty) {
+                Some(StructInternalTypeAST::RecordTypeAST(y)) => Cover::Tuple(
+                    y.elements_ty.iter()
+                        .map(|(name, _)| match x.fields.iter().find(|(field_name, _)| field_name == name) {
+                            Some((_, pattern)) => Cover::new(pattern, struct_list),
+                            None => Cover::Any
+                        })
+                        .collect()
+                ),
+                //存在しない構造体のエラーはbindで報告する
+                _ => Cover::Any
+            }
+        }
+    }
+}
+
+//パターンを並べた行のどれかに全ての値が合うか調べる
+fn is_exhaustive(rows: Vec<Vec<Cover>>) -> bool {
+    if rows.is_empty() {
+        return false;
+    }
+    if rows[0].is_empty() {
+        return true;
+    }
+    //先頭の列にタプルがあれば要素の列に展開する
+    let tuple_len = rows.iter()
+        .filter_map(|row| match row[0] {
+            Cover::Tuple(ref x) => Some(x.len()),
+            _ => None
+        })
+        .next();
+    if let Some(len) = tuple_len {
+        return is_exhaustive(rows.into_iter()
+            .map(|mut row| {
+                let elements = match row.remove(0) {
+                    Cover::Tuple(x) => x,
+                    _ => vec![Cover::Any; len]
+                };
+                elements.into_iter().chain(row).collect()
+            })
+            .collect());
+    }
+    //trueとfalseが両方あれば、それぞれの場合を調べる
+    let has_bool = |b| rows.iter().any(|row| match row[0] {
+        Cover::Bool(x) => x == b,
+        _ => false
+    });
+    if has_bool(true) && has_bool(false) {
+        return [true, false].iter().all(|&b| is_exhaustive(rows.iter()
+            .filter(|row| match row[0] {
+                Cover::Bool(x) => x == b,
+                Cover::Any => true,
+                _ => false
+            })
+            .map(|row| row[1..].to_vec())
+            .collect()));
+    }
+    //数値は全ての値を並べられないので、何にでも合う行だけを調べる
+    is_exhaustive(rows.iter()
+        .filter(|row| match row[0] {
+            Cover::Any => true,
+            _ => false
+        })
+        .map(|row| row[1..].to_vec())
+        .collect())
+}
+
+//条件を全て満たすか調べる式を作る
+fn and_all(tests: Vec<ExprAST>, pos: SourcePosition) -> Option<ExprAST> {
+    tests.into_iter().fold(None, |acc, test| Some(match acc {
+        Some(acc) => ExprAST::create_if_ast(acc, test, ExprAST::create_bool_ast(false, pos), pos),
+        None => test
+    }))
+}
+
+fn non_exhaustive(pos: SourcePosition) -> ExprAST {
+    ExprAST::PanicAST(PanicAST { message: "non-exhaustive patterns".to_string(), pos })
+}
+
+//パターンの形に引数の型を固定してから評価する
+fn with_checks(expr: ExprAST, checks: Vec<(ExprAST, PatternShapeAST)>, pos: SourcePosition) -> ExprAST {
+    if checks.is_empty() {
+        return expr;
+    }
+    ExprAST::PatternCheckAST(Box::new(PatternCheckAST { checks, body: expr, pos }))
+}
+
+impl PatternAST {
+    //exprの値をパターンで分解する
+    //checksには型をパターンの形に固定する式と形を、testsにはパターンに合うか調べる式を追加する
+    fn bind(
+        self,
+        expr: ExprAST,
+        struct_list: &HashMap<String, DecStructHir>,
+        bindings: &mut Bindings,
+        checks: &mut Vec<(ExprAST, PatternShapeAST)>,
+        tests: &mut Vec<ExprAST>,
+    ) -> ResolveResult<()> {
+        match self {
+            PatternAST::NumAST(x) => {
+                let pos = x.pos;
+                tests.push(ExprAST::create_op_ast("==".to_string(), pos, expr, ExprAST::NumAST(x)));
+            }
+            PatternAST::BoolAST(x) => {
+                if x.bool {
+                    tests.push(expr);
+                } else {
+                    tests.push(ExprAST::create_if_ast(expr, ExprAST::create_bool_ast(false, x.pos), ExprAST::create_bool_ast(true, x.pos), x.pos));
+                }
+            }
+            PatternAST::VariableAST(x) => {
+                if bindings.contains_key(&x.id) {
+                    return Err(Error::new(x.pos, &format!("duplicate pattern variable `{}`", x.id)));
+                }
+                bindings.insert(x.id, expr);
+            }
+            PatternAST::TupleAST(x) => {
+                let x = *x;
+                let elements = (0..x.elements.len())
+                    .map(|idx| ExprAST::create_property_ast(idx.to_string(), expr.clone(), x.pos))
+                    .collect::<Vec<_>>();
+                checks.push((expr.clone(), PatternShapeAST::TupleAST(x.elements.len())));
+                for (pattern, element) in x.elements.into_iter().zip(elements) {
+                    pattern.bind(element, struct_list, bindings, checks, tests)?;
+                }
+            }
+            PatternAST::RecordAST(x) => {
+                let x = *x;
+                if struct_list.contains_key(&x.constructor_name) == false { return Err(Error::new(x.pos, "not found constructor")); }
+                let record_ty = match struct_list[&x.constructor_name].ty.ty {
+                    StructInternalTypeAST::TupleTypeAST(_) => return Err(Error::new(x.pos, "not RecordStruct")),
+                    StructInternalTypeAST::RecordTypeAST(ref y) => y,
+                };
+                checks.push((expr.clone(), PatternShapeAST::RecordAST(x.constructor_name.clone())));
+                for (name, pattern) in x.fields {
+                    if record_ty.elements_ty.iter().all(|(field_name, _)| *field_name != name) {
+                        return Err(Error::new(pattern.get_pos(), "not found record name"));
+                    }
+                    let element = ExprAST::create_property_ast(name, expr.clone(), x.pos);
+                    pattern.bind(element, struct_list, bindings, checks, tests)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ExprAST {
+    fn resolve_pattern_params(self, struct_list: &HashMap<String, DecStructHir>, pattern_count: &mut usize, warning_list: &mut Vec<Error>) -> ResolveResult<ExprAST> {
+        Ok(match self {
+            ExprAST::LambdaAST(x) => {
+                let mut x = *x;
+                let body = x.body.resolve_pattern_params(struct_list, pattern_count, warning_list)?;
+                let patterns = x.patterns;
+                x.patterns = vec![];
+                x.body = bind_patterns(&mut x.params, patterns, body, struct_list, pattern_count, warning_list)?;
+                ExprAST::LambdaAST(Box::new(x))
+            }
+            ExprAST::MatchAST(x) => resolve_match(*x, struct_list, pattern_count, warning_list)?,
+            ExprAST::BoolAST(_) | ExprAST::NumAST(_) | ExprAST::VariableAST(_) | ExprAST::PanicAST(_) => self,
+            ExprAST::FuncCallAST(x) => {
+                let mut x = *x;
+                x.func = x.func.resolve_pattern_params(struct_list, pattern_count, warning_list)?;
+                x.param = x.param.resolve_pattern_params(struct_list, pattern_count, warning_list)?;
+                ExprAST::FuncCallAST(Box::new(x))
+            }
+            ExprAST::IfAST(x) => {
+                let mut x = *x;
+                x.cond = x.cond.resolve_pattern_params(struct_list, pattern_count, warning_list)?;
+                x.t_expr = x.t_expr.resolve_pattern_params(struct_list, pattern_count, warning_list)?;
+                x.f_expr = x.f_expr.resolve_pattern_params(struct_list, pattern_count, warning_list)?;
+                ExprAST::IfAST(Box::new(x))
+            }
+            ExprAST::OpAST(x) => {
+                let mut x = *x;
+                x.l_expr = x.l_expr.resolve_pattern_params(struct_list, pattern_count, warning_list)?;
+                x.r_expr = x.r_expr.resolve_pattern_params(struct_list, pattern_count, warning_list)?;
+                ExprAST::OpAST(Box::new(x))
+            }
+            ExprAST::ParenAST(x) => {
+                let mut x = *x;
+                x.expr = x.expr.resolve_pattern_params(struct_list, pattern_count, warning_list)?;
+                ExprAST::ParenAST(Box::new(x))
+            }
+            ExprAST::TupleAST(x) => {
+                let mut x = *x;
+                x.elements = x.elements.into_iter()
+                    .map(|x| x.resolve_pattern_params(struct_list, pattern_count, warning_list))
+                    .collect::<ResolveResult<Vec<_>>>()?;
+                ExprAST::TupleAST(Box::new(x))
+            }
+            ExprAST::TupleStructAST(x) => {
+                let mut x = *x;
+                x.tuple.elements = x.tuple.elements.into_iter()
+                    .map(|x| x.resolve_pattern_params(struct_list, pattern_count, warning_list))
+                    .collect::<ResolveResult<Vec<_>>>()?;
+                ExprAST::TupleStructAST(Box::new(x))
+            }
+            ExprAST::IndexPropertyAST(x) => {
+                let mut x = *x;
+                x.expr = x.expr.resolve_pattern_params(struct_list, pattern_count, warning_list)?;
+                ExprAST::IndexPropertyAST(Box::new(x))
+            }
+            ExprAST::NamePropertyAST(x) => {
+                let mut x = *x;
+                x.expr = x.expr.resolve_pattern_params(struct_list, pattern_count, warning_list)?;
+                ExprAST::NamePropertyAST(Box::new(x))
+            }
+            ExprAST::RecordUpdateAST(x) => {
+                let mut x = *x;
+                x.expr = x.expr.resolve_pattern_params(struct_list, pattern_count, warning_list)?;
+                x.params = x.params.into_iter()
+                    .map(|(name, e)| Ok((name, e.resolve_pattern_params(struct_list, pattern_count, warning_list)?)))
+                    .collect::<ResolveResult<Vec<_>>>()?;
+                ExprAST::RecordUpdateAST(Box::new(x))
+            }
+            ExprAST::NamedParamsConstructorCallAST(_) | ExprAST::PatternCheckAST(_) => panic!("bug"),
+        })
+    }
+
+    //パターンの変数を値を取り出す式に置き換える
+    //ラムダ式で明示的にキャプチャしたパターンの変数は、パターンの引数をキャプチャする
+    fn substitute(self, bindings: &Bindings) -> ResolveResult<ExprAST> {
+        self.replace_variables(&mut vec![], &mut |x, _: &Vec<String>| Ok(match bindings.get(&x.id) {
+            Some(expr) => expr.clone(),
+            None => ExprAST::VariableAST(x)
+        }))
+    }
+}
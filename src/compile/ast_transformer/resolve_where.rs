@@ -0,0 +1,200 @@
+use super::super::ir::ast::*;
+use super::super::ir::hir::*;
+use super::super::Error;
+use std::collections::HashMap;
+
+type ResolveResult<T> = Result<T, Error>;
+//局所関数の名前 => 持ち上げた関数
+type LocalFuncs = HashMap<String, LocalFunc>;
+
+struct LocalFunc {
+    name: String,
+    //引数の前に追加した外側の変数(#f#xのような内部の名前)
+    captures: Vec<String>,
+}
+
+//f x = g 1 where { g y = x + y; }のような局所関数を
+//f #f#x = f#g #f#x 1;
+//f#g #f#x y = #f#x + y;
+//のようにグローバル関数に持ち上げる
+//局所関数が使う外側の変数は引数の前に追加し、部分適用してクロージャの環境として渡す
+//キャプチャする変数は節の中で#f#xのような隠れない名前に変えるので、(\x -> g x)のように内側で隠れていても外側の値を渡せる
+
+impl ProgramHir {
+    pub fn resolve_where(mut self) -> ResolveResult<ProgramHir> {
+        let mut def_func_list = self.def_func_list.drain().map(|(_, f)| f).collect::<Vec<_>>();
+        //持ち上げた関数の中のwhereも順に処理する
+        while let Some(f) = def_func_list.pop() {
+            let (f, mut lifted) = f.resolve_where()?;
+            def_func_list.append(&mut lifted);
+            self.def_func_list.insert(f.name.clone(), f);
+        }
+        Ok(self)
+    }
+}
+
+impl DefFuncHir {
+    //whereの局所関数を取り除いて、持ち上げた関数を返す
+    fn resolve_where(mut self) -> ResolveResult<(DefFuncHir, Vec<DefFuncHir>)> {
+        let mut x = match self.body {
+            ExprAST::MatchAST(x) => *x,
+            body => {
+                self.body = body;
+                return Ok((self, vec![]));
+            }
+        };
+        let mut lifted = vec![];
+        let mut used_names = vec![];
+        //持ち上げた関数ではキャプチャした変数も引数になっている
+        let params = self.params.iter().map(|x| x.id.clone()).collect::<Vec<_>>();
+        x.clauses = x.clauses.into_iter()
+            .map(|clause| clause.resolve_where(&self.name, &params, &mut used_names, &mut lifted))
+            .collect::<ResolveResult<Vec<_>>>()?;
+        self.body = ExprAST::MatchAST(Box::new(x));
+        Ok((self, lifted))
+    }
+}
+
+impl ClauseAST {
+    fn resolve_where(
+        mut self,
+        outer_name: &String,
+        params: &Vec<String>,
+        used_names: &mut Vec<String>,
+        lifted: &mut Vec<DefFuncHir>,
+    ) -> ResolveResult<ClauseAST> {
+        if self.where_list.is_empty() {
+            return Ok(self);
+        }
+        //続けて書いた同じ名前の局所関数は節としてまとめる
+        let mut local_func_list: Vec<DefFuncAST> = vec![];
+        for f in self.where_list.drain(..) {
+            if local_func_list.last().map(|x| x.name == f.name).unwrap_or(false) {
+                let g = local_func_list.pop().unwrap().add_clauses(f)?;
+                local_func_list.push(g);
+            } else if local_func_list.iter().any(|x| x.name == f.name) {
+                return Err(Error::new(f.pos, "Duplicate function"));
+            } else {
+                local_func_list.push(f);
+            }
+        }
+        let local_names = local_func_list.iter().map(|f| f.name.clone()).collect::<Vec<_>>();
+        //局所関数から見える外側の変数
+        let mut outer = params.clone();
+        self.patterns.iter().for_each(|x| x.variables(&mut outer));
+        outer.retain(|x| !local_names.contains(x));
+
+        //局所関数が使う外側の変数をキャプチャする。呼び出す局所関数のキャプチャも必要になる
+        let free_list = local_func_list.iter()
+            .map(|f| {
+                let mut free = vec![];
+                f.free_variables(&mut vec![], &mut free);
+                free
+            })
+            .collect::<Vec<_>>();
+        let mut captures_list = free_list.iter()
+            .map(|free| outer.iter().filter(|x| free.contains(x)).cloned().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        loop {
+            let mut changed = false;
+            for (idx, free) in free_list.iter().enumerate() {
+                for (callee_idx, name) in local_names.iter().enumerate() {
+                    if callee_idx == idx || !free.contains(name) { continue; }
+                    for capture in captures_list[callee_idx].clone() {
+                        if !captures_list[idx].contains(&capture) {
+                            captures_list[idx].push(capture);
+                            changed = true;
+                        }
+                    }
+                }
+            }
+            if !changed { break; }
+        }
+        for captures in captures_list.iter_mut() {
+            *captures = outer.iter().filter(|x| captures.contains(x)).cloned().collect();
+        }
+        //節のパターンで束縛した変数をキャプチャするなら内部の名前に変える
+        //持ち上げた関数の引数は既に内部の名前になっている
+        let mut renames = HashMap::new();
+        for capture in captures_list.iter().flat_map(|x| x.iter()) {
+            if !capture.starts_with('#') {
+                renames.insert(capture.clone(), format!("#{}#{}", outer_name, capture));
+            }
+        }
+        let captures_list = captures_list.into_iter()
+            .map(|captures| captures.into_iter().map(|x| renames.get(&x).cloned().unwrap_or(x)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        self.patterns = self.patterns.into_iter().map(|x| rename_pattern(x, &renames)).collect();
+
+        let mut local_funcs = LocalFuncs::new();
+        for (f, captures) in local_func_list.iter().zip(captures_list.iter()) {
+            let mut name = format!("{}#{}", outer_name, f.name);
+            let mut count = 0;
+            while used_names.contains(&name) {
+                count += 1;
+                name = format!("{}#{}#{}", outer_name, f.name, count);
+            }
+            used_names.push(name.clone());
+            local_funcs.insert(f.name.clone(), LocalFunc { name, captures: captures.clone() });
+        }
+        let mut replace = |x, _: &Vec<String>| -> ResolveResult<ExprAST> { Ok(replace_local_func(x, &local_funcs, &renames)) };
+        self.guards = self.guards.into_iter()
+            .map(|(guard, body)| Ok((
+                guard.replace_variables(&mut vec![], &mut replace)?,
+                body.replace_variables(&mut vec![], &mut replace)?,
+            )))
+            .collect::<ResolveResult<Vec<_>>>()?;
+        for (f, captures) in local_func_list.into_iter().zip(captures_list) {
+            let f = f.replace_variables(&mut vec![], &mut replace)?;
+            let pos = f.pos;
+            let mut params = captures.iter()
+                .map(|x| VariableAST::new(x.clone(), pos))
+                .collect::<Vec<_>>();
+            params.extend(f.params);
+            lifted.push(DefFuncAST {
+                name: local_funcs[&f.name].name.clone(),
+                params,
+                patterns: f.patterns.into_iter().map(|(idx, x)| (idx + captures.len(), x)).collect(),
+                body: f.body,
+                pos,
+                doc: f.doc,
+            });
+        }
+        Ok(self)
+    }
+}
+
+//局所関数を持ち上げた関数の部分適用に、キャプチャする変数を内部の名前に置き換える
+fn replace_local_func(x: VariableAST, local_funcs: &LocalFuncs, renames: &HashMap<String, String>) -> ExprAST {
+    match local_funcs.get(&x.id) {
+        Some(f) => {
+            f.captures.iter()
+                .fold(ExprAST::create_variable_ast(f.name.clone(), x.pos), |acc, capture|
+                    ExprAST::create_func_call_ast(acc, ExprAST::create_variable_ast(capture.clone(), x.pos)),
+                )
+        }
+        None => match renames.get(&x.id) {
+            Some(name) => ExprAST::create_variable_ast(name.clone(), x.pos),
+            None => ExprAST::VariableAST(x),
+        }
+    }
+}
+
+//パターンで束縛する変数の名前を変える
+fn rename_pattern(pattern: PatternAST, renames: &HashMap<String, String>) -> PatternAST {
+    match pattern {
+        PatternAST::VariableAST(x) => match renames.get(&x.id) {
+            Some(name) => PatternAST::VariableAST(VariableAST::new(name.clone(), x.pos)),
+            None => PatternAST::VariableAST(x),
+        },
+        PatternAST::TupleAST(mut x) => {
+            x.elements = x.elements.into_iter().map(|x| rename_pattern(x, renames)).collect();
+            PatternAST::TupleAST(x)
+        }
+        PatternAST::RecordAST(mut x) => {
+            x.fields = x.fields.into_iter().map(|(name, x)| (name, rename_pattern(x, renames))).collect();
+            PatternAST::RecordAST(x)
+        }
+        x => x,
+    }
+}
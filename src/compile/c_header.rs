@@ -0,0 +1,122 @@
+use super::ir::mir::ExportFuncMir;
+use super::types::*;
+
+//公開する関数と、その引数に現れる構造体のレイアウトを記述したCヘッダーを生成する
+pub fn c_header(guard_name: &str, export_func_list: &[ExportFuncMir]) -> String {
+    let mut header = CHeader { typedefs: vec![], named_types: vec![] };
+    let func_decls = export_func_list
+        .iter()
+        .map(|x| header.func_decl(x))
+        .collect::<Vec<_>>();
+    let guard = guard_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect::<String>() + "_H";
+    format!(
+        "/* Generated by ruscall. Do not edit. */\n\
+         #ifndef {guard}\n\
+         #define {guard}\n\n\
+         #include <stdbool.h>\n\
+         #include <stdint.h>\n\n\
+         #ifdef __cplusplus\n\
+         extern \"C\" {{\n\
+         #endif\n\n\
+         /* Ruscallの構造体とタプルはパディングなしで並ぶ */\n\
+         #pragma pack(push, 1)\n\
+         {typedefs}\
+         #pragma pack(pop)\n\n\
+         {func_decls}\n\n\
+         #ifdef __cplusplus\n\
+         }}\n\
+         #endif\n\n\
+         #endif\n",
+        guard = guard,
+        typedefs = header.typedefs.iter().map(|x| x.to_string() + "\n").collect::<String>(),
+        func_decls = func_decls.join("\n"),
+    )
+}
+
+struct CHeader {
+    //型定義。依存される型が先に並ぶ
+    typedefs: Vec<String>,
+    //定義済みの構造体とタプルの名前
+    named_types: Vec<(Type, String)>,
+}
+
+impl CHeader {
+    //構造体とタプルはポインタで受け渡し、戻り値は最後の引数のポインタに書き込む
+    fn func_decl(&mut self, export_func: &ExportFuncMir) -> String {
+        let (param_types, ret_type) = export_func.split_ty().unwrap();
+        let mut params = param_types
+            .iter()
+            .filter(|x| !is_unit(x))
+            .enumerate()
+            .map(|(i, x)| match x {
+                Type::TupleType(_) | Type::StructType(_) => format!("const {} *a{}", self.type_name(x), i),
+                _ => format!("{} a{}", self.type_name(x), i),
+            })
+            .collect::<Vec<_>>();
+        let ret = match ret_type {
+            ref x if is_unit(x) => "void".to_string(),
+            ref x @ Type::TupleType(_) | ref x @ Type::StructType(_) => {
+                params.push(format!("{} *ret", self.type_name(x)));
+                "void".to_string()
+            }
+            ref x => self.type_name(x),
+        };
+        if params.len() == 0 {
+            params.push("void".to_string());
+        }
+        format!("{} {}({});", ret, export_func.name, params.join(", "))
+    }
+
+    fn type_name(&mut self, ty: &Type) -> String {
+        match ty {
+            Type::TCon { name } if name == "Int32" => "int32_t".to_string(),
+            Type::TCon { name } if name == "Bool" => "bool".to_string(),
+            Type::TupleType(x) => {
+                let name = format!("ruscall_tuple{}", self.named_types.len());
+                let fields = x.element_tys
+                    .iter()
+                    .enumerate()
+                    .map(|(i, ty)| (format!("_{}", i), ty.clone()))
+                    .collect();
+                self.define(ty, name, fields)
+            }
+            Type::StructType(x) => {
+                let fields = match x.ty {
+                    StructInternalType::TupleType(ref x) => x.element_tys
+                        .iter()
+                        .enumerate()
+                        .map(|(i, ty)| (format!("_{}", i), ty.clone()))
+                        .collect(),
+                    StructInternalType::RecordType(ref x) => x.element_tys.clone(),
+                };
+                self.define(ty, x.name.clone(), fields)
+            }
+            _ => panic!("not C ABI type"),
+        }
+    }
+
+    fn define(&mut self, ty: &Type, name: String, fields: Vec<(String, Type)>) -> String {
+        if let Some((_, name)) = self.named_types.iter().find(|(x, _)| x == ty) {
+            return name.clone();
+        }
+        //大きさ0の()のフィールドはレイアウトに影響しないので書かない
+        let fields = fields
+            .iter()
+            .filter(|(_, ty)| !is_unit(ty))
+            .map(|(field_name, ty)| format!("    {} {};\n", self.type_name(ty), field_name))
+            .collect::<String>();
+        self.typedefs.push(format!("typedef struct {} {{\n{}}} {};\n", name, fields, name));
+        self.named_types.push((ty.clone(), name.clone()));
+        name
+    }
+}
+
+fn is_unit(ty: &Type) -> bool {
+    match ty {
+        Type::TupleType(x) => x.element_tys.len() == 0,
+        _ => false
+    }
+}
@@ -7,12 +7,20 @@ use super::semantic_analysis::type_env::TypeInfo;
 use super::semantic_analysis::type_inference::assump_env::AssumpEnv;
 use super::types::types::*;
 use std::collections::hash_map::HashMap;
+use combine::stream::state::SourcePosition;
+use compile::arity::analyze_arity;
+use compile::debug_info::{DebugInfo, DIScope};
 use compile::mangling::mangle;
+use compile::optimize::{eliminate_tail_calls, set_tail_call};
+
+//Ruscallの関数に使う呼び出し規約(fastcc)。末尾呼び出しを最適化できる
+const FAST_CALL_CONV: u32 = 8;
 
 pub struct CodeGenResult<'a> {
     pub file_name: &'a str,
     pub builder: builder::Builder,
     pub module: module::Module,
+    pub export_func_list: Vec<mir::ExportFuncMir>,
 }
 
 type FuncList = HashMap<String, mir::FuncMir>;
@@ -26,83 +34,287 @@ struct GenInfo<'a> {
     pub function: values::FunctionValue,
     pub func_list: &'a FuncList,
     pub assump: &'a AssumpEnv,
+    pub debug_info: &'a DebugInfo,
+    pub di_scope: Option<DIScope>,
+}
+
+impl<'a> GenInfo<'a> {
+    //今のブロックの最後の命令。式を生成した後にその式の命令を探す起点になる
+    fn last_instruction(&self) -> (Option<basic_block::BasicBlock>, Option<values::InstructionValue>) {
+        let block = self.builder.get_insert_block();
+        let instruction = block.as_ref().and_then(|x| x.get_last_instruction());
+        (block, instruction)
+    }
+
+    //式を生成する前のブロックで、式が追加した命令にデバッグ情報の位置を付ける
+    fn set_debug_location(&self, (block, instruction): (Option<basic_block::BasicBlock>, Option<values::InstructionValue>), pos: SourcePosition) {
+        if let (Some(di_scope), Some(block)) = (self.di_scope, block) {
+            let start = match instruction {
+                Some(x) => x.get_next_instruction(),
+                None => block.get_first_instruction(),
+            };
+            self.debug_info.set_location(di_scope, start, pos);
+        }
+    }
 }
 
 //コード生成する関数
 impl mir::ProgramMir {
-    pub fn code_gen(self, file_name: &str, assump: AssumpEnv) -> CodeGenResult {
+    pub fn code_gen(self, file_name: &str, assump: AssumpEnv, debug_info: DebugInfo) -> CodeGenResult {
         //llvm初期化
-        targets::Target::initialize_all(&targets::InitializationConfig::default());
+        targets::Target::initialize_native(&targets::InitializationConfig::default())
+            .expect("failed to initialize native target");
         let builder = builder::Builder::create();
         let module = module::Module::create(file_name);
 
         let mut ty_info = self.ty_info;
+        let export_func_list = self.export_func_list;
         //外部関数宣言のコード化
         self.ex_dec_func_list
             .into_iter()
             .for_each(|x| ex_func_gen(x, &module, &mut ty_info));
 
+        //ライブラリとしてコンパイルする時はmainがない
         let main_func = match self.implicit_func_list.get("main") {
-            None => &self.explicit_func_list.iter().find(|x| x.func.name == "main").unwrap().func,
-            Some(func) => &func.func
-        }.clone();
+            None => self.explicit_func_list.iter().find(|x| x.func.name == "main").map(|x| &x.func),
+            Some(func) => Some(&func.func)
+        }.cloned();
 
         //関数定義のコード化
-        let func_list = self.implicit_func_list
+        let mut func_list = self.implicit_func_list
             .into_iter().map(|(_, x)| x.func)
             .chain(self.explicit_func_list.into_iter().map(|x| x.func))
             .map(|func| (func.name.clone(), func))
             .collect::<FuncList>();
+        analyze_arity(&mut func_list);
 
-        let main_func_ty = Type::create_func_type(vec![Type::create_tuple_type(vec![])], Type::create_int32());
-        main_func.code_gen(&module, &builder, &mut ty_info, &main_func_ty, &func_list, &assump);
+        if let Some(main_func) = main_func {
+            let main_func_ty = Type::create_func_type(vec![Type::create_tuple_type(vec![])], Type::create_int32());
+            main_func.code_gen(&module, &builder, &mut ty_info, &main_func_ty, &func_list, &assump, &debug_info);
+        }
+        //公開する関数のC ABIラッパーのコード化
+        export_func_list
+            .iter()
+            .for_each(|x| export_func_gen(x, &module, &builder, &mut ty_info.clone(), &func_list, &assump, &debug_info));
+        debug_info.finalize();
         if let Err(err_msg) = module.verify() {
             module.print_to_stderr();
             panic!("llvm error:{}", err_msg.to_string());
         }
+        //自己再帰の末尾呼び出しは最適化レベルに関わらずループにする
+        eliminate_tail_calls(&module);
 
         CodeGenResult {
             file_name,
             module,
             builder,
+            export_func_list,
         }
     }
 }
 
 //関数を取得する。存在しない場合は新たに登録する。
-fn get_function(name: &String, ty: &Type, module: &module::Module, builder: &builder::Builder, ty_info: &mut TypeInfo, func_list: &FuncList, assump: &AssumpEnv) -> values::FunctionValue {
+fn get_function(name: &String, ty: &Type, module: &module::Module, builder: &builder::Builder, ty_info: &mut TypeInfo, func_list: &FuncList, assump: &AssumpEnv, debug_info: &DebugInfo) -> values::FunctionValue {
     use super::types::Qual;
-    match module.get_function(&name) {
+    if name == "main" {
+        return module.get_function(&name).unwrap_or_else(|| add_function(name, &ty, module, true, 0));
+    }
+    //定義がなければ外部関数。公開用のラッパーと同名になるので定義があれば名前で探さない
+    if !func_list.contains_key(name) {
+        return module.get_function(&name).unwrap();
+    }
+    let ty = ty_info.qual_unify(assump.global_get(&name).unwrap().get_qual().clone(), Qual::new(ty.clone())).unwrap().t;
+    let ty = ty_info.type_look_up(&ty, true);
+    let symbol_name = func_list[name].symbol_name();
+    match module.get_function(&mangle(&symbol_name, &ty)) {
         Some(func) => func,
-        None => if name == "main" {
-            add_function(name, &ty, module, true)
-        } else {
-            let ty = ty_info.qual_unify(assump.global_get(&name).unwrap().get_qual().clone(), Qual::new(ty.clone())).unwrap().t;
-            let ty = ty_info.type_look_up(&ty, true);
-            match module.get_function(&mangle(name, &ty)) {
-                Some(func) => func,
-                None => {
-                    let func = add_function(name, &ty, module, false);
-                    let hoge = builder.get_insert_block().unwrap();
-                    func_list[name].clone().code_gen(module, builder, ty_info, &ty, func_list, assump);
-                    builder.position_at_end(&hoge);
-                    func
-                }
-            }
+        None => {
+            let func = add_function(&symbol_name, &ty, module, false, func_list[name].env_len);
+            let hoge = builder.get_insert_block().unwrap();
+            func_list[name].clone().code_gen(module, builder, ty_info, &ty, func_list, assump, debug_info);
+            builder.position_at_end(&hoge);
+            func
         }
     }
 }
 
-fn add_function(name: &String, ty: &Type, module: &module::Module, no_mangle: bool) -> values::FunctionValue {
-    let mangled_name = if no_mangle { name.to_string() } else { mangle(name, ty) };
-    module.add_function(&mangled_name, ty.to_llvm_any_type(false).as_any_type_enum().into_function_type(), Some(module::Linkage::External))
+//arityの最後の引数を受け取るラムダ式の関数を、環境の値も引数として直接受け取る形で取得する。引数が揃った呼び出しで使う
+//シンボル名とデバッグ情報にはカリー化する前の関数の名前を使う
+fn get_direct_function(arity: &mir::ArityMir, ty: &Type, module: &module::Module, builder: &builder::Builder, ty_info: &mut TypeInfo, func_list: &FuncList, assump: &AssumpEnv, debug_info: &DebugInfo) -> values::FunctionValue {
+    use super::types::Qual;
+    let ty = ty_info.qual_unify(assump.global_get(&arity.func_name).unwrap().get_qual().clone(), Qual::new(ty.clone())).unwrap().t;
+    let ty = ty_info.type_look_up(&ty, true);
+    let direct_name = arity.name.clone() + "#direct";
+    match module.get_function(&mangle(&direct_name, &ty)) {
+        Some(func) => func,
+        None => {
+            let func = add_function(&direct_name, &ty, module, false, 0);
+            let block = builder.get_insert_block().unwrap();
+            let mut func_mir = func_list[&arity.func_name].clone();
+            func_mir.name = arity.name.clone();
+            func_mir.env_len = 0;
+            func_mir.code_gen_body(func, module, builder, ty_info, &ty, func_list, assump, debug_info);
+            builder.position_at_end(&block);
+            func
+        }
+    }
+}
+
+//no_mangleなら外部から呼ぶCの関数、それ以外は環境のポインタを先頭に受け取るfastccの関数を追加する
+//env_lenはラムダ式の関数で環境から渡される引数の数
+fn add_function(name: &String, ty: &Type, module: &module::Module, no_mangle: bool, env_len: usize) -> values::FunctionValue {
+    if no_mangle {
+        return module.add_function(&name, ty.to_llvm_any_type(false).as_any_type_enum().into_function_type(), Some(module::Linkage::External));
+    }
+    let func = module.add_function(&mangle(name, ty), ty.get_lambda_ty().func_ty.to_closure_fn_type(env_len), Some(module::Linkage::External));
+    func.set_call_conventions(FAST_CALL_CONV);
+    func
+}
+
+//C呼び出し規約の関数(外部関数とmain)を、環境のポインタを受け取るfastccの関数でラップする
+fn get_closure_wrapper(func: values::FunctionValue, func_ty: &FuncType, module: &module::Module, builder: &builder::Builder) -> values::FunctionValue {
+    if func.get_call_conventions() == FAST_CALL_CONV {
+        return func;
+    }
+    let name = "#closure#".to_string() + func.get_name().to_str().unwrap();
+    if let Some(wrapper) = module.get_function(&name) {
+        return wrapper;
+    }
+    let wrapper = module.add_function(&name, func_ty.to_closure_fn_type(0), Some(module::Linkage::Internal));
+    wrapper.set_call_conventions(FAST_CALL_CONV);
+    let current_block = builder.get_insert_block().unwrap();
+    let entry_block = wrapper.append_basic_block(&"entry");
+    builder.position_at_end(&entry_block);
+    let callsite = builder.build_call(func, &wrapper.get_params()[1..], "");
+    set_tail_call(&callsite);
+    builder.build_return(Some(&callsite.try_as_basic_value().left().unwrap()));
+    builder.position_at_end(&current_block);
+    wrapper
+}
+
+//allocaは関数の先頭にまとめる。分岐先で確保するとループ化した時にスタックが伸び続ける
+fn build_entry_alloca<T: types::BasicType>(function: values::FunctionValue, ty: T) -> values::PointerValue {
+    let entry_block = function.get_entry_basic_block().unwrap();
+    let builder = builder::Builder::create();
+    match entry_block.get_first_instruction() {
+        Some(instruction) => builder.position_before(&instruction),
+        None => builder.position_at_end(&entry_block),
+    }
+    builder.build_alloca(ty, "")
+}
+
+//クロージャの環境を指すポインタの型。環境のないクロージャはnullを持つ
+fn env_ptr_type() -> types::PointerType {
+    types::IntType::i8_type().ptr_type(AddressSpace::Generic)
+}
+
+//関数のポインタと環境のポインタからクロージャの値を作る
+fn build_closure(builder: &builder::Builder, function: values::FunctionValue, func: values::FunctionValue, env_ptr: values::PointerValue) -> values::BasicValueEnum {
+    let func_ptr = func.as_any_value_enum().into_pointer_value();
+    let closure_ty = types::StructType::struct_type(&[env_ptr_type().as_basic_type_enum(), func_ptr.get_type().as_basic_type_enum()], true);
+    let closure_ptr = build_entry_alloca(function, closure_ty);
+    builder.build_store(unsafe { builder.build_struct_gep(closure_ptr, 0, "") }, env_ptr);
+    builder.build_store(unsafe { builder.build_struct_gep(closure_ptr, 1, "") }, func_ptr);
+    builder.build_load(closure_ptr, "")
+}
+
+//クロージャの値を呼び出す。環境のポインタを引数の前に渡す
+fn build_lambda_call(builder: &builder::Builder, function: values::FunctionValue, lambda: values::BasicValueEnum, params_val: Vec<values::BasicValueEnum>) -> values::CallSiteValue {
+    let lambda_ptr = build_entry_alloca(function, lambda.get_type());
+    builder.build_store(lambda_ptr, lambda);
+    let env_ptr = builder.build_load(unsafe { builder.build_struct_gep(lambda_ptr, 0, "") }, "");
+    let func_ptr = builder.build_load(unsafe { builder.build_struct_gep(lambda_ptr, 1, "") }, "").into_pointer_value();
+    let args: Vec<_> = Some(env_ptr).into_iter().chain(params_val).collect();
+    let callsite = builder.build_call_pointer(func_ptr, &args, "");
+    callsite.set_call_convention(FAST_CALL_CONV);
+    callsite
+}
+
+//ランタイムの関数を取得する。まだ宣言していなければ宣言する
+fn get_runtime_function(module: &module::Module, name: &str, fn_type: types::FunctionType) -> values::FunctionValue {
+    module.get_function(name).unwrap_or_else(|| module.add_function(name, fn_type, Some(module::Linkage::External)))
+}
+
+//クロージャの環境を確保する領域の今の位置を取得する
+fn build_env_mark(module: &module::Module, builder: &builder::Builder) -> values::BasicValueEnum {
+    let mark_func = get_runtime_function(module, "ruscall_env_mark", env_ptr_type().fn_type(&[], false));
+    builder.build_call(mark_func, &[], "env_mark").try_as_basic_value().left().unwrap()
+}
+
+//markの後に確保した環境を解放する
+fn build_env_release(module: &module::Module, builder: &builder::Builder, mark: values::BasicValueEnum) {
+    let release_func = get_runtime_function(module, "ruscall_env_release", types::VoidType::void_type().fn_type(&[env_ptr_type().as_basic_type_enum()], false));
+    builder.build_call(release_func, &[mark], "");
+}
+
+//クロージャの環境を領域から確保する。確保できなければパニックする
+fn build_env_alloc(gen_info: &GenInfo, env_ty: types::StructType, pos: SourcePosition) -> values::PointerValue {
+    let i32_type = types::IntType::i32_type();
+    let alloc_func = get_runtime_function(gen_info.module, "ruscall_env_alloc", env_ptr_type().fn_type(&[i32_type.as_basic_type_enum()], false));
+    //nullから1つ先の要素へのオフセットが環境の大きさになる
+    let size_ptr = unsafe { gen_info.builder.build_gep(env_ty.ptr_type(AddressSpace::Generic).const_null(), &[i32_type.const_int(1, false)], "") };
+    let size = gen_info.builder.build_ptr_to_int(size_ptr, i32_type, "env_size");
+    let env_ptr = gen_info.builder.build_call(alloc_func, &[size.as_basic_value_enum()], "env")
+        .try_as_basic_value().left().unwrap().into_pointer_value();
+    let is_null = gen_info.builder.build_is_null(env_ptr, "");
+    build_panic_if(gen_info, is_null, "out of memory", pos);
+    gen_info.builder.build_pointer_cast(env_ptr, env_ty.ptr_type(AddressSpace::Generic), "")
+}
+
+//Cから呼べる公開用のラッパー関数を生成する
+//構造体とタプルはポインタで受け取り、戻り値は最後の引数のポインタに書き込む
+fn export_func_gen(export_func: &mir::ExportFuncMir, module: &module::Module, builder: &builder::Builder, ty_info: &mut TypeInfo, func_list: &FuncList, assump: &AssumpEnv, debug_info: &DebugInfo) {
+    let ty = export_func.ty.clone().unwrap();
+    let (param_types, ret_type) = export_func.split_ty().unwrap();
+    let ret_by_pointer = ret_type.is_aggregate() && !ret_type.is_unit();
+    let mut c_params_ty: Vec<_> = param_types.iter()
+        .filter(|x| !x.is_unit())
+        .map(|x| x.to_c_abi_type())
+        .collect();
+    if ret_by_pointer {
+        c_params_ty.push(ret_type.to_c_abi_type());
+    }
+    let c_func_ty = if ret_type.is_unit() || ret_by_pointer {
+        types::VoidType::void_type().fn_type(&c_params_ty, false)
+    } else {
+        ret_type.to_c_abi_type().fn_type(&c_params_ty, false)
+    };
+    let wrapper = module.add_function(&export_func.name, c_func_ty, Some(module::Linkage::External));
+    let entry_block = wrapper.append_basic_block(&"entry");
+    builder.position_at_end(&entry_block);
+
+    let func = get_function(&export_func.name, &ty, module, builder, ty_info, func_list, assump, debug_info);
+    //呼び出しの間に確保した環境は戻り値から辿れなければ戻る前に解放する
+    let env_mark = if ret_type.has_closure() { None } else { Some(build_env_mark(module, builder)) };
+    let mut c_params = wrapper.get_params().into_iter();
+    let mut value = build_closure(builder, wrapper, func, env_ptr_type().const_null());
+    for param_ty in param_types {
+        let arg = if param_ty.is_unit() {
+            param_ty.to_llvm_basic_type().into_struct_type().get_undef().as_basic_value_enum()
+        } else {
+            param_ty.from_c_abi_value(builder, c_params.next().unwrap())
+        };
+        value = build_lambda_call(builder, wrapper, value, vec![arg])
+            .try_as_basic_value().left().unwrap();
+    }
+    if let Some(env_mark) = env_mark {
+        build_env_release(module, builder, env_mark);
+    }
+    if ret_type.is_unit() {
+        builder.build_return(None);
+    } else if ret_by_pointer {
+        builder.build_store(c_params.next().unwrap().into_pointer_value(), value);
+        builder.build_return(None);
+    } else {
+        builder.build_return(Some(&ret_type.to_c_abi_value(builder, value)));
+    }
 }
 
 fn ex_func_gen(dec_func_ir: mir::DecFuncMir, module: &module::Module, ty_info: &mut TypeInfo) {
     use compile::types::show_type::ShowType;
     match ty_info.look_up_func_name(dec_func_ir.name.clone()) {
         Type::LambdaType(ty) =>
-            add_function(&dec_func_ir.name, &Type::LambdaType(ty.clone()), module, true),
+            add_function(&dec_func_ir.name, &Type::LambdaType(ty.clone()), module, true, 0),
         x => panic!("error!{}", x.show()),
     };
 }
@@ -138,9 +350,82 @@ impl Type {
             Type::TGen(_, _) => panic!("TGen error")
         }
     }
+
+    //クロージャを含む型か。含まない値からは環境を辿れない
+    fn has_closure(&self) -> bool {
+        match self {
+            Type::LambdaType(_) => true,
+            Type::TupleType(x) => x.element_tys.iter().any(|x| x.has_closure()),
+            Type::StructType(x) => (0..x.get_elements_len()).any(|i| x.get_elements_at(i).has_closure()),
+            _ => false,
+        }
+    }
+}
+
+//C ABIでの値の表現
+impl Type {
+    fn get_undef(&self) -> values::BasicValueEnum {
+        match self.to_llvm_basic_type() {
+            types::BasicTypeEnum::IntType(x) => x.get_undef().as_basic_value_enum(),
+            types::BasicTypeEnum::StructType(x) => x.get_undef().as_basic_value_enum(),
+            types::BasicTypeEnum::PointerType(x) => x.get_undef().as_basic_value_enum(),
+            _ => panic!("undefined!"),
+        }
+    }
+
+    fn is_unit(&self) -> bool {
+        match self {
+            Type::TupleType(x) => x.element_tys.len() == 0,
+            _ => false
+        }
+    }
+
+    fn is_aggregate(&self) -> bool {
+        match self {
+            Type::TupleType(_) | Type::StructType(_) => true,
+            _ => false
+        }
+    }
+
+    //Boolはi8、構造体とタプルはポインタで受け渡す
+    fn to_c_abi_type(&self) -> types::BasicTypeEnum {
+        match self {
+            Type::TCon { name } if name == "Bool" => types::IntType::i8_type().as_basic_type_enum(),
+            x if x.is_aggregate() => x.to_llvm_basic_type().ptr_type(AddressSpace::Generic).as_basic_type_enum(),
+            x => x.to_llvm_basic_type(),
+        }
+    }
+
+    fn from_c_abi_value(&self, builder: &builder::Builder, value: values::BasicValueEnum) -> values::BasicValueEnum {
+        match self {
+            Type::TCon { name } if name == "Bool" =>
+                builder.build_int_truncate(value.into_int_value(), types::IntType::bool_type(), "").as_basic_value_enum(),
+            x if x.is_aggregate() => builder.build_load(value.into_pointer_value(), ""),
+            _ => value,
+        }
+    }
+
+    fn to_c_abi_value(&self, builder: &builder::Builder, value: values::BasicValueEnum) -> values::BasicValueEnum {
+        match self {
+            Type::TCon { name } if name == "Bool" =>
+                builder.build_int_z_extend(value.into_int_value(), types::IntType::i8_type(), "").as_basic_value_enum(),
+            _ => value,
+        }
+    }
 }
 
 impl FuncType {
+    //クロージャの関数の型。環境のポインタを先頭に受け取り、環境から渡される引数は含めない
+    fn to_closure_fn_type(&self, env_len: usize) -> types::FunctionType {
+        self.ret_type.to_llvm_basic_type()
+            .fn_type(
+                &Some(env_ptr_type().as_basic_type_enum()).into_iter()
+                    .chain(self.param_types.iter().skip(env_len).map(|x| x.to_llvm_basic_type()))
+                    .collect::<Vec<_>>(),
+                false,
+            )
+    }
+
     fn to_llvm_type(&self) -> types::FunctionType {
         self.ret_type.to_llvm_basic_type()
             .fn_type(
@@ -168,26 +453,16 @@ impl TupleType {
 impl LambdaType {
     fn to_llvm_any_type(&self, fn_pointer_flag: bool) -> types::AnyTypeEnum {
         if fn_pointer_flag {
-            if let Some(env_ty) = self.env_ty.clone() {
-                let env_llvm_ty = env_ty.to_llvm_type();
-                let func_llvm_ty = self.func_ty.to_llvm_type().ptr_type(AddressSpace::Generic);
-                types::StructType::struct_type(&vec![env_llvm_ty.as_basic_type_enum(), func_llvm_ty.as_basic_type_enum()], true).as_any_type_enum()
-            } else {
-                self.func_ty.to_llvm_type().ptr_type(AddressSpace::Generic).as_any_type_enum()
-            }
+            self.to_llvm_basic_type().as_any_type_enum()
         } else {
             self.func_ty.to_llvm_type().as_any_type_enum()
         }
     }
 
+    //関数の値はすべて{環境のポインタ, 関数のポインタ}のクロージャで表す
     fn to_llvm_basic_type(&self) -> types::BasicTypeEnum {
-        if let Some(env_ty) = self.env_ty.clone() {
-            let env_llvm_ty = env_ty.to_llvm_type();
-            let func_llvm_ty = self.func_ty.to_llvm_type().ptr_type(AddressSpace::Generic);
-            types::StructType::struct_type(&vec![env_llvm_ty.as_basic_type_enum(), func_llvm_ty.as_basic_type_enum()], true).as_basic_type_enum()
-        } else {
-            self.func_ty.to_llvm_type().ptr_type(AddressSpace::Generic).as_basic_type_enum()
-        }
+        let func_llvm_ty = self.func_ty.to_closure_fn_type(0).ptr_type(AddressSpace::Generic);
+        types::StructType::struct_type(&vec![env_ptr_type().as_basic_type_enum(), func_llvm_ty.as_basic_type_enum()], true).as_basic_type_enum()
     }
 }
 
@@ -222,29 +497,92 @@ impl mir::ExprMir {
             mir::ExprMir::TupleStructMir(_) => panic!("undefined"),
             mir::ExprMir::IndexPropertyMir(x) => ty_info.look_up(&x.ty_id),
             mir::ExprMir::NamePropertyMir(x) => ty_info.look_up(&x.ty_id),
+            mir::ExprMir::RecordUpdateMir(x) => ty_info.look_up(&x.ty_id),
+            mir::ExprMir::PatternCheckMir(x) => x.body.get_ty(ty_info, params_ty),
             mir::ExprMir::LambdaMir(x) => ty_info.look_up(&x.ty_id),
+            mir::ExprMir::PanicMir(x) => ty_info.look_up(&x.ty_id),
         }
     }
 }
 
 impl mir::FuncMir {
-    fn code_gen(self, module: &module::Module, builder: &builder::Builder, ty_info: &mut TypeInfo, ty: &Type, func_list: &FuncList, assump: &AssumpEnv) {
+    fn code_gen(self, module: &module::Module, builder: &builder::Builder, ty_info: &mut TypeInfo, ty: &Type, func_list: &FuncList, assump: &AssumpEnv, debug_info: &DebugInfo) {
         let mut ty_info = ty_info.clone();
-        let function = get_function(&self.name, &ty, module, builder, &mut ty_info, func_list, assump);
+        let function = get_function(&self.name, &ty, module, builder, &mut ty_info, func_list, assump, debug_info);
+        self.code_gen_body(function, module, builder, &mut ty_info, ty, func_list, assump, debug_info);
+    }
+
+    fn code_gen_body(self, function: values::FunctionValue, module: &module::Module, builder: &builder::Builder, ty_info: &mut TypeInfo, ty: &Type, func_list: &FuncList, assump: &AssumpEnv, debug_info: &DebugInfo) {
         let ty = ty_info.type_look_up(ty, true);
-        let params = function.get_params();
         let entry_block = function.append_basic_block(&"entry");
         builder.position_at_end(&entry_block);
-        let mut gen_info = GenInfo { module, builder, params, ty_info: &mut ty_info, function, func_list, params_ty: &ty.get_lambda_ty().func_ty.param_types, assump };
-        let value = self.body.code_gen(&mut gen_info);
-        builder.build_return(Some(&value));
+        let di_scope = debug_info.create_function(function, &self.symbol_name(), &ty, self.pos);
+        let params_ty = &ty.get_lambda_ty().func_ty.param_types;
+        //mainはCの関数なので環境のポインタを受け取らない
+        let first_arg = if self.name == "main" { 0 } else { 1 };
+        let params = if self.name == "main" {
+            function.get_params()
+        } else {
+            build_closure_params(builder, function, &params_ty[..self.env_len])
+        };
+        let mut gen_info = GenInfo { module, builder, params, ty_info, function, func_list, params_ty, assump, debug_info, di_scope };
+        self.body.code_gen_tail(&mut gen_info);
+        if let Some(di_scope) = di_scope {
+            debug_info.declare_parameters(di_scope, function, &self.param_names[self.env_len..], &params_ty[self.env_len..], first_arg, self.pos);
+            debug_info.set_function_location(di_scope, function, self.pos);
+        }
+    }
+}
+
+//クロージャの関数の引数を取り出す。環境の値を環境のポインタから読んで引数の前に並べる
+fn build_closure_params(builder: &builder::Builder, function: values::FunctionValue, env_tys: &[Type]) -> Vec<values::BasicValueEnum> {
+    let mut params = function.get_params();
+    let env_ptr = params.remove(0).into_pointer_value();
+    if env_tys.len() == 0 {
+        return params;
     }
+    let env_llvm_ty = TupleType { element_tys: env_tys.to_vec() }.to_llvm_type();
+    let env_ptr = builder.build_pointer_cast(env_ptr, env_llvm_ty.ptr_type(AddressSpace::Generic), "env");
+    (0..env_tys.len())
+        .map(|idx| builder.build_load(unsafe { builder.build_struct_gep(env_ptr, idx as u32, "") }, ""))
+        .chain(params)
+        .collect()
 }
 
 impl mir::ExprMir {
+    //末尾位置の式をコード生成してreturnする
+    fn code_gen_tail(self, gen_info: &mut GenInfo) {
+        let pos = self.get_pos();
+        let last_instruction = gen_info.last_instruction();
+        match self {
+            mir::ExprMir::IfMir(x) => x.code_gen_tail(gen_info),
+            mir::ExprMir::PatternCheckMir(x) => x.body.code_gen_tail(gen_info),
+            mir::ExprMir::CallMir(x) => {
+                let value = x.code_gen_call(gen_info, true);
+                gen_info.builder.build_return(Some(&value));
+            }
+            x => {
+                let value = x.code_gen(gen_info);
+                gen_info.builder.build_return(Some(&value));
+            }
+        };
+        gen_info.set_debug_location(last_instruction, pos);
+    }
+
     fn code_gen(
         self,
         gen_info: &mut GenInfo,
+    ) -> values::BasicValueEnum {
+        let pos = self.get_pos();
+        let last_instruction = gen_info.last_instruction();
+        let value = self.code_gen_expr(gen_info);
+        gen_info.set_debug_location(last_instruction, pos);
+        value
+    }
+
+    fn code_gen_expr(
+        self,
+        gen_info: &mut GenInfo,
     ) -> values::BasicValueEnum {
         match self {
             mir::ExprMir::NumMir(num_ir) => types::IntType::i32_type().const_int(num_ir.num as u64, true).as_basic_value_enum(),
@@ -255,13 +593,20 @@ impl mir::ExprMir {
             mir::ExprMir::IfMir(x) => x.code_gen(gen_info).as_basic_value(),
             mir::ExprMir::OpMir(op_ir) => op_ir.code_gen(gen_info),
             mir::ExprMir::VariableMir(var_ir) => gen_info.params[gen_info.params.len() - var_ir.id - 1],
-            mir::ExprMir::GlobalVariableMir(x) => x.code_gen(gen_info).as_any_value_enum().into_pointer_value().as_basic_value_enum(),
+            mir::ExprMir::GlobalVariableMir(x) => {
+                let func = x.code_gen(gen_info);
+                build_closure(gen_info.builder, gen_info.function, func, env_ptr_type().const_null())
+            }
             mir::ExprMir::TupleMir(x) => x.code_gen(gen_info),
             mir::ExprMir::TupleStructMir(x) => x.tuple.code_gen(gen_info),
-            mir::ExprMir::LambdaMir(x) => x.code_gen(gen_info),
+            mir::ExprMir::LambdaMir(x) => x.code_gen(gen_info, false),
+            mir::ExprMir::PanicMir(x) => x.code_gen(gen_info),
             mir::ExprMir::CallMir(x) => x.code_gen(gen_info),
             mir::ExprMir::IndexPropertyMir(x) => x.code_gen(gen_info),
             mir::ExprMir::NamePropertyMir(x) => x.code_gen(gen_info),
+            mir::ExprMir::RecordUpdateMir(x) => x.code_gen(gen_info),
+            //パターンの形は型検査でしか使わない
+            mir::ExprMir::PatternCheckMir(x) => x.body.code_gen(gen_info),
         }
     }
 }
@@ -269,7 +614,8 @@ impl mir::ExprMir {
 impl mir::GlobalVariableMir {
     fn code_gen(self, gen_info: &mut GenInfo) -> values::FunctionValue {
         let func_ty = gen_info.ty_info.look_up(&self.ty_id);
-        get_function(&self.id, &func_ty, gen_info.module, gen_info.builder, &mut gen_info.ty_info.clone(), gen_info.func_list, gen_info.assump)
+        let func = get_function(&self.id, &func_ty, gen_info.module, gen_info.builder, &mut gen_info.ty_info.clone(), gen_info.func_list, gen_info.assump, gen_info.debug_info);
+        get_closure_wrapper(func, &func_ty.get_lambda_ty().func_ty, gen_info.module, gen_info.builder)
     }
 }
 
@@ -296,6 +642,18 @@ impl mir::IfMir {
         phi_node.add_incoming(&[(t_value, &then_block), (f_value, &else_block)]);
         phi_node
     }
+
+    //末尾位置のifは合流せずにそれぞれの分岐でreturnする
+    fn code_gen_tail(self, gen_info: &mut GenInfo) {
+        let cond_value = self.cond.code_gen(gen_info).into_int_value();
+        let then_block = gen_info.function.append_basic_block(&"then");
+        let else_block = gen_info.function.append_basic_block(&"else");
+        gen_info.builder.build_conditional_branch(cond_value, &then_block, &else_block);
+        gen_info.builder.position_at_end(&then_block);
+        self.t_expr.code_gen_tail(gen_info);
+        gen_info.builder.position_at_end(&else_block);
+        self.f_expr.code_gen_tail(gen_info);
+    }
 }
 
 impl mir::CallMir {
@@ -303,42 +661,100 @@ impl mir::CallMir {
         self,
         gen_info: &mut GenInfo,
     ) -> values::BasicValueEnum {
-        let mut params_val: Vec<_> = self
-            .params
+        self.code_gen_call(gen_info, false)
+    }
+
+    //tail_flagが立っていれば末尾呼び出しとして生成する
+    //クロージャを含まない値を返す呼び出しは、その間に確保した環境がもう使われないので呼び出しの後に解放する
+    //末尾呼び出しの後には何も置けないので、その環境は呼び出し元の呼び出しが終わった時に解放される
+    fn code_gen_call(
+        self,
+        gen_info: &mut GenInfo,
+        tail_flag: bool,
+    ) -> values::BasicValueEnum {
+        let env_mark = if tail_flag || gen_info.ty_info.look_up(&self.ty_id).has_closure() {
+            None
+        } else {
+            Some(build_env_mark(gen_info.module, gen_info.builder))
+        };
+        let (func, args) = self.flatten();
+        let args_len = args.len();
+        let args_val: Vec<_> = args
             .into_iter()
-            .map(|x| x.code_gen(gen_info).as_basic_value_enum())
+            .map(|x| x.code_gen(gen_info))
             .collect();
-        match self.func.get_ty(gen_info.ty_info, gen_info.params_ty) {
-            Type::LambdaType(x) => {
-                let callsite =
-                    if let Some(env_ty) = x.env_ty {
-                        let lambda = self.func.code_gen(gen_info);
-                        let lambda_ptr = gen_info.builder.build_alloca(lambda.as_basic_value_enum().get_type(), "");
-                        gen_info.builder.build_store(lambda_ptr, lambda);
-                        let envs_tuple_pointer = unsafe { gen_info.builder.build_struct_gep(lambda_ptr, 0, "") };
-                        let func_pointer =
-                            gen_info.builder.build_load(unsafe { gen_info.builder.build_struct_gep(lambda_ptr, 1, "") }, "").into_pointer_value();
-                        let mut envs_val: Vec<_> = env_ty
-                            .element_tys
-                            .into_iter()
-                            .enumerate()
-                            .map(|(idx, _)| {
-                                let pointer =
-                                    unsafe { gen_info.builder.build_struct_gep(envs_tuple_pointer, idx as u32, "") };
-                                gen_info.builder.build_load(pointer, "")
-                            }).collect();
-                        envs_val.append(&mut params_val);
-                        gen_info.builder.build_call_pointer(func_pointer, &envs_val, "")
-                    } else {
-                        let func_pointer = self.func.code_gen(gen_info).into_pointer_value();
-
-                        gen_info.builder.build_call_pointer(func_pointer, &params_val, "")
-                    };
-                callsite.try_as_basic_value().left().unwrap()
+        //環境をスタックに置いたラムダ式の呼び出しか
+        let mut stack_env = false;
+        let (mut callsite, applied) = match func {
+            mir::ExprMir::GlobalVariableMir(x) => {
+                match gen_info.func_list.get(&x.id).and_then(|f| f.arity.clone()) {
+                    //引数が揃っていればカリー化で作られたラムダ式の関数をまとめて呼ぶ
+                    Some(arity) if arity.arity <= args_val.len() => {
+                        let func_ty = gen_info.ty_info.look_up(&x.ty_id);
+                        let (param_types, ret_type) = func_ty.split_curried(arity.arity).unwrap();
+                        let direct_ty = Type::create_func_type(arity.arg_indices.iter().map(|&i| param_types[i].clone()).collect(), ret_type);
+                        let func = get_direct_function(&arity, &direct_ty, gen_info.module, gen_info.builder, &mut gen_info.ty_info.clone(), gen_info.func_list, gen_info.assump, gen_info.debug_info);
+                        let args: Vec<_> = Some(env_ptr_type().const_null().as_basic_value_enum()).into_iter()
+                            .chain(arity.arg_indices.iter().map(|&i| args_val[i]))
+                            .collect();
+                        (build_direct_call(gen_info.builder, func, &args), arity.arity)
+                    }
+                    //トップレベルの関数はクロージャを作らずに直接呼ぶ
+                    _ => {
+                        let func = x.code_gen(gen_info);
+                        let args = [env_ptr_type().const_null().as_basic_value_enum(), args_val[0]];
+                        (build_direct_call(gen_info.builder, func, &args), 1)
+                    }
+                }
+            }
+            //その場で呼ぶラムダ式の環境は、呼ばれた関数が最初に読むだけなのでスタックに置ける
+            mir::ExprMir::LambdaMir(x) => {
+                stack_env = x.env.len() > 0;
+                let lambda = x.code_gen(gen_info, true);
+                (build_lambda_call(gen_info.builder, gen_info.function, lambda, vec![args_val[0]]), 1)
+            }
+            func => {
+                let lambda = func.code_gen(gen_info);
+                (build_lambda_call(gen_info.builder, gen_info.function, lambda, vec![args_val[0]]), 1)
             }
-            x => panic!("{:?}", x),
+        };
+        //残りの引数は戻り値のクロージャに1つずつ渡す
+        for arg in args_val.into_iter().skip(applied) {
+            let lambda = callsite.try_as_basic_value().left().unwrap();
+            callsite = build_lambda_call(gen_info.builder, gen_info.function, lambda, vec![arg]);
+        }
+        //スタックの環境を渡す呼び出しは末尾呼び出しにできない
+        if tail_flag && !(stack_env && applied == args_len) {
+            set_tail_call(&callsite);
+        }
+        let value = callsite.try_as_basic_value().left().unwrap();
+        if let Some(env_mark) = env_mark {
+            build_env_release(gen_info.module, gen_info.builder, env_mark);
         }
+        value
     }
+
+    //f a1 .. anの形の呼び出しを関数と引数のリストに分ける
+    fn flatten(self) -> (mir::ExprMir, Vec<mir::ExprMir>) {
+        let mut args = self.params;
+        let mut func = self.func;
+        loop {
+            func = match func {
+                mir::ExprMir::CallMir(x) => {
+                    let x = *x;
+                    args = x.params.into_iter().chain(args).collect();
+                    x.func
+                }
+                func => return (func, args),
+            };
+        }
+    }
+}
+
+fn build_direct_call(builder: &builder::Builder, func: values::FunctionValue, args: &[values::BasicValueEnum]) -> values::CallSiteValue {
+    let callsite = builder.build_call(func, args, "");
+    callsite.set_call_convention(FAST_CALL_CONV);
+    callsite
 }
 
 impl mir::TupleMir {
@@ -352,7 +768,7 @@ impl mir::TupleMir {
             .map(|x| x.code_gen(gen_info))
             .collect();
         let ty = types::StructType::struct_type(&elements_val.iter().map(|x| x.get_type()).collect::<Vec<_>>(), true);
-        let val = gen_info.builder.build_alloca(ty, "");
+        let val = build_entry_alloca(gen_info.function, ty);
         elements_val.into_iter().enumerate().for_each(|(id, x)| {
             let ptr = unsafe { gen_info.builder.build_struct_gep(val, id as u32, "") };
             gen_info.builder.build_store(ptr, x);
@@ -374,6 +790,9 @@ impl mir::OpMir {
             "*" => gen_info.builder.build_int_mul(lhs, rhs, ""),
             "==" => gen_info.builder.build_int_compare(IntPredicate::EQ, lhs, rhs, ""),
             "/" => {
+                //0で割ったらパニックする
+                let is_zero = gen_info.builder.build_int_compare(IntPredicate::EQ, rhs, types::IntType::i32_type().const_int(0, false), "");
+                build_panic_if(gen_info, is_zero, "attempt to divide by zero", self.pos);
                 let lhs = gen_info.builder.build_signed_int_to_float(lhs, types::FloatType::f64_type(), "");
                 let rhs = gen_info.builder.build_signed_int_to_float(rhs, types::FloatType::f64_type(), "");
                 gen_info.builder.build_float_to_signed_int(gen_info.builder.build_float_div(lhs, rhs, ""), types::IntType::i32_type(), "")
@@ -383,19 +802,59 @@ impl mir::OpMir {
     }
 }
 
+impl mir::PanicMir {
+    fn code_gen(
+        self,
+        gen_info: &mut GenInfo,
+    ) -> values::BasicValueEnum {
+        build_panic(gen_info, &self.message, self.pos);
+        //ここから先には到達しないが、式の値が必要なので型に合わせたundefを返す
+        let dead_block = gen_info.function.append_basic_block(&"after_panic");
+        gen_info.builder.position_at_end(&dead_block);
+        gen_info.ty_info.look_up(&self.ty_id).get_undef()
+    }
+}
+
+//ランタイムのruscall_panicを呼んでメッセージと位置を表示し、終了する
+fn build_panic(gen_info: &GenInfo, message: &str, pos: SourcePosition) {
+    let i8_ptr_type = types::IntType::i8_type().ptr_type(AddressSpace::Generic).as_basic_type_enum();
+    let i32_type = types::IntType::i32_type();
+    let func_type = types::VoidType::void_type().fn_type(&[i8_ptr_type, i8_ptr_type, i32_type.as_basic_type_enum(), i32_type.as_basic_type_enum()], false);
+    let panic_func = get_runtime_function(gen_info.module, "ruscall_panic", func_type);
+    let message = gen_info.builder.build_global_string_ptr(message, "");
+    let file = gen_info.builder.build_global_string_ptr(gen_info.debug_info.src_file_name(), "");
+    gen_info.builder.build_call(panic_func, &[
+        message.as_pointer_value().as_basic_value_enum(),
+        file.as_pointer_value().as_basic_value_enum(),
+        i32_type.const_int(pos.line as u64, false).as_basic_value_enum(),
+        i32_type.const_int(pos.column as u64, false).as_basic_value_enum(),
+    ], "");
+    gen_info.builder.build_unreachable();
+}
+
+//condが真ならパニックする
+fn build_panic_if(gen_info: &GenInfo, cond: values::IntValue, message: &str, pos: SourcePosition) {
+    let panic_block = gen_info.function.append_basic_block(&"panic");
+    let ok_block = gen_info.function.append_basic_block(&"ok");
+    gen_info.builder.build_conditional_branch(cond, &panic_block, &ok_block);
+    gen_info.builder.position_at_end(&panic_block);
+    build_panic(gen_info, message, pos);
+    gen_info.builder.position_at_end(&ok_block);
+}
+
 impl mir::LambdaMir {
+    //on_stackが立っていれば環境をスタックに置く。その場で呼び出すラムダ式にだけ使える
     fn code_gen(
         self,
         gen_info: &mut GenInfo,
+        on_stack: bool,
     ) -> values::BasicValueEnum {
         //ラムダ式の関数作成
         let func_ty = gen_info.ty_info.look_up(&self.func_id);
-        let func = get_function(&self.func_name, &func_ty, gen_info.module, gen_info.builder, &mut gen_info.ty_info.clone(), gen_info.func_list, gen_info.assump);
-        let func_llvm_ty = func_ty
-            .to_llvm_any_type(false).into_function_type();
+        let func = get_function(&self.func_name, &func_ty, gen_info.module, gen_info.builder, &mut gen_info.ty_info.clone(), gen_info.func_list, gen_info.assump, gen_info.debug_info);
 
-        if self.env.len() == 0 {
-            func.as_any_value_enum().as_pointer_value().as_basic_value_enum()
+        let env_ptr = if self.env.len() == 0 {
+            env_ptr_type().const_null()
         } else {
             //環境の値取得
             let env_val: Vec<_> = self
@@ -407,23 +866,19 @@ impl mir::LambdaMir {
             let env_llvm_ty =
                 types::StructType::struct_type(&env_val.iter().map(|x| x.get_type()).collect::<Vec<_>>(), true);
 
-            //ラムダ式の型生成
-            let lambda_ty = types::StructType::struct_type(&[env_llvm_ty.as_basic_type_enum(), func_llvm_ty.ptr_type(AddressSpace::Generic).as_basic_type_enum()], true);
-
-            //ラムダ式の生成
-            let env_tuple_val = gen_info.builder.build_alloca(env_llvm_ty, "");
+            //関数の外に返せるクロージャの環境は領域に置き、クロージャを含まない値を返す呼び出しが終わった時に解放する
+            let env_tuple_ptr = if on_stack {
+                build_entry_alloca(gen_info.function, env_llvm_ty)
+            } else {
+                build_env_alloc(gen_info, env_llvm_ty, self.pos)
+            };
             env_val.into_iter().enumerate().for_each(|(id, x)| {
-                let ptr = unsafe { gen_info.builder.build_struct_gep(env_tuple_val, id as u32, "") };
+                let ptr = unsafe { gen_info.builder.build_struct_gep(env_tuple_ptr, id as u32, "") };
                 gen_info.builder.build_store(ptr, x);
             });
-            let env_tuple_val = gen_info.builder.build_load(env_tuple_val, "");
-            let lambda_val = gen_info.builder.build_alloca(lambda_ty, "");
-            let env_tuple_ptr = unsafe { gen_info.builder.build_struct_gep(lambda_val, 0, "") };
-            gen_info.builder.build_store(env_tuple_ptr, env_tuple_val);
-            let func_ptr = unsafe { gen_info.builder.build_struct_gep(lambda_val, 1, "") };
-            gen_info.builder.build_store(func_ptr, func.as_any_value_enum().into_pointer_value().as_basic_value_enum());
-            gen_info.builder.build_load(lambda_val, "ret")
-        }
+            gen_info.builder.build_pointer_cast(env_tuple_ptr, env_ptr_type(), "")
+        };
+        build_closure(gen_info.builder, gen_info.function, func, env_ptr)
     }
 }
 
@@ -434,7 +889,7 @@ impl mir::IndexPropertyMir {
     ) -> values::BasicValueEnum {
         let expr_value = self.expr.clone().code_gen(gen_info);
         let expr_ty = expr_value.get_type();//self.expr.get_ty(gen_info.ty_info, gen_info.params_ty);
-        let expr_ptr = gen_info.builder.build_alloca(expr_ty, "");
+        let expr_ptr = build_entry_alloca(gen_info.function, expr_ty);
         gen_info.builder.build_store(expr_ptr, expr_value);
         let ptr = unsafe {
             gen_info.builder.build_struct_gep(
@@ -469,7 +924,7 @@ impl mir::NamePropertyMir {
             }
             _ => panic!("bug!")
         };
-        let expr_ptr = gen_info.builder.build_alloca(expr_ty.to_llvm_basic_type(), "");
+        let expr_ptr = build_entry_alloca(gen_info.function, expr_ty.to_llvm_basic_type());
         let expr_value = self.expr.code_gen(gen_info);
         gen_info.builder.build_store(expr_ptr, expr_value);
         let ptr = unsafe {
@@ -481,4 +936,39 @@ impl mir::NamePropertyMir {
         };
         gen_info.builder.build_load(ptr, "")
     }
+}
+
+impl mir::RecordUpdateMir {
+    fn code_gen(
+        self,
+        gen_info: &mut GenInfo,
+    ) -> values::BasicValueEnum {
+        //フィールドの位置は単相化した後の構造体の型から決める
+        let expr_ty = self.expr.get_ty(gen_info.ty_info, gen_info.params_ty);
+        let element_tys = match expr_ty {
+            Type::StructType(ref x) => {
+                match x.ty {
+                    StructInternalType::RecordType(ref x) => x.element_tys.clone(),
+                    _ => panic!("bug!")
+                }
+            }
+            _ => panic!("bug!")
+        };
+        let expr_ptr = build_entry_alloca(gen_info.function, expr_ty.to_llvm_basic_type());
+        let expr_value = self.expr.code_gen(gen_info);
+        gen_info.builder.build_store(expr_ptr, expr_value);
+        for (name, param) in self.params {
+            let index = element_tys.iter().position(|(x, _)| x == &name).unwrap();
+            let value = param.code_gen(gen_info);
+            let ptr = unsafe {
+                gen_info.builder.build_struct_gep(
+                    expr_ptr,
+                    index as u32,
+                    "",
+                )
+            };
+            gen_info.builder.build_store(ptr, value);
+        }
+        gen_info.builder.build_load(expr_ptr, "")
+    }
 }
\ No newline at end of file
@@ -0,0 +1,153 @@
+use super::linker::{LinkerKind, LinkMode};
+
+//ビルド時のオプション
+#[derive(Debug, PartialEq, Clone)]
+pub struct CompileOption {
+    pub opt_level: OptLevel,
+    pub linker: Option<LinkerKind>,
+    pub link_mode: LinkMode,
+    pub emit: EmitKind,
+    //中間ファイルを出力先の隣に残す
+    pub save_temps: bool,
+    pub target: Option<String>,
+    pub target_cpu: Option<String>,
+    pub target_features: Vec<String>,
+    //DWARFのデバッグ情報を出力する
+    pub debug_info: bool,
+}
+
+impl CompileOption {
+    pub fn new() -> CompileOption {
+        CompileOption {
+            opt_level: OptLevel::O0,
+            linker: None,
+            link_mode: LinkMode::Dynamic,
+            emit: EmitKind::Exe,
+            save_temps: false,
+            target: None,
+            target_cpu: None,
+            target_features: vec![],
+            debug_info: false,
+        }
+    }
+
+    //wasm32向けのコンパイルか
+    pub fn is_wasm(&self) -> bool {
+        self.target.as_ref().map_or(false, |target| target.starts_with("wasm32"))
+    }
+
+    //ターゲットのポインタのビット数
+    pub fn pointer_bits(&self) -> u64 {
+        match self.target {
+            Some(ref target) if ["wasm32", "i386", "i586", "i686", "arm", "thumb", "mips-", "mipsel-"].iter().any(|x| target.starts_with(x)) => 32,
+            Some(_) => 64,
+            None => if cfg!(target_pointer_width = "32") { 32 } else { 64 },
+        }
+    }
+
+    //出力ファイル名を省略した時に入力ファイル名に付ける拡張子
+    pub fn output_extension(&self) -> &'static str {
+        if self.is_wasm() && self.emit == EmitKind::Exe {
+            ".wasm"
+        } else {
+            self.emit.extension()
+        }
+    }
+
+    //値を次の引数で受け取るオプションか
+    pub fn takes_value(arg: &str) -> bool {
+        match arg {
+            "--target" | "--target-cpu" | "--target-feature" | "--linker" | "--emit" | "--crate-type" => true,
+            _ => false,
+        }
+    }
+
+    //オプションを1つ解釈する。知らないオプションならfalseを返す
+    //値を取るオプションは--name=valueの形で渡す
+    pub fn parse_arg(&mut self, arg: &str) -> bool {
+        match arg {
+            "-O0" => self.opt_level = OptLevel::O0,
+            "-O1" => self.opt_level = OptLevel::O1,
+            "-O2" => self.opt_level = OptLevel::O2,
+            "-O3" => self.opt_level = OptLevel::O3,
+            "-Os" => self.opt_level = OptLevel::Os,
+            "-static" => self.link_mode = LinkMode::Static,
+            "-dynamic" => self.link_mode = LinkMode::Dynamic,
+            "--save-temps" => self.save_temps = true,
+            "-g" => self.debug_info = true,
+            _ if arg.starts_with("--emit=") => match EmitKind::from_name(&arg["--emit=".len()..]) {
+                Some(emit) => self.emit = emit,
+                None => return false,
+            },
+            _ if arg.starts_with("--crate-type=") => match &arg["--crate-type=".len()..] {
+                "bin" => self.emit = EmitKind::Exe,
+                "staticlib" => self.emit = EmitKind::StaticLib,
+                "cdylib" => self.emit = EmitKind::Cdylib,
+                _ => return false,
+            },
+            _ if arg.starts_with("--target=") => self.target = Some(arg["--target=".len()..].to_string()),
+            _ if arg.starts_with("--target-cpu=") => self.target_cpu = Some(arg["--target-cpu=".len()..].to_string()),
+            _ if arg.starts_with("--target-feature=") => self.target_features.extend(
+                arg["--target-feature=".len()..].split(',').filter(|x| x.len() != 0).map(|x| x.to_string())
+            ),
+            _ if arg.starts_with("--linker=") => match LinkerKind::from_name(&arg["--linker=".len()..]) {
+                Some(linker) => self.linker = Some(linker),
+                None => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+//最適化レベル
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OptLevel {
+    O0,
+    O1,
+    O2,
+    O3,
+    Os,
+}
+
+//出力するファイルの種類
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EmitKind {
+    LlvmIr,
+    LlvmBc,
+    Asm,
+    Obj,
+    StaticLib,
+    //C ABIで関数を公開する共有ライブラリ
+    Cdylib,
+    Exe,
+}
+
+impl EmitKind {
+    pub fn from_name(name: &str) -> Option<EmitKind> {
+        match name {
+            "llvm-ir" => Some(EmitKind::LlvmIr),
+            "llvm-bc" => Some(EmitKind::LlvmBc),
+            "asm" => Some(EmitKind::Asm),
+            "obj" => Some(EmitKind::Obj),
+            "staticlib" => Some(EmitKind::StaticLib),
+            "cdylib" => Some(EmitKind::Cdylib),
+            "exe" => Some(EmitKind::Exe),
+            _ => None,
+        }
+    }
+
+    //出力ファイル名を省略した時に入力ファイル名に付ける拡張子
+    pub fn extension(&self) -> &'static str {
+        let windows = cfg!(target_os = "windows");
+        match self {
+            EmitKind::LlvmIr => ".ll",
+            EmitKind::LlvmBc => ".bc",
+            EmitKind::Asm => if windows { ".asm" } else { ".s" },
+            EmitKind::Obj => if windows { ".obj" } else { ".o" },
+            EmitKind::StaticLib => if windows { ".lib" } else { ".a" },
+            EmitKind::Cdylib => if windows { ".dll" } else if cfg!(target_os = "macos") { ".dylib" } else { ".so" },
+            EmitKind::Exe => if windows { ".exe" } else { ".out" },
+        }
+    }
+}
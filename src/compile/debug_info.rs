@@ -0,0 +1,220 @@
+extern crate inkwell;
+extern crate llvm_sys;
+
+use self::inkwell::values::{AsValueRef, FunctionValue, InstructionValue};
+use self::llvm_sys::prelude::LLVMValueRef;
+use super::types::*;
+use combine::stream::state::SourcePosition;
+use std::cell::Cell;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_uint};
+use std::path::Path;
+use std::ptr;
+
+//src/llvm_shim/debug_info.cppの型
+enum RuscallDIBuilder {}
+
+enum DIType {}
+
+enum DISubprogram {}
+
+extern "C" {
+    fn ruscall_di_builder_create(function: LLVMValueRef, file_name: *const c_char, directory: *const c_char, optimized: bool) -> *mut RuscallDIBuilder;
+    fn ruscall_di_builder_finalize(di: *mut RuscallDIBuilder);
+    fn ruscall_di_basic_type(di: *mut RuscallDIBuilder, name: *const c_char, size_in_bits: u64, encoding: c_uint) -> *mut DIType;
+    fn ruscall_di_pointer_type(di: *mut RuscallDIBuilder, name: *const c_char, size_in_bits: u64) -> *mut DIType;
+    fn ruscall_di_struct_type(
+        di: *mut RuscallDIBuilder, name: *const c_char, size_in_bits: u64,
+        member_names: *const *const c_char, member_types: *const *mut DIType, member_sizes: *const u64, member_count: c_uint,
+    ) -> *mut DIType;
+    fn ruscall_di_create_function(
+        di: *mut RuscallDIBuilder, function: LLVMValueRef, name: *const c_char, line: c_uint,
+        types: *const *mut DIType, type_count: c_uint, optimized: bool,
+    ) -> *mut DISubprogram;
+    fn ruscall_di_declare_parameter(
+        di: *mut RuscallDIBuilder, scope: *mut DISubprogram, function: LLVMValueRef, arg_index: c_uint, arg_no: c_uint,
+        name: *const c_char, ty: *mut DIType, line: c_uint,
+    );
+    fn ruscall_di_set_location(instruction: LLVMValueRef, scope: *mut DISubprogram, line: c_uint, column: c_uint);
+}
+
+const DW_ATE_BOOLEAN: c_uint = 0x02;
+const DW_ATE_SIGNED: c_uint = 0x05;
+
+//DWARFのデバッグ情報を生成する。-gがなければソースファイル名を持つだけで何もしない
+pub struct DebugInfo {
+    //パニックのメッセージに使うソースファイル名
+    src_file_name: String,
+    src_file: Option<(CString, CString)>,
+    optimized: bool,
+    pointer_bits: u64,
+    //最初の関数を生成した時にモジュールと一緒に作る
+    builder: Cell<*mut RuscallDIBuilder>,
+}
+
+//関数のデバッグ情報上のスコープ
+#[derive(Clone, Copy)]
+pub struct DIScope(*mut DISubprogram);
+
+impl DebugInfo {
+    pub fn new(src_file_name: &str, optimized: bool, pointer_bits: u64) -> DebugInfo {
+        let path = Path::new(src_file_name);
+        let file_name = path.file_name().map(|x| x.to_string_lossy().to_string()).unwrap_or(src_file_name.to_string());
+        let directory = path.parent()
+            .and_then(|x| x.canonicalize().ok())
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or(".".to_string());
+        DebugInfo {
+            src_file_name: src_file_name.to_string(),
+            src_file: Some((CString::new(file_name).unwrap(), CString::new(directory).unwrap())),
+            optimized,
+            pointer_bits,
+            builder: Cell::new(ptr::null_mut()),
+        }
+    }
+
+    pub fn disabled(src_file_name: &str) -> DebugInfo {
+        DebugInfo {
+            src_file_name: src_file_name.to_string(),
+            src_file: None,
+            optimized: false,
+            pointer_bits: 64,
+            builder: Cell::new(ptr::null_mut()),
+        }
+    }
+
+    pub fn src_file_name(&self) -> &str {
+        &self.src_file_name
+    }
+
+    //関数のサブプログラムを作る。tyは関数の型
+    pub fn create_function(&self, function: FunctionValue, name: &str, ty: &Type, pos: SourcePosition) -> Option<DIScope> {
+        let (file_name, directory) = self.src_file.as_ref()?;
+        if self.builder.get().is_null() {
+            self.builder.set(unsafe {
+                ruscall_di_builder_create(function.as_value_ref(), file_name.as_ptr(), directory.as_ptr(), self.optimized)
+            });
+        }
+        let func_ty = &ty.get_lambda_ty().func_ty;
+        let types = Some(&func_ty.ret_type)
+            .into_iter()
+            .chain(func_ty.param_types.iter())
+            .map(|x| self.di_type(x))
+            .collect::<Vec<_>>();
+        let name = CString::new(name).unwrap();
+        let subprogram = unsafe {
+            ruscall_di_create_function(
+                self.builder.get(), function.as_value_ref(), name.as_ptr(), pos.line as c_uint,
+                types.as_ptr(), types.len() as c_uint, self.optimized,
+            )
+        };
+        Some(DIScope(subprogram))
+    }
+
+    //引数の名前と型を記録する。first_argは最初の引数のLLVMの関数での位置
+    pub fn declare_parameters(&self, scope: DIScope, function: FunctionValue, param_names: &[String], param_tys: &[Type], first_arg: usize, pos: SourcePosition) {
+        param_names.iter().zip(param_tys.iter()).enumerate().for_each(|(i, (name, ty))| {
+            let name = CString::new(name.as_str()).unwrap();
+            unsafe {
+                ruscall_di_declare_parameter(
+                    self.builder.get(), scope.0, function.as_value_ref(), (first_arg + i) as c_uint, (i + 1) as c_uint,
+                    name.as_ptr(), self.di_type(ty), pos.line as c_uint,
+                )
+            }
+        });
+    }
+
+    //startの次の命令からブロックの最後まで、位置が付いていない命令にposを付ける
+    pub fn set_location(&self, scope: DIScope, start: Option<InstructionValue>, pos: SourcePosition) {
+        let mut instruction = start;
+        while let Some(inst) = instruction {
+            unsafe { ruscall_di_set_location(inst.as_value_ref(), scope.0, pos.line as c_uint, pos.column as c_uint) };
+            instruction = inst.get_next_instruction();
+        }
+    }
+
+    //関数全体の位置が付いていない命令に関数の位置を付ける
+    pub fn set_function_location(&self, scope: DIScope, function: FunctionValue, pos: SourcePosition) {
+        let mut block = function.get_first_basic_block();
+        while let Some(b) = block {
+            self.set_location(scope, b.get_first_instruction(), pos);
+            block = b.get_next_basic_block();
+        }
+    }
+
+    //モジュールの検証前に呼ぶ
+    pub fn finalize(&self) {
+        if !self.builder.get().is_null() {
+            unsafe { ruscall_di_builder_finalize(self.builder.get()) };
+            self.builder.set(ptr::null_mut());
+        }
+    }
+
+    fn di_type(&self, ty: &Type) -> *mut DIType {
+        let name = CString::new(ty.show()).unwrap();
+        match ty {
+            Type::TCon { name: ty_name } if ty_name == "Int32" => unsafe {
+                ruscall_di_basic_type(self.builder.get(), name.as_ptr(), 32, DW_ATE_SIGNED)
+            },
+            Type::TCon { name: ty_name } if ty_name == "Bool" => unsafe {
+                ruscall_di_basic_type(self.builder.get(), name.as_ptr(), 8, DW_ATE_BOOLEAN)
+            },
+            Type::TupleType(x) => self.di_struct_type(
+                &name,
+                x.element_tys.iter().enumerate().map(|(i, ty)| (format!("_{}", i), ty.clone())).collect(),
+            ),
+            Type::StructType(x) => self.di_struct_type(
+                &CString::new(x.name.as_str()).unwrap(),
+                match x.ty {
+                    StructInternalType::TupleType(ref x) => x.element_tys.iter().enumerate().map(|(i, ty)| (format!("_{}", i), ty.clone())).collect(),
+                    StructInternalType::RecordType(ref x) => x.element_tys.clone(),
+                },
+            ),
+            //クロージャは{env, fn}のポインタの組
+            Type::LambdaType(_) => {
+                let env_name = CString::new("env").unwrap();
+                let fn_name = CString::new("fn").unwrap();
+                let member_names = [env_name.as_ptr(), fn_name.as_ptr()];
+                let member_types = unsafe {
+                    [
+                        ruscall_di_pointer_type(self.builder.get(), env_name.as_ptr(), self.pointer_bits),
+                        ruscall_di_pointer_type(self.builder.get(), name.as_ptr(), self.pointer_bits),
+                    ]
+                };
+                let member_sizes = [self.pointer_bits, self.pointer_bits];
+                unsafe {
+                    ruscall_di_struct_type(
+                        self.builder.get(), name.as_ptr(), self.pointer_bits * 2,
+                        member_names.as_ptr(), member_types.as_ptr(), member_sizes.as_ptr(), 2,
+                    )
+                }
+            }
+            //単相化された後には現れない
+            _ => ptr::null_mut(),
+        }
+    }
+
+    fn di_struct_type(&self, name: &CString, members: Vec<(String, Type)>) -> *mut DIType {
+        let member_names = members.iter().map(|(name, _)| CString::new(name.as_str()).unwrap()).collect::<Vec<_>>();
+        let member_name_ptrs = member_names.iter().map(|x| x.as_ptr()).collect::<Vec<_>>();
+        let member_types = members.iter().map(|(_, ty)| self.di_type(ty)).collect::<Vec<_>>();
+        let member_sizes = members.iter().map(|(_, ty)| self.size_in_bits(ty)).collect::<Vec<_>>();
+        unsafe {
+            ruscall_di_struct_type(
+                self.builder.get(), name.as_ptr(), member_sizes.iter().sum(),
+                member_name_ptrs.as_ptr(), member_types.as_ptr(), member_sizes.as_ptr(), members.len() as c_uint,
+            )
+        }
+    }
+
+    //タプルと構造体はパディングなしで並ぶ
+    fn size_in_bits(&self, ty: &Type) -> u64 {
+        match ty {
+            Type::TCon { name } if name == "Int32" => 32,
+            Type::TupleType(x) => x.element_tys.iter().map(|x| self.size_in_bits(x)).sum(),
+            Type::StructType(x) => (0..x.get_elements_len()).map(|i| self.size_in_bits(x.get_elements_at(i))).sum(),
+            Type::LambdaType(_) => self.pointer_bits * 2,
+            _ => 8,
+        }
+    }
+}
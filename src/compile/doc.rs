@@ -0,0 +1,188 @@
+use super::ir::ast::*;
+use super::semantic_analysis::type_inference::assump_env::AssumpEnv;
+use super::types::ShowType;
+
+//ドキュメントの出力形式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocFormat {
+    Markdown,
+    Html,
+}
+
+//宣言1つ分のドキュメント
+struct DocItem {
+    name: String,
+    //型や結合規則を表す宣言の形
+    signature: String,
+    //構造体のフィールド
+    fields: Vec<String>,
+    doc: Option<String>,
+}
+
+//関数は推論した型スキーム、構造体はフィールド、演算子は結合規則と優先順位を並べたドキュメントを生成する
+pub fn doc(title: &str, ast: &ProgramAST, assump: &AssumpEnv, format: DocFormat) -> String {
+    let mut funcs: Vec<DocItem> = vec![];
+    let mut structs = vec![];
+    let mut operators = vec![];
+    for stmt in ast.stmt_list.iter() {
+        match stmt {
+            StmtAST::DefFuncAST(x) => add_func(&mut funcs, &x.name, None, &x.doc, assump),
+            StmtAST::DecFuncAST(x) => add_func(&mut funcs, &x.name, Some(&x.ty), &x.doc, assump),
+            StmtAST::DecStructAST(x) => structs.push(DocItem {
+                name: x.ty.name.clone(),
+                signature: "struct ".to_string() + &x.ty.name,
+                fields: show_fields(&x.ty.ty),
+                doc: x.doc.clone(),
+            }),
+            StmtAST::InfixAST(x) => {
+                let fixity = match x.ty {
+                    InfixType::Left => "infixl",
+                    InfixType::Right => "infixr",
+                };
+                operators.push(DocItem {
+                    name: x.op.clone(),
+                    signature: format!("{} {} {}", fixity, x.priority.0, x.op),
+                    fields: vec![],
+                    doc: x.doc.clone(),
+                })
+            }
+            StmtAST::NoneAST => (),
+        }
+    }
+    let sections = vec![("Functions", funcs), ("Structs", structs), ("Operators", operators)]
+        .into_iter()
+        .filter(|(_, items)| items.len() != 0)
+        .collect::<Vec<_>>();
+    match format {
+        DocFormat::Markdown => markdown(title, &sections),
+        DocFormat::Html => html(title, &sections),
+    }
+}
+
+//節や型宣言で同じ名前が何度も出てくるので1つにまとめる
+fn add_func(funcs: &mut Vec<DocItem>, name: &String, ty: Option<&FuncTypeAST>, doc: &Option<String>, assump: &AssumpEnv) {
+    if let Some(item) = funcs.iter_mut().find(|x| &x.name == name) {
+        if item.doc.is_none() {
+            item.doc = doc.clone();
+        }
+        return;
+    }
+    //型検査で型スキームが決まらなかった宣言は書かれた型を使う
+    let ty = match (assump.global_get(name), ty) {
+        (Some(scheme), _) => scheme.show(),
+        (None, Some(ty)) => show_func_type_ast(ty),
+        (None, None) => "_".to_string(),
+    };
+    funcs.push(DocItem {
+        name: name.clone(),
+        signature: format!("{} :: {}", name, ty),
+        fields: vec![],
+        doc: doc.clone(),
+    });
+}
+
+fn show_fields(ty: &StructInternalTypeAST) -> Vec<String> {
+    match ty {
+        StructInternalTypeAST::RecordTypeAST(x) => x.elements_ty
+            .iter()
+            .map(|(name, ty)| format!("{}: {}", name, show_type_ast(ty)))
+            .collect(),
+        StructInternalTypeAST::TupleTypeAST(x) => x.elements_ty
+            .iter()
+            .enumerate()
+            .map(|(idx, ty)| format!("{}: {}", idx, show_type_ast(ty)))
+            .collect(),
+    }
+}
+
+//型をShowTypeと同じ書式で表示する
+pub fn show_type_ast(ty: &TypeAST) -> String {
+    match ty {
+        TypeAST::Type(x) => x.show(),
+        TypeAST::IdTypeAST(x) | TypeAST::TypeVarName(x) => x.clone(),
+        TypeAST::FuncTypeAST(x) => "(".to_string() + &show_func_type_ast(x) + ")",
+        TypeAST::TupleTypeAST(x) => {
+            "(".to_string()
+                + &x.elements_ty.iter().map(show_type_ast).collect::<Vec<_>>().join(",")
+                + ")"
+        }
+        TypeAST::StructTypeAST(x) => x.name.clone(),
+        TypeAST::RowTypeAST(x) => {
+            let fields = x.elements_ty
+                .iter()
+                .map(|(name, ty)| name.clone() + ":" + &show_type_ast(ty))
+                .collect::<Vec<_>>();
+            let row = x.row_name.as_ref().map(|x| "|".to_string() + x).unwrap_or_default();
+            "{".to_string() + &fields.join(",") + &row + "}"
+        }
+    }
+}
+
+fn show_func_type_ast(ty: &FuncTypeAST) -> String {
+    ty.params_ty
+        .iter()
+        .fold("".to_string(), |acc, x| acc + &show_type_ast(x) + "->")
+        + &show_type_ast(&ty.ret_ty)
+}
+
+fn markdown(title: &str, sections: &[(&str, Vec<DocItem>)]) -> String {
+    let mut out = format!("# {}\n", title);
+    for (section, items) in sections {
+        out += &format!("\n## {}\n", section);
+        for item in items {
+            out += &format!("\n### `{}`\n\n```\n{}\n```\n", item.name, item.signature);
+            if item.fields.len() != 0 {
+                out += "\n";
+                item.fields.iter().for_each(|x| out += &format!("- `{}`\n", x));
+            }
+            //ドキュメントコメントはそのままMarkdownとして出力する
+            if let Some(ref doc) = item.doc {
+                out += &format!("\n{}\n", doc);
+            }
+        }
+    }
+    out
+}
+
+fn html(title: &str, sections: &[(&str, Vec<DocItem>)]) -> String {
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n",
+        title = escape_html(title)
+    );
+    for (section, items) in sections {
+        out += &format!("<h2>{}</h2>\n", section);
+        for item in items {
+            out += &format!(
+                "<h3><code>{}</code></h3>\n<pre><code>{}</code></pre>\n",
+                escape_html(&item.name),
+                escape_html(&item.signature)
+            );
+            if item.fields.len() != 0 {
+                out += "<ul>\n";
+                item.fields.iter().for_each(|x| out += &format!("<li><code>{}</code></li>\n", escape_html(x)));
+                out += "</ul>\n";
+            }
+            //空行で段落を分ける
+            if let Some(ref doc) = item.doc {
+                doc.split("\n\n")
+                    .filter(|x| x.trim().len() != 0)
+                    .for_each(|x| out += &format!("<p>{}</p>\n", escape_html(x.trim())));
+            }
+        }
+    }
+    out + "</body>\n</html>\n"
+}
+
+fn escape_html(s: &str) -> String {
+    s.chars().fold(String::new(), |acc, c| match c {
+        '&' => acc + "&amp;",
+        '<' => acc + "&lt;",
+        '>' => acc + "&gt;",
+        '"' => acc + "&quot;",
+        c => {
+            let mut acc = acc;
+            acc.push(c);
+            acc
+        }
+    })
+}
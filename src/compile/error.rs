@@ -1,7 +1,7 @@
 use combine::easy;
 use combine::stream::state::SourcePosition;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Error {
     pos: SourcePosition,
     msg: String,
@@ -25,6 +25,18 @@ impl Error {
             self.pos.line, self.pos.column, self.msg
         )
     }
+    pub fn to_warning_string(&self) -> String {
+        format!(
+            "\nwarning!\n\nposition:\nline:{} column:{}\n\nmessage:\n{}\n",
+            self.pos.line, self.pos.column, self.msg
+        )
+    }
+    pub fn pos(&self) -> SourcePosition {
+        self.pos
+    }
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
     pub fn new(pos: SourcePosition, msg: &str) -> Error {
         Error {
             pos,
@@ -0,0 +1,595 @@
+use super::error::Error;
+use super::ir::ast::*;
+use super::parser;
+use super::types::ShowType;
+use combine::stream::state::SourcePosition;
+
+//1行の幅
+const WIDTH: usize = 80;
+const INDENT: usize = 4;
+
+//ソースを決まった書式に整える
+//コメントは構文木に残らないので別に集め、文の前か、文の最後の行の後ろに置き直す
+//文の途中にあったコメントは、その後ろの字句の前に置く
+pub fn format_src(src: &str) -> Result<String, String> {
+    if parser::has_layout_pragma(src) {
+        return Err("layout files can not be formatted".to_string());
+    }
+    let stmt_list = parser::parse_spanned(src)
+        .map_err(|err| Error::from_parse_error(err).to_string())?
+        .0;
+    let mut comments = scan_comments(src).into_iter().peekable();
+    let mut out = Output { text: String::new(), last_line: None };
+    for (start, stmt, end) in stmt_list.iter() {
+        let mut printer = Printer { src, comments: vec![] };
+        while comments.peek().map(|x| is_before(&x.start, end)).unwrap_or(false) {
+            let comment = comments.next().unwrap();
+            if is_before(&comment.start, start) {
+                out.push_comment(&comment, comment.start.line);
+            } else {
+                printer.comments.push(comment);
+            }
+        }
+        let doc = printer.stmt_doc(stmt);
+        //後ろに字句のないコメントは文の前に置く
+        for comment in printer.comments.iter() {
+            out.push(start.line, comment.end.line, &comment.text);
+        }
+        out.push(start.line, end.line, &render(&doc, WIDTH));
+    }
+    for comment in comments {
+        out.push_comment(&comment, comment.start.line);
+    }
+    if !out.text.is_empty() {
+        out.text.push('\n');
+    }
+    Ok(out.text)
+}
+
+fn is_before(a: &SourcePosition, b: &SourcePosition) -> bool {
+    (a.line, a.column) < (b.line, b.column)
+}
+
+struct Output {
+    text: String,
+    //直前に出力したもののソース上の最後の行
+    last_line: Option<i32>,
+}
+
+impl Output {
+    //前のものと空行が1行以上あった所には空行を1行入れる
+    fn push(&mut self, start_line: i32, end_line: i32, text: &str) {
+        if let Some(line) = self.last_line {
+            self.text.push('\n');
+            if start_line > line + 1 {
+                self.text.push('\n');
+            }
+        }
+        self.text += text;
+        self.last_line = Some(self.last_line.map(|x| x.max(end_line)).unwrap_or(end_line));
+    }
+
+    //前の文と同じ行から始まるコメントはその文の後ろに付ける
+    fn push_comment(&mut self, comment: &Comment, start_line: i32) {
+        if self.last_line == Some(start_line) {
+            self.text.push(' ');
+            self.text += &comment.text;
+            self.last_line = Some(comment.end.line);
+        } else {
+            self.push(start_line, comment.end.line, &comment.text);
+        }
+    }
+}
+
+struct Comment {
+    start: SourcePosition,
+    end: SourcePosition,
+    text: String,
+}
+
+//skipperと同じ規則でコメントを探す。文字列リテラルはないので//と/*はいつもコメントの始まり
+fn scan_comments(src: &str) -> Vec<Comment> {
+    let mut comments = vec![];
+    let mut pos = SourcePosition { line: 1, column: 1 };
+    let mut chars = src.chars().peekable();
+    while let Some(c) = chars.next() {
+        let start = pos;
+        advance(&mut pos, c);
+        match (c, chars.peek().cloned()) {
+            ('/', Some('/')) => {
+                let mut text = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c == '\n' { break; }
+                    chars.next();
+                    advance(&mut pos, c);
+                    text.push(c);
+                }
+                comments.push(Comment { start, end: pos, text: text.trim_end().to_string() });
+            }
+            ('/', Some('*')) => {
+                let mut text = c.to_string();
+                let mut depth = 0;
+                while let Some(c) = chars.next() {
+                    advance(&mut pos, c);
+                    text.push(c);
+                    match (c, chars.peek().cloned()) {
+                        ('*', Some('/')) if text.len() > 2 => {
+                            chars.next();
+                            advance(&mut pos, '/');
+                            text.push('/');
+                            if depth == 0 { break; }
+                            depth -= 1;
+                        }
+                        ('/', Some('*')) => {
+                            chars.next();
+                            advance(&mut pos, '*');
+                            text.push('*');
+                            depth += 1;
+                        }
+                        _ => (),
+                    }
+                }
+                comments.push(Comment { start, end: pos, text });
+            }
+            _ => (),
+        }
+    }
+    comments
+}
+
+//combineのSourcePositionと同じ数え方で位置を進める
+fn advance(pos: &mut SourcePosition, c: char) {
+    if c == '\n' {
+        pos.line += 1;
+        pos.column = 1;
+    } else {
+        pos.column += 1;
+    }
+}
+
+//Wadlerのpretty printerと同じ考え方の文書
+//Groupは中身が1行に収まれば平らに、収まらなければLineで改行して出力する
+enum Doc {
+    Text(String),
+    //平らな時は中の文字列、改行する時は改行とインデントになる
+    Line(&'static str),
+    //いつも改行する
+    HardLine,
+    //改行する時だけ出力する
+    IfBreak(&'static str),
+    Nest(Box<Doc>),
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+fn text<S: Into<String>>(s: S) -> Doc {
+    Doc::Text(s.into())
+}
+
+fn line() -> Doc {
+    Doc::Line(" ")
+}
+
+fn softline() -> Doc {
+    Doc::Line("")
+}
+
+fn nest(doc: Doc) -> Doc {
+    Doc::Nest(Box::new(doc))
+}
+
+fn group(doc: Doc) -> Doc {
+    Doc::Group(Box::new(doc))
+}
+
+fn concat(docs: Vec<Doc>) -> Doc {
+    Doc::Concat(docs)
+}
+
+//間にsepの作る区切りを入れて並べる
+fn join<F: Fn() -> Vec<Doc>>(docs: Vec<Doc>, sep: F) -> Vec<Doc> {
+    let mut result = vec![];
+    for (idx, doc) in docs.into_iter().enumerate() {
+        if idx != 0 {
+            result.extend(sep());
+        }
+        result.push(doc);
+    }
+    result
+}
+
+fn render(doc: &Doc, width: usize) -> String {
+    let mut out = String::new();
+    let mut column = 0;
+    //(インデント, 平らかどうか, 文書)
+    let mut stack = vec![(0, false, doc)];
+    while let Some((indent, flat, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(s) => {
+                out += s;
+                column += s.chars().count();
+            }
+            Doc::Line(s) if flat => {
+                out += s;
+                column += s.len();
+            }
+            Doc::Line(_) | Doc::HardLine => {
+                out.push('\n');
+                out += &" ".repeat(indent);
+                column = indent;
+            }
+            Doc::IfBreak(s) => if !flat {
+                out += s;
+                column += s.len();
+            },
+            Doc::Nest(x) => stack.push((indent + INDENT, flat, x)),
+            Doc::Group(x) => {
+                let flat = flat || fits(width as isize - column as isize, (indent, x), &stack);
+                stack.push((indent, flat, x));
+            }
+            Doc::Concat(x) => x.iter().rev().for_each(|x| stack.push((indent, flat, x))),
+        }
+    }
+    //空行のインデントを消す
+    out.lines().map(|x| x.trim_end()).collect::<Vec<_>>().join("\n")
+}
+
+//docを平らにして、続きの最初の改行までが残りの幅に収まるか
+fn fits(mut rest_width: isize, (indent, doc): (usize, &Doc), rest: &[(usize, bool, &Doc)]) -> bool {
+    let mut stack = vec![(indent, true, doc)];
+    let mut rest = rest.iter().rev();
+    while rest_width >= 0 {
+        let (indent, flat, doc) = match stack.pop() {
+            Some(x) => x,
+            None => match rest.next() {
+                Some(&x) => x,
+                None => return true,
+            }
+        };
+        match doc {
+            Doc::Text(s) => rest_width -= s.chars().count() as isize,
+            Doc::Line(s) if flat => rest_width -= s.len() as isize,
+            Doc::Line(_) => return true,
+            Doc::HardLine => return !flat,
+            Doc::IfBreak(s) => if !flat {
+                rest_width -= s.len() as isize
+            },
+            Doc::Nest(x) => stack.push((indent + INDENT, flat, x)),
+            Doc::Group(x) => stack.push((indent, flat, x)),
+            Doc::Concat(x) => x.iter().rev().for_each(|x| stack.push((indent, flat, x))),
+        }
+    }
+    false
+}
+
+//{ a, b }のように並べ、収まらなければ1行に1つずつ並べて最後にも,を付ける
+fn braces(docs: Vec<Doc>) -> Doc {
+    if docs.is_empty() {
+        return text("{}");
+    }
+    group(concat(vec![
+        text("{"),
+        nest(concat(vec![line(), concat(join(docs, || vec![text(","), line()])), Doc::IfBreak(",")])),
+        line(),
+        text("}"),
+    ]))
+}
+
+//(a, b)のように並べる。要素が1つのタプルは(a,)になる
+fn parens(docs: Vec<Doc>) -> Doc {
+    let single = docs.len() == 1;
+    group(concat(vec![
+        text("("),
+        nest(concat(vec![
+            softline(),
+            concat(join(docs, || vec![text(","), line()])),
+            if single { text(",") } else { Doc::IfBreak(",") },
+        ])),
+        softline(),
+        text(")"),
+    ]))
+}
+
+//文の中のコメントをその後ろの字句の前に置きながら文書を作る
+struct Printer<'a> {
+    src: &'a str,
+    //まだ置いていない文の中のコメント
+    comments: Vec<Comment>,
+}
+
+impl<'a> Printer<'a> {
+    //posの字句の前にあるコメントを置いてから字句を書く
+    //行コメントの後は改行し、ブロックコメントの後は空白を入れる
+    fn token<S: Into<String>>(&mut self, pos: SourcePosition, s: S) -> Doc {
+        let mut docs = vec![];
+        while self.comments.first().map(|x| is_before(&x.start, &pos)).unwrap_or(false) {
+            let comment = self.comments.remove(0);
+            let sep = if comment.text.starts_with("//") { Doc::HardLine } else { text(" ") };
+            docs.push(text(comment.text));
+            docs.push(sep);
+        }
+        docs.push(text(s));
+        concat(docs)
+    }
+
+    //posから始まる識別子
+    fn word_at(&self, pos: SourcePosition) -> String {
+        self.src.lines()
+            .nth((pos.line - 1) as usize)
+            .unwrap_or("")
+            .chars()
+            .skip((pos.column - 1) as usize)
+            .take_while(|&c| c.is_alphanumeric() || c == '_')
+            .collect()
+    }
+
+    fn stmt_doc(&mut self, stmt: &StmtAST) -> Doc {
+        match stmt {
+            StmtAST::InfixAST(x) => {
+                let fixity = match x.ty {
+                    InfixType::Left => "infixl",
+                    InfixType::Right => "infixr",
+                };
+                text(format!("{} {} {};", fixity, x.priority.0, x.op))
+            }
+            StmtAST::DecStructAST(x) => {
+                let ty = match x.ty.ty {
+                    StructInternalTypeAST::RecordTypeAST(ref x) => concat(vec![
+                        text(" "),
+                        braces(x.elements_ty.iter().map(|(name, ty)| text(format!("{}: {}", name, show_type(ty)))).collect()),
+                    ]),
+                    StructInternalTypeAST::TupleTypeAST(ref x) => text(show_type(&TypeAST::TupleTypeAST(Box::new(x.clone())))),
+                };
+                concat(vec![text("struct "), text(x.ty.name.clone()), ty, text(";")])
+            }
+            StmtAST::DefFuncAST(x) => concat(vec![self.def_func_doc(x), text(";")]),
+            StmtAST::DecFuncAST(x) => {
+                let prefix = if x.extern_flag {
+                    "ex "
+                } else if x.export_flag {
+                    "export "
+                } else {
+                    ""
+                };
+                text(format!("{}{} :: {};", prefix, x.name, show_func_type(&x.ty)))
+            }
+            StmtAST::NoneAST => text(""),
+        }
+    }
+
+    fn def_func_doc(&mut self, x: &DefFuncAST) -> Doc {
+        let name = self.token(x.pos, x.name.clone());
+        let clause = match x.body {
+            ExprAST::MatchAST(ref x) => &x.clauses[0],
+            ref body => {
+                let params = self.params_doc(&x.params, &x.patterns);
+                let head = head_doc(name, params);
+                return self.rhs(head, body);
+            }
+        };
+        //節とガードとwhereはfrom_clausesで1つの節にまとめられている
+        //ガードを書かなかった節には節の位置のtrueのガードが入っている
+        let params = clause.patterns.iter().map(|x| self.pattern_doc(x)).collect();
+        let head = head_doc(name, params);
+        let guarded = match clause.guards.as_slice() {
+            [(ExprAST::BoolAST(BoolAST { bool: true, pos }), _)] => *pos != clause.pos,
+            _ => true,
+        };
+        let mut docs = vec![];
+        match guarded {
+            false => docs.push(self.rhs(head, &clause.guards[0].1)),
+            true => {
+                docs.push(head);
+                let mut guards = vec![];
+                for (cond, body) in clause.guards.iter() {
+                    //otherwiseとtrueは書いた通りに残す
+                    let cond = match cond {
+                        ExprAST::BoolAST(BoolAST { bool: true, pos }) if self.word_at(*pos) == "otherwise" => self.token(*pos, "otherwise"),
+                        cond => self.expr_doc(cond),
+                    };
+                    let guard = self.rhs(concat(vec![text("| "), cond]), body);
+                    guards.push(concat(vec![Doc::HardLine, guard]));
+                }
+                docs.push(nest(concat(guards)));
+            }
+        }
+        if !clause.where_list.is_empty() {
+            let where_list = clause.where_list.iter()
+                .map(|x| concat(vec![Doc::HardLine, self.def_func_doc(x), text(";")]))
+                .collect();
+            let where_doc = concat(vec![
+                text("where {"),
+                nest(concat(where_list)),
+                Doc::HardLine,
+                text("}"),
+            ]);
+            //ガードの後のwhereは次の行に書く
+            if guarded {
+                docs.push(nest(concat(vec![Doc::HardLine, where_doc])));
+            } else {
+                docs.push(concat(vec![text(" "), where_doc]));
+            }
+        }
+        concat(docs)
+    }
+
+    fn params_doc(&mut self, params: &Vec<VariableAST>, patterns: &Vec<(usize, PatternAST)>) -> Vec<Doc> {
+        params.iter().enumerate()
+            .map(|(idx, param)| match patterns.iter().find(|(x, _)| *x == idx) {
+                Some((_, pattern)) => self.pattern_doc(pattern),
+                None => self.token(param.pos, param.id.clone()),
+            })
+            .collect()
+    }
+
+    //head = body。収まらなければ本体を次の行に書く。ifとラムダは=の後ろから書き始める
+    fn rhs(&mut self, head: Doc, body: &ExprAST) -> Doc {
+        match body {
+            ExprAST::IfAST(_) | ExprAST::LambdaAST(_) => concat(vec![head, text(" = "), self.expr_doc(body)]),
+            _ => {
+                let body = self.expr_doc(body);
+                group(concat(vec![head, text(" ="), nest(concat(vec![line(), body]))]))
+            }
+        }
+    }
+
+    fn expr_doc(&mut self, expr: &ExprAST) -> Doc {
+        match expr {
+            ExprAST::OpAST(_) => {
+                //左に結合した演算子の列を並べ直す
+                let mut ops = vec![];
+                let mut expr = expr;
+                while let ExprAST::OpAST(x) = expr {
+                    ops.push(x);
+                    expr = &x.l_expr;
+                }
+                let first = self.expr_doc(expr);
+                let rest = ops.into_iter().rev()
+                    .map(|x| {
+                        let op = self.token(x.pos, x.op.clone() + " ");
+                        concat(vec![line(), op, self.expr_doc(&x.r_expr)])
+                    })
+                    .collect();
+                group(concat(vec![first, nest(concat(rest))]))
+            }
+            ExprAST::NumAST(x) => self.token(x.pos, x.num.to_string()),
+            ExprAST::BoolAST(x) => self.token(x.pos, x.bool.to_string()),
+            ExprAST::PanicAST(x) => self.token(x.pos, "panic"),
+            ExprAST::VariableAST(x) => self.token(x.pos, x.id.clone()),
+            ExprAST::IfAST(x) => {
+                let keyword = self.token(x.pos, "if ");
+                let cond = self.expr_doc(&x.cond);
+                let t_expr = self.expr_doc(&x.t_expr);
+                let f_expr = self.expr_doc(&x.f_expr);
+                group(concat(vec![
+                    keyword,
+                    cond,
+                    text(" {"),
+                    nest(concat(vec![line(), t_expr])),
+                    line(),
+                    text("} else {"),
+                    nest(concat(vec![line(), f_expr])),
+                    line(),
+                    text("}"),
+                ]))
+            }
+            ExprAST::ParenAST(x) => concat(vec![text("("), self.expr_doc(&x.expr), text(")")]),
+            ExprAST::FuncCallAST(_) => {
+                let mut calls = vec![];
+                let mut expr = expr;
+                while let ExprAST::FuncCallAST(x) = expr {
+                    calls.push(x);
+                    expr = &x.func;
+                }
+                let func = self.expr_doc(expr);
+                let params = calls.into_iter().rev()
+                    .map(|x| concat(vec![line(), self.expr_doc(&x.param)]))
+                    .collect();
+                group(concat(vec![func, nest(concat(params))]))
+            }
+            ExprAST::NamedParamsConstructorCallAST(x) => {
+                let name = self.token(x.pos, x.constructor_name.clone() + " ");
+                let params = x.params.iter().map(|(name, expr)| self.field_doc(name, expr)).collect();
+                concat(vec![name, braces(params)])
+            }
+            ExprAST::RecordUpdateAST(x) => {
+                let expr = self.expr_doc(&x.expr);
+                let params = x.params.iter()
+                    .map(|(name, expr)| concat(vec![text(name.clone() + " = "), self.expr_doc(expr)]))
+                    .collect();
+                concat(vec![expr, text(" "), braces(params)])
+            }
+            ExprAST::TupleAST(x) if x.elements.is_empty() => self.token(x.pos, "()"),
+            ExprAST::TupleAST(x) => parens(x.elements.iter().map(|x| self.expr_doc(x)).collect()),
+            ExprAST::IndexPropertyAST(x) => concat(vec![self.expr_doc(&x.expr), text(format!(".{}", x.index))]),
+            ExprAST::NamePropertyAST(x) => concat(vec![self.expr_doc(&x.expr), text(format!(".{}", x.property_name))]),
+            ExprAST::LambdaAST(x) => {
+                let mut docs = vec![self.token(x.pos, "\\")];
+                if let Some(ref env) = x.env {
+                    docs.push(text(format!("[{}] ", env.iter().map(|x| x.id.clone()).collect::<Vec<_>>().join(", "))));
+                }
+                let params = self.params_doc(&x.params, &x.patterns);
+                if !params.is_empty() {
+                    docs.extend(join(params, || vec![text(", ")]));
+                    docs.push(text(" "));
+                }
+                docs.push(text("->"));
+                let body = self.expr_doc(&x.body);
+                docs.push(nest(concat(vec![line(), body])));
+                group(concat(docs))
+            }
+            //構文解析では作られない
+            ExprAST::MatchAST(_) | ExprAST::TupleStructAST(_) | ExprAST::PatternCheckAST(_) => panic!("bug"),
+        }
+    }
+
+    //フィールド名と同じ名前の変数は省略して書く
+    fn field_doc(&mut self, name: &str, expr: &ExprAST) -> Doc {
+        match expr {
+            ExprAST::VariableAST(x) if x.id == name => self.token(x.pos, name.to_string()),
+            expr => concat(vec![text(name.to_string() + " = "), self.expr_doc(expr)]),
+        }
+    }
+
+    fn pattern_doc(&mut self, pattern: &PatternAST) -> Doc {
+        match pattern {
+            PatternAST::VariableAST(x) => self.token(x.pos, x.id.clone()),
+            PatternAST::NumAST(x) => self.token(x.pos, x.num.to_string()),
+            PatternAST::BoolAST(x) => self.token(x.pos, x.bool.to_string()),
+            PatternAST::TupleAST(x) if x.elements.is_empty() => self.token(x.pos, "()"),
+            PatternAST::TupleAST(x) => parens(x.elements.iter().map(|x| self.pattern_doc(x)).collect()),
+            PatternAST::RecordAST(x) => {
+                let name = self.token(x.pos, x.constructor_name.clone() + " ");
+                let fields = x.fields.iter()
+                    .map(|(name, pattern)| match pattern {
+                        PatternAST::VariableAST(x) if &x.id == name => self.token(x.pos, name.clone()),
+                        pattern => concat(vec![text(name.clone() + " = "), self.pattern_doc(pattern)]),
+                    })
+                    .collect();
+                concat(vec![name, braces(fields)])
+            }
+        }
+    }
+}
+
+fn head_doc(name: Doc, params: Vec<Doc>) -> Doc {
+    let mut docs = vec![name];
+    for param in params {
+        docs.push(text(" "));
+        docs.push(param);
+    }
+    concat(docs)
+}
+
+fn show_type(ty: &TypeAST) -> String {
+    match ty {
+        TypeAST::Type(x) => x.show(),
+        TypeAST::IdTypeAST(x) | TypeAST::TypeVarName(x) => x.clone(),
+        TypeAST::FuncTypeAST(x) => "(".to_string() + &show_func_type(x) + ")",
+        TypeAST::TupleTypeAST(x) => match x.elements_ty.len() {
+            1 => "(".to_string() + &show_type(&x.elements_ty[0]) + ",)",
+            _ => "(".to_string() + &x.elements_ty.iter().map(show_type).collect::<Vec<_>>().join(", ") + ")",
+        },
+        TypeAST::StructTypeAST(x) => x.name.clone(),
+        TypeAST::RowTypeAST(x) => {
+            let fields = x.elements_ty.iter()
+                .map(|(name, ty)| format!("{}: {}", name, show_type(ty)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            match x.row_name {
+                Some(ref row) if fields.is_empty() => format!("{{| {}}}", row),
+                Some(ref row) => format!("{{{} | {}}}", fields, row),
+                None => format!("{{{}}}", fields),
+            }
+        }
+    }
+}
+
+//引数の関数型は括弧で囲み、戻り値の関数型はそのまま続ける
+fn show_func_type(ty: &FuncTypeAST) -> String {
+    let ret = match ty.ret_ty {
+        TypeAST::FuncTypeAST(ref x) => show_func_type(x),
+        ref x => show_type(x),
+    };
+    ty.params_ty.iter().map(show_type).fold(String::new(), |acc, x| acc + &x + " -> ") + &ret
+}
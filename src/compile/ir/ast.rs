@@ -1,5 +1,6 @@
 use super::super::types::types::*;
 use combine::stream::state::SourcePosition;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ProgramAST {
@@ -15,6 +16,19 @@ pub enum StmtAST {
     NoneAST,
 }
 
+impl StmtAST {
+    //直前の///コメントを宣言に付ける
+    pub fn with_doc(self, doc: Option<String>) -> StmtAST {
+        match self {
+            StmtAST::InfixAST(x) => StmtAST::InfixAST(InfixAST { doc, ..x }),
+            StmtAST::DecStructAST(x) => StmtAST::DecStructAST(DecStructAST { doc, ..x }),
+            StmtAST::DefFuncAST(x) => StmtAST::DefFuncAST(DefFuncAST { doc, ..x }),
+            StmtAST::DecFuncAST(x) => StmtAST::DecFuncAST(DecFuncAST { doc, ..x }),
+            StmtAST::NoneAST => StmtAST::NoneAST,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
 pub struct Priority(pub i8);
 
@@ -23,6 +37,8 @@ pub struct InfixAST {
     pub ty: InfixType,
     pub op: String,
     pub priority: Priority,
+    //ドキュメントコメント
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -35,6 +51,8 @@ pub enum InfixType {
 pub struct DecStructAST {
     pub pos: SourcePosition,
     pub ty: StructTypeAST,
+    //ドキュメントコメント
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -47,11 +65,15 @@ pub enum ExprAST {
     ParenAST(Box<ParenAST>),
     FuncCallAST(Box<FuncCallAST>),
     NamedParamsConstructorCallAST(Box<NamedParamsConstructorCallAST>),
+    RecordUpdateAST(Box<RecordUpdateAST>),
+    MatchAST(Box<MatchAST>),
     TupleAST(Box<TupleAST>),
     TupleStructAST(Box<TupleStructAST>),
     IndexPropertyAST(Box<IndexPropertyAST>),
     NamePropertyAST(Box<NamePropertyAST>),
     LambdaAST(Box<LambdaAST>),
+    PatternCheckAST(Box<PatternCheckAST>),
+    PanicAST(PanicAST),
 }
 
 impl ExprAST {
@@ -86,6 +108,9 @@ impl ExprAST {
     pub fn create_named_params_constructor_call_ast(constructor_name: String, params: Vec<(String, ExprAST)>, pos: SourcePosition) -> ExprAST {
         ExprAST::NamedParamsConstructorCallAST(Box::new(NamedParamsConstructorCallAST { constructor_name, params, pos }))
     }
+    pub fn create_record_update_ast(expr: ExprAST, params: Vec<(String, ExprAST)>, pos: SourcePosition) -> ExprAST {
+        ExprAST::RecordUpdateAST(Box::new(RecordUpdateAST { expr, params, pos }))
+    }
     pub fn create_tuple_ast(elements: Vec<ExprAST>, pos: SourcePosition) -> ExprAST {
         ExprAST::TupleAST(Box::new(TupleAST { elements, pos }))
     }
@@ -113,8 +138,8 @@ impl ExprAST {
             }
         }
     }
-    pub fn create_lambda_ast(env: Vec<VariableAST>, params: Vec<VariableAST>, body: ExprAST, pos: SourcePosition) -> ExprAST {
-        ExprAST::LambdaAST(Box::new(LambdaAST { env, params, body, pos }))
+    pub fn create_lambda_ast(env: Option<Vec<VariableAST>>, params: Vec<VariableAST>, body: ExprAST, pos: SourcePosition) -> ExprAST {
+        ExprAST::LambdaAST(Box::new(LambdaAST { env, params, patterns: vec![], body, pos }))
     }
 
     pub fn get_pos(&self) -> SourcePosition {
@@ -127,11 +152,15 @@ impl ExprAST {
             ExprAST::VariableAST(x) => x.pos,
             ExprAST::FuncCallAST(x) => x.func.get_pos(),
             ExprAST::NamedParamsConstructorCallAST(x) => x.pos,
+            ExprAST::RecordUpdateAST(x) => x.pos,
+            ExprAST::MatchAST(x) => x.pos,
             ExprAST::TupleAST(x) => x.pos,
             ExprAST::TupleStructAST(x) => x.tuple.pos,
             ExprAST::LambdaAST(x) => x.pos,
             ExprAST::IndexPropertyAST(x) => x.pos,
-            ExprAST::NamePropertyAST(x) => x.pos
+            ExprAST::NamePropertyAST(x) => x.pos,
+            ExprAST::PatternCheckAST(x) => x.pos,
+            ExprAST::PanicAST(x) => x.pos,
         }
     }
 }
@@ -157,6 +186,13 @@ pub struct BoolAST {
     pub pos: SourcePosition,
 }
 
+//評価されると実行時エラーで終了する式
+#[derive(Debug, Clone, PartialEq)]
+pub struct PanicAST {
+    pub message: String,
+    pub pos: SourcePosition,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct IfAST {
     pub cond: ExprAST,
@@ -215,12 +251,96 @@ pub struct NamedParamsConstructorCallAST {
 
 }
 
+//複数の節で定義された関数の本体
+//exprsの値を上の節から順にパターンとガードで調べ、最初に合った節の式を評価する
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchAST {
+    pub exprs: Vec<ExprAST>,
+    pub clauses: Vec<ClauseAST>,
+    pub pos: SourcePosition,
+}
+
+//fact n | n == 0 = 1 | otherwise = n * fact (n - 1) のような節
+//ガードのない節はガードがtrueの節になる
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClauseAST {
+    pub patterns: Vec<PatternAST>,
+    //(ガード, 式)
+    pub guards: Vec<(ExprAST, ExprAST)>,
+    //whereで定義した局所関数
+    pub where_list: Vec<DefFuncAST>,
+    pub pos: SourcePosition,
+}
+
+//引数のパターンの形
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternShapeAST {
+    //要素の数
+    TupleAST(usize),
+    //構造体名
+    RecordAST(String),
+}
+
+//checksの式の型をパターンの形に固定してからbodyを評価する
+//引数のパターンからresolve_pattern_paramsで作られる
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternCheckAST {
+    pub checks: Vec<(ExprAST, PatternShapeAST)>,
+    pub body: ExprAST,
+    pub pos: SourcePosition,
+}
+
+//p { y = 5 }のようなレコード更新式
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordUpdateAST {
+    pub expr: ExprAST,
+    pub params: Vec<(String, ExprAST)>,
+    pub pos: SourcePosition,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct DefFuncAST {
     pub name: String,
     pub params: Vec<VariableAST>,
+    //分解するパターンの引数。(引数の位置, パターン)
+    pub patterns: Vec<(usize, PatternAST)>,
     pub body: ExprAST,
     pub pos: SourcePosition,
+    //ドキュメントコメント
+    pub doc: Option<String>,
+}
+
+impl DefFuncAST {
+    //節を並べた関数を作る。引数は#arg0, #arg1..で受け取る
+    pub fn from_clauses(name: String, params_len: usize, clauses: Vec<ClauseAST>, pos: SourcePosition) -> DefFuncAST {
+        let params = (0..params_len)
+            .map(|idx| VariableAST::new(format!("#arg{}", idx), pos))
+            .collect::<Vec<_>>();
+        let exprs = params.iter().map(|x| ExprAST::VariableAST(x.clone())).collect();
+        DefFuncAST {
+            name,
+            params,
+            patterns: vec![],
+            body: ExprAST::MatchAST(Box::new(MatchAST { exprs, clauses, pos })),
+            pos,
+            doc: None,
+        }
+    }
+
+    //関数の定義を節に分ける
+    pub fn into_clauses(self) -> Vec<ClauseAST> {
+        match self.body {
+            ExprAST::MatchAST(x) => x.clauses,
+            body => {
+                let mut patterns = self.patterns.into_iter().collect::<HashMap<_, _>>();
+                let patterns = self.params.into_iter().enumerate()
+                    .map(|(idx, param)| patterns.remove(&idx).unwrap_or(PatternAST::VariableAST(param)))
+                    .collect();
+                let guards = vec![(ExprAST::create_bool_ast(true, self.pos), body)];
+                vec![ClauseAST { patterns, guards, where_list: vec![], pos: self.pos }]
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -228,7 +348,11 @@ pub struct DecFuncAST {
     pub name: String,
     pub ty: FuncTypeAST,
     pub extern_flag: bool,
+    //C ABIで公開する
+    pub export_flag: bool,
     pub pos: SourcePosition,
+    //ドキュメントコメント
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -259,12 +383,66 @@ pub struct NamePropertyAST {
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct LambdaAST {
-    pub env: Vec<VariableAST>,
+    //キャプチャする変数の明示的な指定。Noneなら自由変数から求める
+    pub env: Option<Vec<VariableAST>>,
     pub params: Vec<VariableAST>,
+    pub patterns: Vec<(usize, PatternAST)>,
     pub body: ExprAST,
     pub pos: SourcePosition,
 }
 
+//引数の位置に書ける(a, b)やPoint{x, y}、0のようなパターン
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternAST {
+    VariableAST(VariableAST),
+    NumAST(NumAST),
+    BoolAST(BoolAST),
+    TupleAST(Box<TuplePatternAST>),
+    RecordAST(Box<RecordPatternAST>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuplePatternAST {
+    pub elements: Vec<PatternAST>,
+    pub pos: SourcePosition,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordPatternAST {
+    pub constructor_name: String,
+    pub fields: Vec<(String, PatternAST)>,
+    pub pos: SourcePosition,
+}
+
+impl PatternAST {
+    pub fn get_pos(&self) -> SourcePosition {
+        match self {
+            PatternAST::VariableAST(x) => x.pos,
+            PatternAST::NumAST(x) => x.pos,
+            PatternAST::BoolAST(x) => x.pos,
+            PatternAST::TupleAST(x) => x.pos,
+            PatternAST::RecordAST(x) => x.pos,
+        }
+    }
+
+    //引数のリストを、変数の引数と分解するパターンに分ける
+    //パターンの引数にはresolve_pattern_paramsで名前を付ける
+    pub fn split_params(params: Vec<PatternAST>) -> (Vec<VariableAST>, Vec<(usize, PatternAST)>) {
+        let mut patterns = vec![];
+        let params = params.into_iter().enumerate()
+            .map(|(idx, param)| match param {
+                PatternAST::VariableAST(x) => x,
+                pattern => {
+                    let pos = pattern.get_pos();
+                    patterns.push((idx, pattern));
+                    VariableAST::new("#pattern".to_string(), pos)
+                }
+            })
+            .collect();
+        (params, patterns)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TypeAST {
     Type(Type),
@@ -273,6 +451,7 @@ pub enum TypeAST {
     TupleTypeAST(Box<TupleTypeAST>),
     TypeVarName(String),
     StructTypeAST(StructTypeAST),
+    RowTypeAST(Box<RowTypeAST>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -286,6 +465,14 @@ pub struct TupleTypeAST {
     pub elements_ty: Vec<TypeAST>
 }
 
+//{x: a | r}のような、指定したフィールドを持つ任意の型
+//row_nameは残りのフィールドを表す型変数の名前
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowTypeAST {
+    pub elements_ty: Vec<(String, TypeAST)>,
+    pub row_name: Option<String>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct StructTypeAST {
     pub ty: StructInternalTypeAST,
@@ -1,4 +1,5 @@
 use super::ast::*;
+use super::super::error::Error;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -8,6 +9,8 @@ pub struct ProgramHir {
     pub dec_func_list: HashMap<String, DecFuncHir>,
     pub ex_dec_func_list: HashMap<String, ExDecFuncHir>,
     pub struct_list: HashMap<String, DecStructHir>,
+    //変換中に見つけた、コンパイルは続けられるが報告する問題
+    pub warning_list: Vec<Error>,
 }
 
 pub type InfixHir = InfixAST;
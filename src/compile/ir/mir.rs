@@ -2,6 +2,7 @@ use combine::stream::state::SourcePosition;
 use super::ast::*;
 use super::super::types::*;
 use super::super::semantic_analysis::type_env::TypeInfo;
+use super::super::error::Error;
 use indexmap::IndexMap;
 
 #[derive(Debug, PartialEq)]
@@ -12,8 +13,12 @@ pub struct ProgramMir {
 
     pub ex_dec_func_list: Vec<DecFuncMir>,
 
+    pub export_func_list: Vec<ExportFuncMir>,
+
     pub ty_info: TypeInfo,
 
+    //コンパイルは続けられるが報告する問題
+    pub warning_list: Vec<Error>,
 }
 
 impl ProgramMir {
@@ -22,7 +27,9 @@ impl ProgramMir {
             implicit_func_list: IndexMap::new(),
             explicit_func_list: vec![],
             ex_dec_func_list: vec![],
+            export_func_list: vec![],
             ty_info: TypeInfo::new(),
+            warning_list: vec![],
         }
     }
     pub fn get_func_mir(&self, name: &String) -> Option<&FuncMir> {
@@ -52,7 +59,39 @@ pub struct FuncMir {
     pub name: String,
     pub body: ExprMir,
     pub params_len: usize,
+    //デバッグ情報に使う引数の名前。ラムダ式は環境の変数が先に並ぶ
+    pub param_names: Vec<String>,
+    //ラムダ式の関数で環境から渡される引数の数
+    pub env_len: usize,
+    //arity解析の結果。カリー化する前に引数が2つ以上あったトップレベルの関数だけ持つ
+    pub arity: Option<ArityMir>,
     pub pos: SourcePosition,
+    //定義したトップレベルの関数の名前。ラムダ式の関数のシンボル名に使う
+    pub top_name: String,
+}
+
+impl FuncMir {
+    //ラムダ式の関数は#0のような名前なので、定義した関数の名前を付けたシンボル名にする
+    pub fn symbol_name(&self) -> String {
+        if self.name.starts_with('#') {
+            format!("{}#lambda{}", self.top_name, &self.name[1..])
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+//引数が揃った呼び出しをクロージャを作らずに行うための情報
+pub struct ArityMir {
+    //カリー化する前のトップレベルの関数の名前
+    pub name: String,
+    //カリー化で作られたラムダ式のうち、最後の引数を受け取る関数
+    pub func_name: String,
+    //カリー化する前の引数の数
+    pub arity: usize,
+    //func_nameの関数の引数(環境と引数)がそれぞれ何番目の引数か
+    pub arg_indices: Vec<usize>,
 }
 
 
@@ -70,12 +109,15 @@ pub enum ExprMir {
     LambdaMir(Box<LambdaMir>),
     IndexPropertyMir(Box<IndexPropertyMir>),
     NamePropertyMir(Box<NamePropertyMir>),
+    RecordUpdateMir(Box<RecordUpdateMir>),
+    PatternCheckMir(Box<PatternCheckMir>),
+    PanicMir(PanicMir),
 }
 
 impl ExprMir {
     pub fn get_pos(&self) -> SourcePosition {
         match self {
-            ExprMir::OpMir(x) => x.l_expr.get_pos(),
+            ExprMir::OpMir(x) => x.pos,
             ExprMir::NumMir(x) => x.pos,
             ExprMir::BoolMir(x) => x.pos,
             ExprMir::IfMir(x) => x.pos,
@@ -86,15 +128,19 @@ impl ExprMir {
             ExprMir::CallMir(x) => x.func.get_pos(),
             ExprMir::LambdaMir(x) => x.pos,
             ExprMir::IndexPropertyMir(x) => x.pos,
-            ExprMir::NamePropertyMir(x) => x.pos
+            ExprMir::NamePropertyMir(x) => x.pos,
+            ExprMir::RecordUpdateMir(x) => x.pos,
+            ExprMir::PatternCheckMir(x) => x.pos,
+            ExprMir::PanicMir(x) => x.pos,
         }
     }
 
-    pub fn create_op_mir(op: String, l_expr: ExprMir, r_expr: ExprMir) -> ExprMir {
+    pub fn create_op_mir(op: String, l_expr: ExprMir, r_expr: ExprMir, pos: SourcePosition) -> ExprMir {
         ExprMir::OpMir(Box::new(OpMir {
             op,
             l_expr,
             r_expr,
+            pos,
         }))
     }
     pub fn create_if_mir(cond: ExprMir, t_expr: ExprMir, f_expr: ExprMir, pos: SourcePosition, ty_id: TypeId) -> ExprMir {
@@ -145,6 +191,19 @@ impl ExprMir {
             }
         ))
     }
+    pub fn create_pattern_check_mir(checks: Vec<(ExprMir, PatternShapeAST, Type)>, body: ExprMir, pos: SourcePosition) -> ExprMir {
+        ExprMir::PatternCheckMir(Box::new(PatternCheckMir { checks, body, pos }))
+    }
+    pub fn create_record_update_mir(expr: ExprMir, params: Vec<(String, ExprMir)>, pos: SourcePosition, ty_id: TypeId) -> ExprMir {
+        ExprMir::RecordUpdateMir(Box::new(
+            RecordUpdateMir {
+                expr,
+                params,
+                pos,
+                ty_id,
+            }
+        ))
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -152,6 +211,14 @@ pub struct OpMir {
     pub op: String,
     pub l_expr: ExprMir,
     pub r_expr: ExprMir,
+    pub pos: SourcePosition,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PanicMir {
+    pub message: String,
+    pub pos: SourcePosition,
+    pub ty_id: TypeId,
 }
 
 pub type NumMir = NumAST;
@@ -195,6 +262,23 @@ pub struct NamePropertyMir {
     pub ty_id: TypeId,
 }
 
+//p { y = 5 }のようなレコード更新式。型はpと同じ
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordUpdateMir {
+    pub expr: ExprMir,
+    pub params: Vec<(String, ExprMir)>,
+    pub pos: SourcePosition,
+    pub ty_id: TypeId,
+}
+
+//checksの式の型をパターンの形の型に固定してからbodyを評価する
+#[derive(Clone, Debug, PartialEq)]
+pub struct PatternCheckMir {
+    pub checks: Vec<(ExprMir, PatternShapeAST, Type)>,
+    pub body: ExprMir,
+    pub pos: SourcePosition,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct VariableMir {
     pub id: usize,
@@ -233,3 +317,21 @@ pub struct DecFuncMir {
     pub extern_flag: bool,
     pub pos: SourcePosition,
 }
+
+#[derive(Clone, Debug, PartialEq)]
+//C ABIで公開する関数
+pub struct ExportFuncMir {
+    pub name: String,
+    //宣言された型の引数の数
+    pub arity: usize,
+    pub pos: SourcePosition,
+    //型推論後に決まる単相な型
+    pub ty: Option<Type>,
+}
+
+impl ExportFuncMir {
+    //カリー化された型を引数の型のリストと戻り値の型に分ける
+    pub fn split_ty(&self) -> Option<(Vec<Type>, Type)> {
+        self.ty.as_ref()?.split_curried(self.arity)
+    }
+}
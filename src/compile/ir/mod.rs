@@ -1,3 +1,4 @@
 pub mod ast;
 pub mod hir;
-pub mod mir;
\ No newline at end of file
+pub mod mir;
+mod variables;
\ No newline at end of file
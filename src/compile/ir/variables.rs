@@ -0,0 +1,263 @@
+use super::ast::*;
+use super::super::error::Error;
+
+//式の変数を調べたり置き換えたりする
+//boundはその位置で束縛されている変数で、束縛されていない変数だけを対象にする
+
+impl PatternAST {
+    //パターンで束縛される変数を集める
+    pub fn variables(&self, names: &mut Vec<String>) {
+        match self {
+            PatternAST::VariableAST(x) => names.push(x.id.clone()),
+            PatternAST::NumAST(_) | PatternAST::BoolAST(_) => (),
+            PatternAST::TupleAST(x) => x.elements.iter().for_each(|x| x.variables(names)),
+            PatternAST::RecordAST(x) => x.fields.iter().for_each(|(_, x)| x.variables(names)),
+        }
+    }
+}
+
+impl DefFuncAST {
+    //引数とパターンで束縛される変数
+    pub fn bound_variables(&self) -> Vec<String> {
+        let mut bound = self.params.iter().map(|x| x.id.clone()).collect::<Vec<_>>();
+        self.patterns.iter().for_each(|(_, x)| x.variables(&mut bound));
+        bound
+    }
+
+    pub fn free_variables(&self, bound: &mut Vec<String>, free: &mut Vec<String>) {
+        let len = bound.len();
+        bound.extend(self.bound_variables());
+        self.body.free_variables(bound, free);
+        bound.truncate(len);
+    }
+
+    pub fn replace_variables<F>(mut self, bound: &mut Vec<String>, f: &mut F) -> Result<DefFuncAST, Error>
+        where F: FnMut(VariableAST, &Vec<String>) -> Result<ExprAST, Error> {
+        let len = bound.len();
+        bound.extend(self.bound_variables());
+        self.body = self.body.replace_variables(bound, f)?;
+        bound.truncate(len);
+        Ok(self)
+    }
+}
+
+impl ClauseAST {
+    //節のパターンの変数と局所関数の名前
+    fn bound_variables(&self) -> Vec<String> {
+        let mut bound = vec![];
+        self.patterns.iter().for_each(|x| x.variables(&mut bound));
+        bound.extend(self.where_list.iter().map(|f| f.name.clone()));
+        bound
+    }
+}
+
+impl ExprAST {
+    //式の自由変数を出現順に集める
+    pub fn free_variables(&self, bound: &mut Vec<String>, free: &mut Vec<String>) {
+        match self {
+            ExprAST::NumAST(_) | ExprAST::BoolAST(_) | ExprAST::PanicAST(_) => (),
+            ExprAST::VariableAST(x) => push_free_variable(&x.id, bound, free),
+            ExprAST::IfAST(x) => {
+                x.cond.free_variables(bound, free);
+                x.t_expr.free_variables(bound, free);
+                x.f_expr.free_variables(bound, free);
+            }
+            ExprAST::OpAST(x) => {
+                x.l_expr.free_variables(bound, free);
+                x.r_expr.free_variables(bound, free);
+            }
+            ExprAST::ParenAST(x) => x.expr.free_variables(bound, free),
+            ExprAST::FuncCallAST(x) => {
+                x.func.free_variables(bound, free);
+                x.param.free_variables(bound, free);
+            }
+            ExprAST::NamedParamsConstructorCallAST(x) => x.params.iter().for_each(|(_, e)| e.free_variables(bound, free)),
+            ExprAST::RecordUpdateAST(x) => {
+                x.expr.free_variables(bound, free);
+                x.params.iter().for_each(|(_, e)| e.free_variables(bound, free));
+            }
+            ExprAST::MatchAST(x) => {
+                x.exprs.iter().for_each(|x| x.free_variables(bound, free));
+                for clause in x.clauses.iter() {
+                    let len = bound.len();
+                    bound.extend(clause.bound_variables());
+                    clause.guards.iter().for_each(|(guard, body)| {
+                        guard.free_variables(bound, free);
+                        body.free_variables(bound, free);
+                    });
+                    clause.where_list.iter().for_each(|f| f.free_variables(bound, free));
+                    bound.truncate(len);
+                }
+            }
+            ExprAST::TupleAST(x) => x.elements.iter().for_each(|x| x.free_variables(bound, free)),
+            ExprAST::TupleStructAST(x) => x.tuple.elements.iter().for_each(|x| x.free_variables(bound, free)),
+            ExprAST::LambdaAST(x) => {
+                x.env.iter().flat_map(|env| env.iter()).for_each(|x| push_free_variable(&x.id, bound, free));
+                let len = bound.len();
+                bound.extend(x.params.iter().map(|x| x.id.clone()));
+                x.patterns.iter().for_each(|(_, x)| x.variables(bound));
+                x.body.free_variables(bound, free);
+                bound.truncate(len);
+            }
+            ExprAST::IndexPropertyAST(x) => x.expr.free_variables(bound, free),
+            ExprAST::NamePropertyAST(x) => x.expr.free_variables(bound, free),
+            ExprAST::PatternCheckAST(x) => {
+                x.checks.iter().for_each(|(x, _)| x.free_variables(bound, free));
+                x.body.free_variables(bound, free);
+            }
+        }
+    }
+
+    //自由変数をfの返す式に置き換える
+    //ラムダ式で明示的にキャプチャした変数は、置き換えた式の元になる変数をキャプチャする
+    pub fn replace_variables<F>(self, bound: &mut Vec<String>, f: &mut F) -> Result<ExprAST, Error>
+        where F: FnMut(VariableAST, &Vec<String>) -> Result<ExprAST, Error> {
+        Ok(match self {
+            ExprAST::VariableAST(x) => {
+                if bound.contains(&x.id) {
+                    ExprAST::VariableAST(x)
+                } else {
+                    f(x, bound)?
+                }
+            }
+            ExprAST::NumAST(_) | ExprAST::BoolAST(_) | ExprAST::PanicAST(_) => self,
+            ExprAST::IfAST(x) => {
+                let mut x = *x;
+                x.cond = x.cond.replace_variables(bound, f)?;
+                x.t_expr = x.t_expr.replace_variables(bound, f)?;
+                x.f_expr = x.f_expr.replace_variables(bound, f)?;
+                ExprAST::IfAST(Box::new(x))
+            }
+            ExprAST::OpAST(x) => {
+                let mut x = *x;
+                x.l_expr = x.l_expr.replace_variables(bound, f)?;
+                x.r_expr = x.r_expr.replace_variables(bound, f)?;
+                ExprAST::OpAST(Box::new(x))
+            }
+            ExprAST::ParenAST(x) => {
+                let mut x = *x;
+                x.expr = x.expr.replace_variables(bound, f)?;
+                ExprAST::ParenAST(Box::new(x))
+            }
+            ExprAST::FuncCallAST(x) => {
+                let mut x = *x;
+                x.func = x.func.replace_variables(bound, f)?;
+                x.param = x.param.replace_variables(bound, f)?;
+                ExprAST::FuncCallAST(Box::new(x))
+            }
+            ExprAST::NamedParamsConstructorCallAST(x) => {
+                let mut x = *x;
+                x.params = x.params.into_iter()
+                    .map(|(name, e)| Ok((name, e.replace_variables(bound, f)?)))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                ExprAST::NamedParamsConstructorCallAST(Box::new(x))
+            }
+            ExprAST::RecordUpdateAST(x) => {
+                let mut x = *x;
+                x.expr = x.expr.replace_variables(bound, f)?;
+                x.params = x.params.into_iter()
+                    .map(|(name, e)| Ok((name, e.replace_variables(bound, f)?)))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                ExprAST::RecordUpdateAST(Box::new(x))
+            }
+            ExprAST::MatchAST(x) => {
+                let mut x = *x;
+                x.exprs = x.exprs.into_iter()
+                    .map(|x| x.replace_variables(bound, f))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                x.clauses = x.clauses.into_iter()
+                    .map(|mut clause| {
+                        let len = bound.len();
+                        bound.extend(clause.bound_variables());
+                        clause.guards = clause.guards.into_iter()
+                            .map(|(guard, body)| Ok((guard.replace_variables(bound, f)?, body.replace_variables(bound, f)?)))
+                            .collect::<Result<Vec<_>, Error>>()?;
+                        clause.where_list = clause.where_list.into_iter()
+                            .map(|x| x.replace_variables(bound, f))
+                            .collect::<Result<Vec<_>, Error>>()?;
+                        bound.truncate(len);
+                        Ok(clause)
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                ExprAST::MatchAST(Box::new(x))
+            }
+            ExprAST::TupleAST(x) => {
+                let mut x = *x;
+                x.elements = x.elements.into_iter()
+                    .map(|x| x.replace_variables(bound, f))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                ExprAST::TupleAST(Box::new(x))
+            }
+            ExprAST::TupleStructAST(x) => {
+                let mut x = *x;
+                x.tuple.elements = x.tuple.elements.into_iter()
+                    .map(|x| x.replace_variables(bound, f))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                ExprAST::TupleStructAST(Box::new(x))
+            }
+            ExprAST::LambdaAST(x) => {
+                let mut x = *x;
+                x.env = match x.env {
+                    Some(env) => {
+                        let mut new_env: Vec<VariableAST> = vec![];
+                        for v in env {
+                            let v = if bound.contains(&v.id) {
+                                v
+                            } else {
+                                match f(v.clone(), bound)?.root_variable() {
+                                    Some(root) => VariableAST { id: root.id.clone(), ..v },
+                                    None => v
+                                }
+                            };
+                            if new_env.iter().all(|x| x.id != v.id) {
+                                new_env.push(v);
+                            }
+                        }
+                        Some(new_env)
+                    }
+                    None => None
+                };
+                let len = bound.len();
+                bound.extend(x.params.iter().map(|x| x.id.clone()));
+                x.patterns.iter().for_each(|(_, x)| x.variables(bound));
+                x.body = x.body.replace_variables(bound, f)?;
+                bound.truncate(len);
+                ExprAST::LambdaAST(Box::new(x))
+            }
+            ExprAST::IndexPropertyAST(x) => {
+                let mut x = *x;
+                x.expr = x.expr.replace_variables(bound, f)?;
+                ExprAST::IndexPropertyAST(Box::new(x))
+            }
+            ExprAST::NamePropertyAST(x) => {
+                let mut x = *x;
+                x.expr = x.expr.replace_variables(bound, f)?;
+                ExprAST::NamePropertyAST(Box::new(x))
+            }
+            ExprAST::PatternCheckAST(x) => {
+                let mut x = *x;
+                x.checks = x.checks.into_iter()
+                    .map(|(e, shape)| Ok((e.replace_variables(bound, f)?, shape)))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                x.body = x.body.replace_variables(bound, f)?;
+                ExprAST::PatternCheckAST(Box::new(x))
+            }
+        })
+    }
+
+    //#pattern0.1のようなプロパティアクセスの元になる変数
+    fn root_variable(&self) -> Option<&VariableAST> {
+        match self {
+            ExprAST::VariableAST(x) => Some(x),
+            ExprAST::IndexPropertyAST(x) => x.expr.root_variable(),
+            ExprAST::NamePropertyAST(x) => x.expr.root_variable(),
+            _ => None
+        }
+    }
+}
+
+fn push_free_variable(id: &String, bound: &Vec<String>, free: &mut Vec<String>) {
+    if !bound.contains(id) && !free.contains(id) {
+        free.push(id.clone());
+    }
+}
@@ -0,0 +1,71 @@
+extern crate inkwell;
+
+use self::inkwell::execution_engine::ExecutionEngine;
+use self::inkwell::{module, OptimizationLevel};
+use std::mem;
+use std::os::raw::c_char;
+
+//src/stdlib/cのランタイム関数
+extern "C" {
+    fn print(a: i32) -> i32;
+    fn scan(a: i32) -> i32;
+    fn ruscall_env_alloc(size: u32) -> *mut u8;
+    fn ruscall_env_mark() -> *mut u8;
+    fn ruscall_env_release(mark: *mut u8);
+}
+
+//src/stdlib/c/jit.c
+extern "C" {
+    fn ruscall_jit_panic(message: *const c_char, file: *const c_char, line: i32, column: i32);
+    fn ruscall_jit_call_main(main_func: MainFunc, result: *mut i32) -> i32;
+}
+
+type MainFunc = unsafe extern "C" fn() -> i32;
+
+//パニックしたことを表すrun_mainのエラー
+pub const PANIC_ERROR: &str = "program panicked";
+//パニックした時の終了コード(src/stdlib/cと同じ)
+pub const PANIC_EXIT_CODE: i32 = 101;
+
+//モジュールから参照されているランタイム関数を実行エンジンに登録する
+//パニックはプロセスを終了させずにrun_mainへ戻るものに差し替える
+pub fn link_runtime(module: &module::Module, execution_engine: &ExecutionEngine) {
+    let runtime_func_list: Vec<(&str, usize)> = vec![
+        ("print", print as usize),
+        ("scan", scan as usize),
+        ("ruscall_panic", ruscall_jit_panic as usize),
+        ("ruscall_env_alloc", ruscall_env_alloc as usize),
+        ("ruscall_env_mark", ruscall_env_mark as usize),
+        ("ruscall_env_release", ruscall_env_release as usize),
+    ];
+    for (name, addr) in runtime_func_list {
+        if let Some(func) = module.get_function(name) {
+            execution_engine.add_global_mapping(&func, addr);
+        }
+    }
+}
+
+pub fn create_execution_engine(module: &module::Module) -> Result<ExecutionEngine, String> {
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::None)
+        .map_err(|err| err.to_string())?;
+    link_runtime(module, &execution_engine);
+    Ok(execution_engine)
+}
+
+//main関数をJITで実行し、その戻り値を返す
+//パニックした場合はメッセージを表示してPANIC_ERRORを返す
+pub fn run_main(module: &module::Module) -> Result<i32, String> {
+    let execution_engine = create_execution_engine(module)?;
+    let address = execution_engine
+        .get_function_address("main")
+        .map_err(|err| format!("{:?}", err))?;
+    unsafe {
+        let main: MainFunc = mem::transmute(address);
+        let mut result = 0;
+        if ruscall_jit_call_main(main, &mut result) == 0 {
+            return Err(PANIC_ERROR.to_string());
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,226 @@
+use super::compile_option::{CompileOption, EmitKind};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+//リンカーの種類
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LinkerKind {
+    Cc,
+    Clang,
+    Ld,
+    Msvc,
+    WasmLd,
+}
+
+//実行ファイルのリンク方法
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LinkMode {
+    Static,
+    Dynamic,
+}
+
+impl LinkerKind {
+    pub fn from_name(name: &str) -> Option<LinkerKind> {
+        match name {
+            "cc" | "gcc" => Some(LinkerKind::Cc),
+            "clang" => Some(LinkerKind::Clang),
+            "ld" => Some(LinkerKind::Ld),
+            "link" | "msvc" => Some(LinkerKind::Msvc),
+            "wasm-ld" => Some(LinkerKind::WasmLd),
+            _ => None,
+        }
+    }
+
+    //オプションで指定がなければ、wasmならwasm-ld、それ以外は環境変数RUSCALL_LINKERかプラットフォームで決める
+    pub fn from_option(option: &CompileOption) -> Result<LinkerKind, String> {
+        if let Some(linker) = option.linker {
+            return Ok(linker);
+        }
+        if option.is_wasm() {
+            return Ok(LinkerKind::WasmLd);
+        }
+        match env::var("RUSCALL_LINKER") {
+            Ok(name) => LinkerKind::from_name(&name)
+                .ok_or(format!("unknown linker `{}` in RUSCALL_LINKER", name)),
+            Err(_) => Ok(if cfg!(target_env = "msvc") { LinkerKind::Msvc } else { LinkerKind::Cc }),
+        }
+    }
+
+    //wasm-ldはバージョン付きの名前でインストールされることがあるので環境変数RUSCALL_WASM_LDで変えられる
+    fn command_name(&self) -> String {
+        match self {
+            LinkerKind::Cc => "cc".to_string(),
+            LinkerKind::Clang => "clang".to_string(),
+            LinkerKind::Ld => "ld".to_string(),
+            LinkerKind::Msvc => "link".to_string(),
+            LinkerKind::WasmLd => env::var("RUSCALL_WASM_LD").unwrap_or("wasm-ld".to_string()),
+        }
+    }
+}
+
+//オブジェクトファイルとランタイムをリンクして実行ファイルか共有ライブラリを作る
+//export_namesはC ABIで公開する関数の名前
+pub fn link(obj_file: &Path, output_file: &Path, option: &CompileOption, export_names: &[String]) -> Result<(), String> {
+    let linker = LinkerKind::from_option(option)?;
+    let shared = option.emit == EmitKind::Cdylib;
+    if linker == LinkerKind::WasmLd {
+        //外部関数はenvからのimportになるのでランタイムはリンクしない
+        let mut command = Command::new(linker.command_name());
+        command.args(&["--no-entry", "--allow-undefined"]);
+        if !shared {
+            command.arg("--export=main");
+        }
+        command
+            .args(export_names.iter().map(|x| format!("--export={}", x)))
+            .arg("-o")
+            .arg(output_file)
+            .arg(obj_file);
+        return exec(&mut command);
+    }
+    let runtime = runtime_lib(linker)?;
+    let mut command = Command::new(linker.command_name());
+    match linker {
+        LinkerKind::Cc | LinkerKind::Clang => {
+            command.arg(obj_file).arg(&runtime).arg("-o").arg(output_file);
+            if let (LinkerKind::Clang, Some(target)) = (linker, option.target.as_ref()) {
+                command.arg(format!("--target={}", target));
+            }
+            if shared {
+                command.arg("-shared");
+            } else if option.link_mode == LinkMode::Static {
+                command.arg("-static");
+            } else {
+                //パニック時のバックトレースで関数名を引けるようにシンボルを公開する
+                command.arg("-rdynamic");
+            }
+        }
+        LinkerKind::Ld => ld_args(&mut command, obj_file, &runtime, output_file, option.link_mode, shared)?,
+        LinkerKind::WasmLd => unreachable!(),
+        LinkerKind::Msvc => {
+            command
+                .arg("/NOLOGO")
+                .arg(format!("/OUT:{}", output_file.display()))
+                .arg(obj_file)
+                .arg(&runtime)
+                .arg(if option.link_mode == LinkMode::Static { "libcmt.lib" } else { "msvcrt.lib" })
+                .arg("kernel32.lib");
+            if shared {
+                command.arg("/DLL").args(export_names.iter().map(|x| format!("/EXPORT:{}", x)));
+            }
+        }
+    }
+    exec(&mut command)
+}
+
+//オブジェクトファイルとランタイムをまとめて静的ライブラリを作る
+pub fn archive(obj_file: &Path, output_file: &Path, option: &CompileOption) -> Result<(), String> {
+    let linker = LinkerKind::from_option(option)?;
+    if linker == LinkerKind::WasmLd {
+        //wasmではホスト向けのランタイムは含めない
+        let mut command = Command::new("ar");
+        command.arg("crs").arg(output_file).arg(obj_file);
+        return exec(&mut command);
+    }
+    let runtime = runtime_lib(linker)?;
+    let mut command = if linker == LinkerKind::Msvc {
+        let mut command = Command::new("lib");
+        command.arg("/NOLOGO").arg(format!("/OUT:{}", output_file.display())).arg(obj_file).arg(&runtime);
+        command
+    } else {
+        //ランタイムのアーカイブを複製して、そこにオブジェクトファイルを追加する
+        fs::copy(&runtime, output_file).map_err(|err| format!("failed to create {}: {}", output_file.display(), err))?;
+        let mut command = Command::new("ar");
+        command.arg("rs").arg(output_file).arg(obj_file);
+        command
+    };
+    exec(&mut command)
+}
+
+fn exec(command: &mut Command) -> Result<(), String> {
+    let output = command.output()
+        .map_err(|err| format!("failed to execute {:?}: {}", command, err))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{:?} failed with {}\n{}{}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+}
+
+//ランタイムライブラリのあるディレクトリ。実行時は環境変数RUSCALL_RUNTIME_DIRで上書きできる
+fn runtime_dir() -> PathBuf {
+    env::var("RUSCALL_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or(PathBuf::from(env!("RUSCALL_RUNTIME_DIR")))
+}
+
+fn runtime_lib(linker: LinkerKind) -> Result<PathBuf, String> {
+    let path = runtime_dir().join(if linker == LinkerKind::Msvc { "test.lib" } else { "libtest.a" });
+    if path.exists() {
+        Ok(path)
+    } else {
+        Err(format!("runtime library not found: {}", path.display()))
+    }
+}
+
+//ldを直接使う時はCランタイムの場所をccに問い合わせる
+//共有ライブラリの時はスタートアップルーチンを含めず、位置独立なcrtbeginS.o/crtendS.oを使う
+fn ld_args(command: &mut Command, obj_file: &Path, runtime: &Path, output_file: &Path, link_mode: LinkMode, shared: bool) -> Result<(), String> {
+    //見つからない時のccは名前をそのまま返すので、絶対パスで存在するかも確かめる
+    let crt = |name: &str| -> Result<PathBuf, String> {
+        let not_found = || format!("cannot locate {}, set RUSCALL_LINKER (e.g. RUSCALL_LINKER=cc)", name);
+        let output = Command::new("cc").arg(format!("-print-file-name={}", name)).output()
+            .map_err(|err| format!("{}: {}", not_found(), err))?;
+        let path = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+        if output.status.success() && path.is_absolute() && path.exists() {
+            Ok(path)
+        } else {
+            Err(not_found())
+        }
+    };
+    let libgcc_dir = crt("libgcc.a")?.parent().map(Path::to_path_buf).unwrap_or(PathBuf::new());
+    let libc_dir = crt(if link_mode == LinkMode::Static { "libc.a" } else { "libc.so" })?
+        .parent().map(Path::to_path_buf).unwrap_or(PathBuf::new());
+    command.arg("-o").arg(output_file);
+    if shared {
+        command.arg("-shared");
+    } else {
+        match link_mode {
+            LinkMode::Static => { command.arg("-static"); }
+            LinkMode::Dynamic => {
+                command.arg("-dynamic-linker").arg(dynamic_linker()).arg("--export-dynamic");
+            }
+        }
+        command.arg(crt("crt1.o")?);
+    }
+    command
+        .arg(crt("crti.o")?)
+        .arg(crt(if shared { "crtbeginS.o" } else { "crtbegin.o" })?)
+        .arg(obj_file)
+        .arg(runtime)
+        .arg(format!("-L{}", libgcc_dir.display()))
+        .arg(format!("-L{}", libc_dir.display()))
+        .args(&["--start-group", "-lc", "-lgcc"])
+        .args(if link_mode == LinkMode::Static { &["-lgcc_eh"][..] } else { &[][..] })
+        .arg("--end-group")
+        .arg(crt(if shared { "crtendS.o" } else { "crtend.o" })?)
+        .arg(crt("crtn.o")?);
+    Ok(())
+}
+
+fn dynamic_linker() -> &'static str {
+    if cfg!(target_arch = "aarch64") {
+        "/lib/ld-linux-aarch64.so.1"
+    } else if cfg!(target_arch = "x86") {
+        "/lib/ld-linux.so.2"
+    } else {
+        "/lib64/ld-linux-x86-64.so.2"
+    }
+}
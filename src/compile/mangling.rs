@@ -1,10 +1,190 @@
-use crate::compile::types::types::Type;
-
+use crate::compile::types::types::*;
+use crate::compile::types::show_type::tgen_name;
 
+//シンボル名のマングリング
+//  symbol := "_R" ident type
+//  ident  := 長さ(10進数) 名前のバイト列
+//  type   := "I32"                         Int32
+//          | "B"                           Bool
+//          | "N" ident                     その他の型コンストラクタ
+//          | "T" type* "E"                 タプル
+//          | "S" ident                     構造体(名前で区別する)
+//          | "F" type* "E" type            関数(引数と戻り値)
+//          | "G" 数字 "_"                  量化された型変数
+//          | "V" 数字 "_"                  型変数
+//例えば add::Int32->Int32->Int32 はカリー化されて _R3addFI32EFI32EI32 になる
 pub fn mangle(base_name: &String, ty: &Type) -> String {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
-    let mut hasher = DefaultHasher::new();
-    ty.hash(&mut hasher);
-    base_name.clone() + "#" + &hasher.finish().to_string()
+    let mut symbol = "_R".to_string();
+    mangle_ident(base_name, &mut symbol);
+    mangle_type(ty, &mut symbol);
+    symbol
+}
+
+fn mangle_ident(name: &str, symbol: &mut String) {
+    symbol.push_str(&name.len().to_string());
+    symbol.push_str(name);
+}
+
+fn mangle_types(tys: &[Type], symbol: &mut String) {
+    tys.iter().for_each(|ty| mangle_type(ty, symbol));
+    symbol.push('E');
+}
+
+fn mangle_type(ty: &Type, symbol: &mut String) {
+    match ty {
+        Type::TCon { name } if name == "Int32" => symbol.push_str("I32"),
+        Type::TCon { name } if name == "Bool" => symbol.push('B'),
+        Type::TCon { name } => {
+            symbol.push('N');
+            mangle_ident(name, symbol);
+        }
+        Type::TupleType(x) => {
+            symbol.push('T');
+            mangle_types(&x.element_tys, symbol);
+        }
+        Type::StructType(x) => {
+            symbol.push('S');
+            mangle_ident(&x.name, symbol);
+        }
+        Type::LambdaType(x) => {
+            symbol.push('F');
+            mangle_types(&x.func_ty.param_types, symbol);
+            mangle_type(&x.func_ty.ret_type, symbol);
+        }
+        Type::TGen(n, _) => symbol.push_str(&format!("G{}_", n)),
+        Type::TyVar(id) => symbol.push_str(&format!("V{}_", id.get_id())),
+    }
+}
+
+//マングリングされたシンボルを name::型 の形に戻す。シンボル全体を読めなければNone
+pub fn demangle(symbol: &str) -> Option<String> {
+    match demangle_prefix(symbol) {
+        Some((demangled, len)) if len == symbol.len() => Some(demangled),
+        _ => None,
+    }
+}
+
+//文字列中のマングリングされたシンボルをすべて戻す
+pub fn demangle_text(text: &str) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(index) = rest.find("_R") {
+        //識別子の途中の_Rはシンボルの先頭ではない
+        let in_ident = rest[..index].chars().last().map_or(false, |c| c.is_alphanumeric() || c == '_');
+        result.push_str(&rest[..index]);
+        rest = &rest[index..];
+        match if in_ident { None } else { demangle_prefix(rest) } {
+            Some((demangled, len)) => {
+                result.push_str(&demangled);
+                rest = &rest[len..];
+            }
+            None => {
+                result.push_str("_R");
+                rest = &rest[2..];
+            }
+        }
+    }
+    result + rest
+}
+
+//先頭のシンボルを戻して、読んだバイト数と一緒に返す
+fn demangle_prefix(symbol: &str) -> Option<(String, usize)> {
+    if !symbol.starts_with("_R") {
+        return None;
+    }
+    let mut demangler = Demangler { symbol, pos: 2 };
+    let name = demangler.ident()?;
+    let ty = match demangler.ty()? {
+        //トップレベルの関数の型は括弧を付けずに表示する
+        DemangledType::Func(x) => x,
+        DemangledType::Other(x) => x,
+    };
+    Some((format!("{}::{}", name, ty), demangler.pos))
+}
+
+enum DemangledType {
+    Func(String),
+    Other(String),
+}
+
+impl DemangledType {
+    fn show(self) -> String {
+        match self {
+            DemangledType::Func(x) => "(".to_string() + &x + ")",
+            DemangledType::Other(x) => x,
+        }
+    }
+}
+
+struct Demangler<'a> {
+    symbol: &'a str,
+    pos: usize,
+}
+
+impl<'a> Demangler<'a> {
+    fn eat(&mut self, s: &str) -> bool {
+        if self.symbol[self.pos..].starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let len = self.symbol[self.pos..].bytes().take_while(|c| c.is_ascii_digit()).count();
+        let n = self.symbol[self.pos..self.pos + len].parse().ok()?;
+        self.pos += len;
+        Some(n)
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        let len = self.number()?;
+        let ident = self.symbol.get(self.pos..self.pos + len)?.to_string();
+        self.pos += len;
+        Some(ident)
+    }
+
+    fn types(&mut self) -> Option<Vec<String>> {
+        let mut tys = vec![];
+        while !self.eat("E") {
+            tys.push(self.ty()?.show());
+        }
+        Some(tys)
+    }
+
+    fn func(&mut self) -> Option<String> {
+        let params = self.types()?;
+        let ret = self.ty()?.show();
+        Some(params.into_iter().fold("".to_string(), |acc, x| acc + &x + "->") + &ret)
+    }
+
+    fn ty(&mut self) -> Option<DemangledType> {
+        let ty = if self.eat("I32") {
+            "Int32".to_string()
+        } else if self.eat("B") {
+            "Bool".to_string()
+        } else if self.eat("N") || self.eat("S") {
+            self.ident()?
+        } else if self.eat("T") {
+            "(".to_string() + &self.types()?.join(",") + ")"
+        } else if self.eat("F") {
+            return Some(DemangledType::Func(self.func()?));
+        } else if self.eat("G") {
+            let n = self.number()?;
+            if !self.eat("_") {
+                return None;
+            }
+            tgen_name(n)
+        } else if self.eat("V") {
+            let n = self.number()?;
+            if !self.eat("_") {
+                return None;
+            }
+            n.to_string()
+        } else {
+            return None;
+        };
+        Some(DemangledType::Other(ty))
+    }
 }
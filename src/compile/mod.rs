@@ -1,5 +1,11 @@
+pub mod arity;
+pub mod c_header;
 pub mod code_gen;
+pub mod compile_option;
+pub mod debug_info;
+pub mod doc;
 pub mod error;
+pub mod formatter;
 pub mod output_file;
 pub mod parser;
 pub mod semantic_analysis;
@@ -7,41 +13,157 @@ pub mod types;
 pub mod ast_transformer;
 pub mod mangling;
 pub mod ir;
+pub mod jit;
+pub mod linker;
+pub mod optimize;
+pub mod target;
 
 use super::compile::semantic_analysis::type_inference::assump_env::AssumpEnv;
+use self::debug_info::DebugInfo;
+use self::doc::DocFormat;
 use self::error::Error;
+use self::compile_option::{CompileOption, EmitKind, OptLevel};
 use self::output_file::output_file;
 use self::ir::mir;
 use std::fs;
-use std::io::{BufReader, Read};
+use std::io::{self, BufReader, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-pub fn compile(input_file_name: &str, output_file_name: &str) -> Result<(), String> {
+//コンパイラ内部のデバッグ出力をするかどうか
+static VERBOSE: AtomicBool = AtomicBool::new(true);
+
+pub fn set_verbose(flag: bool) {
+    VERBOSE.store(flag, Ordering::Relaxed);
+}
+
+pub fn is_verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+//出力ファイル名を省略した時は入力ファイル名に拡張子を付けたものにする
+pub fn compile(input_file_name: &str, output_file_name: Option<&str>, option: &CompileOption) -> Result<(), String> {
     println!("input:{}", input_file_name);
-    compile_from_str(&src_file_to_str(input_file_name), output_file_name)
+    let output_file_name = match output_file_name {
+        Some(output_file_name) => output_file_name.to_string(),
+        None => input_file_name.to_string() + option.output_extension(),
+    };
+    let src = src_file_to_str(input_file_name).map_err(|err| format!("{}: {}", input_file_name, err))?;
+    compile_src(&src, input_file_name, &output_file_name, option)
+}
+
+pub fn compile_from_str(str: &str, output_file_name: &str, option: &CompileOption) -> Result<(), String> {
+    compile_src(str, "main.rsc", output_file_name, option)
 }
 
-pub fn compile_from_str(str: &str, output_file_name: &str) -> Result<(), String> {
-    match parse(str) {
-        Ok((program_ir, assump)) => Ok(output_file(program_ir.code_gen(output_file_name, assump))),
+//src_file_nameはデバッグ情報やパニックのメッセージに使うソースファイル名
+fn compile_src(str: &str, src_file_name: &str, output_file_name: &str, option: &CompileOption) -> Result<(), String> {
+    //実行ファイル以外はmain関数がなくてもよい
+    let result = if option.emit == EmitKind::Exe { parse(str) } else { parse_lib(str) };
+    let debug_info = if option.debug_info {
+        DebugInfo::new(src_file_name, option.opt_level != OptLevel::O0, option.pointer_bits())
+    } else {
+        DebugInfo::disabled(src_file_name)
+    };
+    match result {
+        Ok((program_ir, assump)) => output_file(program_ir.code_gen(output_file_name, assump, debug_info), option),
         Err(err) => Err(err),
     }
 }
 
-pub fn src_file_to_str(file_name: &str) -> String {
-    let mut f = BufReader::new(fs::File::open(file_name).unwrap());
+//コード生成を行わずに型検査だけを行う
+pub fn check(input_file_name: &str) -> Result<AssumpEnv, String> {
+    let src = src_file_to_str(input_file_name).map_err(|err| format!("{}: {}", input_file_name, err))?;
+    check_from_str(&src)
+}
+
+pub fn check_from_str(str: &str) -> Result<AssumpEnv, String> {
+    parse(str).map(|(_, assump)| assump)
+}
+
+//ドキュメントコメントと推論した型からドキュメントを生成する
+pub fn doc(input_file_name: &str, format: DocFormat) -> Result<String, String> {
+    let src = src_file_to_str(input_file_name).map_err(|err| format!("{}: {}", input_file_name, err))?;
+    doc_src(&src, input_file_name, format)
+}
+
+pub fn doc_from_str(str: &str, format: DocFormat) -> Result<String, String> {
+    doc_src(str, "main.rsc", format)
+}
+
+//main関数がなくてもよいようにライブラリとして解析する
+fn doc_src(str: &str, src_file_name: &str, format: DocFormat) -> Result<String, String> {
+    let src_str = parser::resolve_layout_pragma(str);
+    let ast = parser::parse(&src_str)
+        .map_err(|err| Error::from_parse_error(err).to_string())?
+        .0;
+    let (_, assump) = semantic_analysis::analysis_lib(ast.clone()).map_err(|err| err.to_string())?;
+    Ok(doc::doc(src_file_name, &ast, &assump, format))
+}
+
+//ファイルを整形して書き換える。checkの時は書き換えずに、整形で変わるかどうかだけを返す
+pub fn fmt(input_file_name: &str, check: bool) -> Result<bool, String> {
+    let src = fs::read_to_string(input_file_name).map_err(|err| format!("{}: {}", input_file_name, err))?;
+    if input_file_name.ends_with(parser::LAYOUT_EXTENSION) {
+        return Err(format!("{}: layout files can not be formatted", input_file_name));
+    }
+    let formatted = formatter::format_src(&src).map_err(|err| format!("{}: {}", input_file_name, err))?;
+    let changed = formatted != src;
+    if changed && !check {
+        fs::write(input_file_name, formatted).map_err(|err| format!("{}: {}", input_file_name, err))?;
+    }
+    Ok(changed)
+}
+
+//ファイルを出力せずにJITで実行し、mainの戻り値を返す
+pub fn run(input_file_name: &str) -> Result<i32, String> {
+    let src = src_file_to_str(input_file_name).map_err(|err| format!("{}: {}", input_file_name, err))?;
+    run_src(&src, input_file_name)
+}
+
+pub fn run_from_str(str: &str) -> Result<i32, String> {
+    run_src(str, "main.rsc")
+}
+
+fn run_src(str: &str, src_file_name: &str) -> Result<i32, String> {
+    let (program_ir, assump) = parse(str)?;
+    let code_gen_result = program_ir.code_gen("main", assump, DebugInfo::disabled(src_file_name));
+    jit::run_main(&code_gen_result.module)
+}
+
+//レイアウト規則の拡張子のファイルは;と{}を補って読み込む
+//プラグマで指定されたものはparseで補う
+pub fn src_file_to_str(file_name: &str) -> io::Result<String> {
+    let mut f = BufReader::new(fs::File::open(file_name)?);
     let mut src_str: String = "".to_string();
-    f.read_to_string(&mut src_str).unwrap();
-    src_str
+    f.read_to_string(&mut src_str)?;
+    if file_name.ends_with(parser::LAYOUT_EXTENSION) && !parser::has_layout_pragma(&src_str) {
+        Ok(parser::layout(&src_str))
+    } else {
+        Ok(src_str)
+    }
 }
 
 pub fn parse(src_str: &str) -> Result<(mir::ProgramMir, AssumpEnv), String> {
-    match parser::parse(src_str) {
+    parse_with(src_str, semantic_analysis::analysis)
+}
+
+//main関数のないライブラリとしてパースする
+pub fn parse_lib(src_str: &str) -> Result<(mir::ProgramMir, AssumpEnv), String> {
+    parse_with(src_str, semantic_analysis::analysis_lib)
+}
+
+fn parse_with<F>(src_str: &str, analysis: F) -> Result<(mir::ProgramMir, AssumpEnv), String>
+    where F: FnOnce(ir::ast::ProgramAST) -> Result<(mir::ProgramMir, AssumpEnv), Error>
+{
+    let src_str = parser::resolve_layout_pragma(src_str);
+    match parser::parse(&src_str) {
         Ok(ast) => {
-            println!("\nparse\n{:?}\n", ast);
-            let result = semantic_analysis::analysis(ast.0);
+            debug_println!("\nparse\n{:?}\n", ast);
+            let result = analysis(ast.0);
             match result {
                 Ok((ir, assump)) => {
-                    println!("resolve_op\n\n{:?}\n", ir);
+                    debug_println!("resolve_op\n\n{:?}\n", ir);
+                    ir.warning_list.iter().for_each(|x| eprintln!("{}", x.to_warning_string()));
                     Result::Ok((ir, assump))
                 }
                 Err(err) => Result::Err(err.to_string()),
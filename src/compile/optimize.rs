@@ -0,0 +1,96 @@
+extern crate inkwell;
+extern crate llvm_sys;
+
+use self::inkwell::values::{AsValueRef, CallSiteValue};
+use self::inkwell::{module, passes::PassManager, OptimizationLevel};
+use self::llvm_sys::prelude::LLVMValueRef;
+use super::compile_option::OptLevel;
+
+//src/llvm_shim/tail_call.cpp
+extern "C" {
+    fn ruscall_set_tail_call(call: LLVMValueRef);
+    fn ruscall_relax_must_tail_calls(function: LLVMValueRef);
+}
+
+impl OptLevel {
+    //ターゲットマシンのコード生成に使う最適化レベル
+    pub fn to_llvm_opt_level(&self) -> OptimizationLevel {
+        match self {
+            OptLevel::O0 => OptimizationLevel::None,
+            OptLevel::O1 => OptimizationLevel::Less,
+            OptLevel::O2 | OptLevel::Os => OptimizationLevel::Default,
+            OptLevel::O3 => OptimizationLevel::Aggressive,
+        }
+    }
+}
+
+//最適化レベルに応じたパスをモジュールにかける
+pub fn optimize(module: &module::Module, opt_level: OptLevel) {
+    if opt_level == OptLevel::O0 {
+        return;
+    }
+
+    //モジュール全体にかけるパス
+    let mpm = PassManager::create_for_module();
+    match opt_level {
+        OptLevel::O2 | OptLevel::O3 => mpm.add_function_inlining_pass(),
+        //サイズ優先の時は関数を膨らませない
+        _ => mpm.add_always_inliner_pass(),
+    }
+    mpm.add_global_dce_pass();
+    mpm.run_on_module(module);
+
+    //関数ごとにかけるパス
+    let fpm = PassManager::create_for_function(module);
+    //タプルや構造体のallocaをレジスタに昇格させる
+    fpm.add_scalar_repl_aggregates_pass();
+    fpm.add_promote_memory_to_register_pass();
+    fpm.add_instruction_combining_pass();
+    fpm.add_reassociate_pass();
+    fpm.add_cfg_simplification_pass();
+    if opt_level != OptLevel::O1 {
+        fpm.add_gvn_pass();
+        fpm.add_tail_call_elimination_pass();
+        fpm.add_instruction_combining_pass();
+        fpm.add_cfg_simplification_pass();
+    }
+    if opt_level == OptLevel::O3 {
+        fpm.add_aggressive_dce_pass();
+    }
+    fpm.initialize();
+    let mut function = module.get_first_function();
+    while let Some(func) = function {
+        fpm.run_on_function(&func);
+        function = func.get_next_function();
+    }
+    fpm.finalize();
+}
+
+//自己再帰の末尾呼び出しをループに変換する
+pub fn eliminate_tail_calls(module: &module::Module) {
+    let fpm = PassManager::create_for_function(module);
+    fpm.add_tail_call_elimination_pass();
+    fpm.initialize();
+    let mut function = module.get_first_function();
+    while let Some(func) = function {
+        fpm.run_on_function(&func);
+        function = func.get_next_function();
+    }
+    fpm.finalize();
+}
+
+//末尾位置の呼び出しに印を付ける。呼び出し元と型が同じ関数への呼び出しはmusttailになる
+//関数の値を返す呼び出ししかないので、戻り値の値が呼び出し命令になっている
+pub fn set_tail_call(callsite: &CallSiteValue) {
+    let call = callsite.try_as_basic_value().left().unwrap();
+    unsafe { ruscall_set_tail_call(call.as_value_ref()) };
+}
+
+//wasm32はmusttailに対応していないのでtailに戻す
+pub fn relax_must_tail_calls(module: &module::Module) {
+    let mut function = module.get_first_function();
+    while let Some(func) = function {
+        unsafe { ruscall_relax_must_tail_calls(func.as_value_ref()) };
+        function = func.get_next_function();
+    }
+}
@@ -1,81 +1,126 @@
 extern crate inkwell;
 
 use self::inkwell::*;
+use super::c_header::c_header;
 use super::code_gen::CodeGenResult;
-use std::fs::File;
-use std::path::Path;
+use super::compile_option::{CompileOption, EmitKind};
+use super::ir::mir::ExportFuncMir;
+use super::linker::{archive, link};
+use super::optimize::{optimize, relax_must_tail_calls};
+use super::target::{create_target_machine, set_module_target};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 
-//コードを実行形式で出力
-pub fn output_file(code_gen_result: CodeGenResult) {
-    let CodeGenResult { module, file_name, .. } = code_gen_result;
+//コードを指定された形式で出力。出力先はCodeGenResultのfile_nameそのもの
+pub fn output_file(code_gen_result: CodeGenResult, option: &CompileOption) -> Result<(), String> {
+    let CodeGenResult { module, file_name, export_func_list, .. } = code_gen_result;
+    let output_path = Path::new(file_name);
+    let export_names = export_func_list.iter().map(|x| x.name.clone()).collect::<Vec<_>>();
 
-    use std::env;
-    let triple = &targets::TargetMachine::get_default_triple().to_string();
-    let target_machine =
-        targets::Target::from_triple(triple).unwrap().create_target_machine(
-            triple,
-            "generic",
-            "",
-            OptimizationLevel::Default,
-            targets::RelocMode::PIC,
-            targets::CodeModel::Default,
-        ).unwrap();
-    module.write_bitcode_to_file(&File::create(file_name.to_string() + ".bc").unwrap(), true, true);
-    target_machine.write_to_file(
-        &module,
-        targets::FileType::Object,
-        &Path::new(&(file_name.to_string() + ".obj")),
-    ).unwrap();
+    let target_machine = create_target_machine(option)?;
+    set_module_target(&module, &target_machine);
+    if option.is_wasm() {
+        relax_must_tail_calls(&module);
+    }
+    optimize(&module, option.opt_level);
+    let write_object = |path: &Path, file_type: targets::FileType| {
+        target_machine.write_to_file(&module, file_type, path).map_err(|err| err.to_string())
+    };
 
-    let current_dir = env::current_dir().unwrap().to_str().unwrap().to_string();
+    match option.emit {
+        EmitKind::LlvmIr => module.print_to_file(output_path).map_err(|err| err.to_string()),
+        EmitKind::LlvmBc => write_bitcode(&module, output_path),
+        EmitKind::Asm => write_object(output_path, targets::FileType::Assembly),
+        EmitKind::Obj => {
+            write_object(output_path, targets::FileType::Object)?;
+            write_header(output_path, &export_func_list)
+        }
+        EmitKind::StaticLib | EmitKind::Cdylib | EmitKind::Exe => {
+            let temps = Temps::new(output_path, option.save_temps)?;
+            if option.save_temps {
+                write_bitcode(&module, &temps.path(".bc"))?;
+            }
+            let obj_path = temps.path(".obj");
+            write_object(&obj_path, targets::FileType::Object)?;
+            match option.emit {
+                EmitKind::StaticLib => archive(&obj_path, output_path, option)?,
+                _ => link(&obj_path, output_path, option, &export_names)?,
+            }
+            if option.emit == EmitKind::Exe {
+                Ok(())
+            } else {
+                write_header(output_path, &export_func_list)
+            }
+        }
+    }
+}
 
-    if cfg!(target_os = "windows") {
-        let compile_bat_dir = env::var("CARGO_MANIFEST_DIR").unwrap_or(".".to_string());
-        command_exec(
-            "cmd",
-            &[
-                "/C",
-                &(compile_bat_dir + "\\compile.bat"),
-                &(current_dir + "\\" + file_name + ".obj"),
-            ],
-        );
+//ライブラリを出力した時は、公開した関数のCヘッダーを拡張子.hで隣に置く
+fn write_header(output_path: &Path, export_func_list: &[ExportFuncMir]) -> Result<(), String> {
+    if export_func_list.len() == 0 {
+        return Ok(());
+    }
+    let header_path = output_path.with_extension("h");
+    let guard_name = header_path.file_stem().map(|x| x.to_string_lossy().to_string()).unwrap_or("ruscall".to_string());
+    fs::write(&header_path, c_header(&guard_name, export_func_list))
+        .map_err(|err| format!("failed to write {}: {}", header_path.display(), err))
+}
+
+fn write_bitcode(module: &module::Module, path: &Path) -> Result<(), String> {
+    if module.write_bitcode_to_path(path) {
+        Ok(())
     } else {
-        command_exec(
-            "sh",
-            &[
-                "-c",
-                &("g++ ".to_owned()
-                    + &(current_dir.clone() + "/" + file_name + ".obj ")
-                    + &(current_dir.clone() + "/" + "libtest.a ")
-                    + "-o "
-                    + &(current_dir + "/" + file_name + ".out")),
-            ],
-        );
-    };
+        Err(format!("failed to write bitcode: {}", path.display()))
+    }
 }
 
-use std::ffi::OsStr;
+//一時ディレクトリの名前に付ける連番
+static TEMPS_COUNT: AtomicUsize = AtomicUsize::new(0);
 
-fn command_exec<I, S>(terminal: &str, args: I)
-    where
-        I: IntoIterator<Item=S> + Clone,
-        S: AsRef<OsStr>,
-{
-    use std::process::Command;
+//中間ファイルの置き場所
+//--save-tempsなら出力先の隣に置き、そうでなければ一時ディレクトリに置いて最後に消す
+struct Temps {
+    dir: Option<PathBuf>,
+    base: PathBuf,
+}
+
+impl Temps {
+    fn new(output_path: &Path, save_temps: bool) -> Result<Temps, String> {
+        if save_temps {
+            return Ok(Temps { dir: None, base: output_path.to_path_buf() });
+        }
+        let file_name = output_path.file_name().map(|x| x.to_os_string()).unwrap_or(OsString::from("out"));
+        //同じプロセスで同じ名前を何度コンパイルしてもぶつからないように連番を付け、
+        //前のプロセスの残りなどで既にあれば次の番号を試す
+        loop {
+            let mut dir_name = OsString::from(format!("ruscall-{}-{}-", process::id(), TEMPS_COUNT.fetch_add(1, Ordering::SeqCst)));
+            dir_name.push(&file_name);
+            let dir = env::temp_dir().join(dir_name);
+            match fs::create_dir(&dir) {
+                Ok(()) => return Ok(Temps { base: dir.join(file_name), dir: Some(dir) }),
+                Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(format!("failed to create temporary directory: {}", err)),
+            }
+        }
+    }
 
-    let output = Command::new(terminal)
-        .args(args.clone())
-        .output()
-        .expect("failed to execute process");
-    println!("status: {}", output.status);
-    println!("stdout: {}", String::from_utf8_lossy(&output.stdout));
-    println!("stderr: {}", String::from_utf8_lossy(&output.stderr));
-    println!(
-        "command: {}",
-        args.into_iter()
-            .fold("".to_string(), |acc, x| acc.to_owned()
-                + " "
-                + x.as_ref().to_str().unwrap())
-    );
-}
\ No newline at end of file
+    fn path(&self, extension: &str) -> PathBuf {
+        let mut path = self.base.clone().into_os_string();
+        path.push(extension);
+        PathBuf::from(path)
+    }
+}
+
+impl Drop for Temps {
+    fn drop(&mut self) {
+        if let Some(ref dir) = self.dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
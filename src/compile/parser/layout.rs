@@ -0,0 +1,261 @@
+//インデントから;と{}を補うレイアウト規則
+//
+//f x = g x + h 2
+//  where
+//    g y = if y == 0
+//            1
+//          else
+//            x * y
+//    h y = g y + 1
+//
+//は行末に;と{}を足して
+//
+//f x = g x + h 2
+//  where {
+//    g y = if y == 0 {
+//            1 }
+//          else {
+//            x * y };
+//    h y = g y + 1; };
+//
+//になる。文字は行末か、whereの後の空白を置き換えて足すので、元のトークンの位置は変わらない
+//明示的な括弧の中では行を続きとして扱う
+
+//レイアウト規則を使うファイルの拡張子
+pub const LAYOUT_EXTENSION: &str = ".rscl";
+//ファイルの先頭にこの行があればレイアウト規則を使う
+pub const LAYOUT_PRAGMA: &str = "//#layout";
+
+pub fn has_layout_pragma(src: &str) -> bool {
+    src.lines().next().map(|x| x.trim() == LAYOUT_PRAGMA).unwrap_or(false)
+}
+
+struct Token {
+    text: String,
+    end: usize,
+    column: usize,
+    //トークンの前の括弧の深さ
+    depth: usize,
+}
+
+//トークンのある行
+struct Line {
+    tokens: Vec<Token>,
+    //行の最初の括弧の深さ
+    depth: usize,
+    //行末の括弧の深さ
+    end_depth: usize,
+}
+
+impl Line {
+    fn indent(&self) -> usize {
+        self.tokens[0].column
+    }
+    fn end(&self) -> usize {
+        self.tokens[self.tokens.len() - 1].end
+    }
+    fn last(&self) -> &str {
+        &self.tokens[self.tokens.len() - 1].text
+    }
+
+    //行末から始まるブロックの種類
+    fn opened_block(&self) -> Option<BlockKind> {
+        match self.last() {
+            "where" => return Some(BlockKind::Items),
+            "else" => return Some(BlockKind::Expr),
+            _ => ()
+        }
+        //if 条件 で終わる行
+        let if_idx = self.tokens.iter().rposition(|x| x.text == "if")?;
+        let if_token = &self.tokens[if_idx];
+        let has_brace = self.tokens[if_idx..].iter().any(|x| x.text == "{");
+        if !has_brace && if_token.depth == self.end_depth && if_idx + 1 < self.tokens.len() {
+            Some(BlockKind::Expr)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum BlockKind {
+    //;で区切られた定義の並び
+    Items,
+    //ifやelseの枝の式
+    Expr,
+}
+
+struct Block {
+    kind: BlockKind,
+    column: usize,
+    depth: usize,
+    braces: bool,
+    //ブロックの最初の行をまだ見ていない
+    first: bool,
+}
+
+//ソースを行ごとのトークンに分ける
+fn scan(src: &str) -> Vec<Line> {
+    let mut lines = vec![];
+    let mut tokens = vec![];
+    let mut depth = 0;
+    let mut line_depth = 0;
+    let mut comment_depth = 0;
+    let mut column = 0;
+    let mut chars = src.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        let next = chars.peek().map(|(_, c)| *c);
+        if c == '\n' {
+            if !tokens.is_empty() {
+                lines.push(Line { tokens, depth: line_depth, end_depth: depth });
+                tokens = vec![];
+            }
+            line_depth = depth;
+            column = 0;
+            continue;
+        }
+        column += 1;
+        if comment_depth > 0 {
+            if c == '*' && next == Some('/') {
+                chars.next();
+                column += 1;
+                comment_depth -= 1;
+            } else if c == '/' && next == Some('*') {
+                chars.next();
+                column += 1;
+                comment_depth += 1;
+            }
+            continue;
+        }
+        match c {
+            '/' if next == Some('/') => {
+                while chars.peek().map(|(_, c)| *c != '\n').unwrap_or(false) {
+                    chars.next();
+                }
+            }
+            '/' if next == Some('*') => {
+                chars.next();
+                column += 1;
+                comment_depth += 1;
+            }
+            ' ' | '\t' | '\r' => (),
+            c if c.is_alphanumeric() || c == '_' => {
+                let start_column = column - 1;
+                let mut end = idx + c.len_utf8();
+                let mut text = c.to_string();
+                while let Some(&(idx, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') { break; }
+                    chars.next();
+                    column += 1;
+                    end = idx + c.len_utf8();
+                    text.push(c);
+                }
+                tokens.push(Token { text, end, column: start_column, depth });
+            }
+            c => {
+                let token_depth = depth;
+                match c {
+                    '(' | '[' | '{' => depth += 1,
+                    ')' | ']' | '}' => depth = depth.saturating_sub(1),
+                    _ => ()
+                }
+                tokens.push(Token { text: c.to_string(), end: idx + c.len_utf8(), column: column - 1, depth: token_depth });
+            }
+        }
+    }
+    if !tokens.is_empty() {
+        lines.push(Line { tokens, depth: line_depth, end_depth: depth });
+    }
+    lines
+}
+
+//レイアウト規則で;と{}を補ったソースを返す
+pub fn layout(src: &str) -> String {
+    let lines = scan(src);
+    //(位置, 足す文字列) 空白を置き換える時はtrue
+    let mut edits: Vec<(usize, String, bool)> = vec![];
+    let mut blocks = vec![Block { kind: BlockKind::Items, column: 0, depth: 0, braces: false, first: true }];
+    let mut pending: Option<BlockKind> = None;
+    //直前の行が;で終わっているか
+    let mut terminated = false;
+    for (idx, line) in lines.iter().enumerate() {
+        //前の行の行末
+        let prev_end = if idx == 0 { 0 } else { lines[idx - 1].end() };
+        if let Some(kind) = pending.take() {
+            let prev = &lines[idx - 1];
+            if line.indent() > prev.indent() {
+                edits.push((prev_end, " {".to_string(), false));
+                blocks.push(Block { kind, column: line.indent(), depth: line.depth, braces: true, first: true });
+                terminated = false;
+            }
+        }
+        loop {
+            let block = blocks.last_mut().unwrap();
+            //明示的な括弧の中は前の行の続き
+            if line.depth != block.depth {
+                break;
+            }
+            //一番外側のブロックは0列目なので閉じない
+            if line.indent() < block.column {
+                if block.kind == BlockKind::Items && !terminated {
+                    edits.push((prev_end, ";".to_string(), false));
+                }
+                edits.push((prev_end, " }".to_string(), false));
+                terminated = false;
+                blocks.pop();
+                continue;
+            }
+            if line.indent() == block.column && block.kind == BlockKind::Items {
+                if !block.first && !terminated {
+                    edits.push((prev_end, ";".to_string(), false));
+                }
+                block.first = false;
+            }
+            break;
+        }
+        terminated = line.last() == ";";
+        pending = line.opened_block();
+        //where g y = ...のように同じ行から始まる定義
+        if let Some(where_idx) = line.tokens.iter().position(|x| x.text == "where") {
+            if where_idx + 1 < line.tokens.len() {
+                let where_token = &line.tokens[where_idx];
+                let next = &line.tokens[where_idx + 1];
+                let has_space = src[where_token.end..].starts_with(|c: char| c == ' ' || c == '\t');
+                if next.text != "{" && has_space {
+                    edits.push((where_token.end, "{".to_string(), true));
+                    blocks.push(Block { kind: BlockKind::Items, column: next.column, depth: next.depth, braces: true, first: false });
+                }
+            }
+        }
+    }
+    if let Some(prev) = lines.last() {
+        while let Some(block) = blocks.pop() {
+            if block.kind == BlockKind::Items && !terminated {
+                edits.push((prev.end(), ";".to_string(), false));
+            }
+            if block.braces {
+                edits.push((prev.end(), " }".to_string(), false));
+            }
+            terminated = false;
+        }
+    }
+    apply_edits(src, edits)
+}
+
+fn apply_edits(src: &str, mut edits: Vec<(usize, String, bool)>) -> String {
+    //同じ位置の編集は足した順に並べる
+    edits.sort_by_key(|(idx, _, _)| *idx);
+    let mut result = String::with_capacity(src.len() + edits.len() * 2);
+    let mut last = 0;
+    for (idx, s, replace) in edits {
+        result.push_str(&src[last..idx]);
+        result.push_str(&s);
+        last = idx;
+        if replace {
+            //置き換える空白は1文字
+            last += src[idx..].chars().next().map(|c| c.len_utf8()).unwrap_or(0);
+        }
+    }
+    result.push_str(&src[last..]);
+    result
+}
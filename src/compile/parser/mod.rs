@@ -1,12 +1,15 @@
 mod parser;
 mod types;
 mod skipper;
+mod layout;
 
 use super::ir::ast;
 use combine::easy;
-use combine::Parser;
+use combine::{eof, Parser};
 use combine::stream::state::{SourcePosition, State};
 
+pub use self::layout::{has_layout_pragma, layout, LAYOUT_EXTENSION};
+
 
 pub fn parse(
     s: &str,
@@ -18,4 +21,42 @@ pub fn parse(
     easy::Errors<char, &str, SourcePosition>,
 > {
     parser::program_parser().easy_parse(State::new(s))
-}
\ No newline at end of file
+}
+//文ごとのソース上の範囲も返す
+pub fn parse_spanned(
+    s: &str,
+) -> Result<
+    (
+        Vec<(SourcePosition, ast::StmtAST, SourcePosition)>,
+        State<&str, SourcePosition>,
+    ),
+    easy::Errors<char, &str, SourcePosition>,
+> {
+    parser::spanned_program_parser().easy_parse(State::new(s))
+}
+
+//式だけを構文解析する
+pub fn parse_expr(
+    s: &str,
+) -> Result<
+    (
+        ast::ExprAST,
+        State<&str, SourcePosition>,
+    ),
+    easy::Errors<char, &str, SourcePosition>,
+> {
+    skipper::skip_many_parser()
+        .with(parser::expr_parser())
+        .skip(skipper::skip_many_parser())
+        .skip(eof())
+        .easy_parse(State::new(s))
+}
+
+//先頭にプラグマがあればレイアウト規則で;と{}を補う
+pub fn resolve_layout_pragma(s: &str) -> String {
+    if has_layout_pragma(s) {
+        layout(s)
+    } else {
+        s.to_string()
+    }
+}
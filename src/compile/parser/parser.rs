@@ -4,17 +4,19 @@ use super::types::{struct_record_parser, ty_tuple_parser};
 use combine::char::{alpha_num, char, digit, lower, string, upper};
 use combine::parser::combinator::try;
 use combine::stream::state::{DefaultPositioned, SourcePosition, State};
-use combine::{easy, optional, sep_by, sep_end_by};
-use combine::{eof, many, many1, position, unexpected, value};
+use combine::{easy, optional, sep_by, sep_end_by, sep_end_by1};
+use combine::{eof, many, many1, not_followed_by, position, unexpected, value};
 
 /*
 BNF
 :program       := {:stmt} :skip_many
-:stmt          := :skip_many (
+:stmt          := :doc_skip_many :decl
+:decl          := (
                         :infix |
                         :def_func |
                         :dec_func |
                         :exturn_dec_func |
+                        :export_dec_func |
                         :struct
                    ) :skip_many ';'
 :struct        := 'struct' :skip_many :upper_id :skip_many :ty_tuple | :struct_record
@@ -22,20 +24,30 @@ BNF
 :struct_record_part
                := :id :skip_many ':' :skip_many :ty_term_with_func
 :upper_id      := [A-Z]{ [a-z] | [0-9] | '_' }
-:def_func      := :id {:skip_many :id} :skip_many '=' :skip_many :expr
-:id            := [a-z]{ [a-z] | [0-9] | '_' }
+:def_func      := :id {:skip_many :pattern} :skip_many ( '=' :skip_many :expr | :guard { :guard } ) [ :where ]
+:where         := 'where' :skip_many '{' :skip_many { :def_func :skip_many ';' :skip_many } '}' :skip_many
+:guard         := '|' :skip_many ( 'otherwise' | :expr ) :skip_many '=' :skip_many :expr
+:pattern       := :num | :bool | :id | :tuple_pattern | :record_pattern
+:tuple_pattern := '(' :skip_many [ :pattern { :skip_many ',' :skip_many :pattern } [:skip_many ','] ] :skip_many ')'
+:record_pattern
+               := :upper_id :skip_many '{' :skip_many :field_pattern { :skip_many ',' :skip_many :field_pattern } [:skip_many ','] :skip_many '}'
+:field_pattern := :id [ :skip_many '=' :skip_many :pattern ]
+:id            := [a-z]{ [a-z] | [0-9] | '_' } (ただし'where'を除く)
 :expr          := :expr_app :skip_many { :op :skip_many :expr_app :skip_many }
 :expr_app      := :term { :skip_many :term }
 :named_params_constructor_call
                := :upper_id  :skip_many :named_params
 :named_params  := '{' :skip_many :named_param { :skip_many ',' :skip_many :named_param } [:skip_many,','] :skip_many  '}'
-:named_param   := :id :skip_many '=' :skip_many :expr
+:named_param   := :id [ :skip_many '=' :skip_many :expr ]
+:record_update := '{' :skip_many :update_param { :skip_many ',' :skip_many :update_param } [:skip_many,','] :skip_many  '}'
+:update_param  := :id :skip_many '=' :skip_many :expr
 :infix         := ('infixr' | 'infixl') :skip_many1 :num :skip_many1 :op
 :op            := '+' | '-' | '/' | '*' | '=='
 :term          :=
                     (
                         :num |
                         :bool |
+                        :panic |
                         :if |
                         :named_params_constructor_call |
                         :id  |
@@ -43,28 +55,37 @@ BNF
                         :paren |
                         :tuple |
                         :lambda
-                    ){:skip_many '.' :skip_many (:id | :num) }
+                    ){:skip_many ( '.' :skip_many (:id | :num) | :record_update ) }
 :paren         := '(' :skip_many :expr ')'
 :num           := [0-9]+
 :bool          := 'true' | 'false'
+:panic         := 'panic'
 :if            := 'if' :skip_many :expr '{' :skip_many :expr '}' :skip_many 'else' :skip_many '{' :skip_many :expr '}'
 :tuple         := '(' :skip_many [ :expr {',' :skip_many :expr} [',' :skip_many]] ')'
-:lambda        := '\' :skip_many [ '[' :lambda_params ']' ] :lambda_params '->' :skip_many :expr
+:lambda        := '\' :skip_many [ '[' :lambda_params ']' ] :lambda_pattern_params '->' :skip_many :expr
 :lambda_params := :skip_many [ :id { :skip_many ',' :skip_many :id } :skip_many ]
+:lambda_pattern_params
+               := :skip_many [ :pattern { :skip_many ',' :skip_many :pattern } :skip_many ]
 :skip          := '\n' | :space | ' ' | '\t'
 :comment       := '//' [^ \n ]*
+:doc_comment   := '///' [ [^ / \n ] [^ \n ]* ]
+:doc_skip_many := { :doc_comment | :skip }
 :comments      := '/*' {:comments |[^(/*)(*/)]} '*/'
 :skip_many     := {:skip}
 :skip_many1    := :skip +
-:ty_term       := 'Int32'| :id | :ty_paren | :ty_tuple | :upper_id
+:ty_term       := 'Int32'| :id | :ty_paren | :ty_tuple | :ty_row | :upper_id
 :ty_term_with_func
                := :ty_term | :ty_func
 :ty_paren      := '(' :skip_many :ty_term_with_func :skip_many ')'
 :ty_tuple      := '(' :skip_many [  :ty_term_with_func :skip_many {',' :skip_many :ty_term :skip_many} [',' :skip_many]] ')'
+:ty_row        := '{' :skip_many [ :ty_row_part { :skip_many ',' :skip_many :ty_row_part } [:skip_many ','] ] :skip_many [ '|' :skip_many :id :skip_many ] '}'
+:ty_row_part   := ( :id | :num ) :skip_many ':' :skip_many :ty_term_with_func
 :ty_func       := :ty_term :skip_many '->' :skip_many  ( :ty_func | :ty_term )
 :dec_func      := :id :skip_many '::' :skip_many :ty_func
 :exturn_dec_func
                 := 'ex' :skip_many :dec_func
+:export_dec_func
+                := 'export' :skip_many :dec_func
 */
 
 pub type MyStream<'a> = easy::Stream<State<&'a str, <&'a str as DefaultPositioned>::Positioner>>;
@@ -77,26 +98,46 @@ parser! {
     }
 }
 
+//文ごとにソース上の始まりと終わりの位置を付けたprogram
+//フォーマッタがコメントを文の前後に置き直すのに使う
+parser! {
+   pub fn spanned_program_parser['a]()(MyStream<'a>) ->Vec<(SourcePosition,ast::StmtAST,SourcePosition)>
+    {
+        many(try((skip_many_parser().with(position()),decl_parser(),position())))
+        .skip(skip_many_parser())
+        .skip(eof().expected("statement or infix"))
+    }
+}
+
 //<stmt>
 parser! {
    fn stmt_parser['a]()(MyStream<'a>) ->ast::StmtAST
     {
         try(
-            skip_many_parser().
-            with (
-                try(infix_parser().map(ast::StmtAST::InfixAST))
-                .or(try(def_func_parser().map(ast::StmtAST::DefFuncAST)))
-                .or(try(dec_func_parser().map(ast::StmtAST::DecFuncAST)))
-                .or(extern_dec_func_parser().map(ast::StmtAST::DecFuncAST))
-                .or(struct_parser().map(ast::StmtAST::DecStructAST))
-            ).
-            skip(
-                skip_many_parser()
-            ).skip(char(';'))
+            (doc_skip_many_parser(),decl_parser())
+            .map(|(doc,stmt):(_,ast::StmtAST)|stmt.with_doc(doc))
         )
     }
 }
 
+//<decl>
+parser! {
+   fn decl_parser['a]()(MyStream<'a>) ->ast::StmtAST
+    {
+        (
+            try(infix_parser().map(ast::StmtAST::InfixAST))
+            .or(try(def_func_parser().map(ast::StmtAST::DefFuncAST)))
+            .or(try(dec_func_parser().map(ast::StmtAST::DecFuncAST)))
+            .or(try(export_dec_func_parser().map(ast::StmtAST::DecFuncAST)))
+            .or(extern_dec_func_parser().map(ast::StmtAST::DecFuncAST))
+            .or(struct_parser().map(ast::StmtAST::DecStructAST))
+        ).
+        skip(
+            skip_many_parser()
+        ).skip(char(';'))
+    }
+}
+
 //<struct>
 parser! {
     fn struct_parser['a]()(MyStream<'a>)->ast::DecStructAST{
@@ -115,6 +156,7 @@ parser! {
             ast::DecStructAST{
                 ty: ast::StructTypeAST{ty,name},
                 pos,
+                doc: None,
             }
         )
 
@@ -135,9 +177,165 @@ parser! {
         (
             position(),
             id_parser().skip(skip_many_parser()),
-            many((position(),id_parser()).skip(skip_many_parser()).map(|(pos,id)|ast::VariableAST::new(id,pos))),
+            many(pattern_parser().skip(skip_many_parser())),
+            char('=').with(skip_many_parser()).with(expr_parser()).map(Ok)
+            .or(many1::<Vec<_>,_>(guard_parser()).map(Err)),
+            optional(try(where_parser()))
+        ).map(|(pos,name,params,body,where_list):(_,_,Vec<_>,_,_)|{
+            match (body,where_list) {
+                (Ok(body),None) => {
+                    let (params,patterns)=ast::PatternAST::split_params(params);
+                    ast::DefFuncAST{name,params,patterns,body,pos,doc:None}
+                }
+                (body,where_list) => {
+                    let guards=match body {
+                        Ok(body)=>vec![(ast::ExprAST::create_bool_ast(true,pos),body)],
+                        Err(guards)=>guards,
+                    };
+                    let params_len=params.len();
+                    let where_list=where_list.unwrap_or(vec![]);
+                    let clause=ast::ClauseAST{patterns:params,guards,where_list,pos};
+                    ast::DefFuncAST::from_clauses(name,params_len,vec![clause],pos)
+                }
+            }
+        })
+    }
+}
+
+//<guard>
+parser! {
+    fn guard_parser['a]()(MyStream<'a>) ->(ast::ExprAST,ast::ExprAST)
+    {
+        (
+            char('|')
+            .with(skip_many_parser())
+            .with(
+                try(
+                    (position(),string("otherwise").skip(not_followed_by(alpha_num().or(char('_')))))
+                    .map(|(pos,_)|ast::ExprAST::create_bool_ast(true,pos))
+                    .skip(skip_many_parser())
+                )
+                .or(expr_parser())
+            ),
             char('=').with(skip_many_parser()).with(expr_parser())
-        ).map(|(pos,name,params,body)|{ast::DefFuncAST{name,params,body,pos}})
+        )
+    }
+}
+
+//<where>
+parser! {
+    fn where_parser['a]()(MyStream<'a>) ->Vec<ast::DefFuncAST>
+    {
+        string("where")
+        .skip(not_followed_by(alpha_num().or(char('_'))))
+        .with(skip_many_parser())
+        .with(char('{'))
+        .with(skip_many_parser())
+        .with(many(
+            def_func_parser()
+            .skip(skip_many_parser())
+            .skip(char(';'))
+            .skip(skip_many_parser())
+        ))
+        .skip(char('}'))
+        .skip(skip_many_parser())
+    }
+}
+
+//<pattern>
+parser! {
+    fn pattern_parser['a]()(MyStream<'a>) ->ast::PatternAST
+    {
+        (position(),num_parser()).map(|(pos,num)|ast::PatternAST::NumAST(ast::NumAST::new(num,pos)))
+        .or(try(
+            (
+                position(),
+                string("true").with(value(true))
+                .or(string("false").with(value(false)))
+                .skip(not_followed_by(alpha_num().or(char('_'))))
+            ).map(|(pos,bool)|ast::PatternAST::BoolAST(ast::BoolAST{bool,pos}))
+        ))
+        .or((position(),id_parser()).map(|(pos,id)|ast::PatternAST::VariableAST(ast::VariableAST::new(id,pos))))
+        .or(tuple_pattern_parser())
+        .or(record_pattern_parser())
+    }
+}
+
+//<tuple_pattern>
+parser! {
+    fn tuple_pattern_parser['a]()(MyStream<'a>) ->ast::PatternAST
+    {
+        (
+            position(),
+            char('(')
+            .with(skip_many_parser())
+            .with(
+                sep_end_by(
+                    pattern_parser(),
+                    try(
+                        skip_many_parser()
+                        .with(char(','))
+                        .skip(skip_many_parser())
+                    )
+                )
+            )
+            .skip(skip_many_parser())
+            .skip(char(')'))
+        ).map(|(pos,mut elements):(_,Vec<_>)|{
+            //(a)は括弧で囲っただけのパターン
+            if elements.len() == 1 {
+                elements.remove(0)
+            } else {
+                ast::PatternAST::TupleAST(Box::new(ast::TuplePatternAST{elements,pos}))
+            }
+        })
+    }
+}
+
+//<record_pattern>
+parser! {
+    fn record_pattern_parser['a]()(MyStream<'a>) ->ast::PatternAST
+    {
+        (
+            position(),
+            upper_id_parser().skip(skip_many_parser()),
+            char('{')
+            .with(skip_many_parser())
+            .with(
+                sep_end_by(
+                    field_pattern_parser(),
+                    try(
+                        skip_many_parser()
+                        .with(char(','))
+                        .skip(skip_many_parser())
+                    )
+                )
+            )
+            .skip(skip_many_parser())
+            .skip(char('}'))
+        ).map(|(pos,constructor_name,fields)|
+            ast::PatternAST::RecordAST(Box::new(ast::RecordPatternAST{constructor_name,fields,pos}))
+        )
+    }
+}
+
+//<field_pattern>
+parser! {
+    fn field_pattern_parser['a]()(MyStream<'a>) ->(String,ast::PatternAST)
+    {
+        (
+            position(),
+            id_parser(),
+            optional(try(
+                skip_many_parser()
+                .with(char('='))
+                .with(skip_many_parser())
+                .with(pattern_parser())
+            ))
+        ).map(|(pos,id,pattern)|{
+            let pattern = pattern.unwrap_or_else(||ast::PatternAST::VariableAST(ast::VariableAST::new(id.clone(),pos)));
+            (id,pattern)
+        })
     }
 }
 
@@ -149,6 +347,20 @@ parser! {
     }
 }
 
+//whereは変数名にならない
+parser! {
+   fn keyword_excluded_id_parser['a]()(MyStream<'a>) ->String
+    {
+        id_parser().then(|id|
+            if id=="where" {
+                unexpected("where").map(|_|String::new()).right()
+            } else {
+                value(id).left()
+            }
+        )
+    }
+}
+
 //<expr_app>
 parser! {
     fn expr_app_parser['a]()(MyStream<'a>)->ast::ExprAST{
@@ -201,19 +413,55 @@ parser! {
 //<named_param>
 parser! {
     fn named_param_parser['a]()(MyStream<'a>)->(String,ast::ExprAST){
+        try(update_param_parser())
+        .or(
+            (position(),id_parser())
+            .map(|(pos,id)|(id.clone(),ast::ExprAST::VariableAST(ast::VariableAST::new(id,pos))))
+        )
+    }
+}
+
+//<record_update>
+parser! {
+    fn record_update_parser['a]()(MyStream<'a>)->Vec<(String,ast::ExprAST)>{
+        char('{')
+        .with(skip_many_parser())
+        .with(
+            sep_end_by1(
+                update_param_parser(),
+                skip_many_parser()
+                    .with(char(','))
+                    .skip(skip_many_parser())
+            )
+                .skip(skip_many_parser())
+        )
+        .skip(char('}'))
+    }
+}
+
+//<update_param>
+parser! {
+    fn update_param_parser['a]()(MyStream<'a>)->(String,ast::ExprAST){
         (
             id_parser()
             .skip(skip_many_parser())
             .skip(char('='))
+            .skip(not_followed_by(char('=')))
             .skip(skip_many_parser()),
             expr_parser()
         )
     }
 }
 
+//termの後置
+enum TermSuffix {
+    Property(String),
+    RecordUpdate(Vec<(String, ast::ExprAST)>),
+}
+
 //<expr>
 parser! {
-    fn expr_parser['a]()(MyStream<'a>)->ast::ExprAST
+    pub fn expr_parser['a]()(MyStream<'a>)->ast::ExprAST
     {
         (
            expr_app_parser().skip(skip_many_parser()),
@@ -260,7 +508,8 @@ parser! {
             ast::InfixAST{
                 ty: ty,
                 priority:ast::Priority(priority),
-                op:op
+                op:op,
+                doc: None,
             }
         })
     }
@@ -274,7 +523,7 @@ parser! {
         or(string("-")).
         or(string("/")).
         or(string("*")).
-        or(string("=="))
+        or(try(string("==")))
         .map(|s|s.to_string())
     }
 }
@@ -291,23 +540,30 @@ parser! {
                 .map(|(pos,num)|ast::ExprAST::create_num_ast(num,pos))
             )
             .or(try(bool_parser()))
+            .or(try(panic_parser()))
             .or(try(if_parser()))
             .or(try(named_params_constructor_call_parser()))
             .or(
-                (position(),id_parser().or(upper_id_parser()))
+                (position(),keyword_excluded_id_parser().or(upper_id_parser()))
                 .skip(skip_many_parser())
                 .map(|(pos,id)|ast::ExprAST::VariableAST(ast::VariableAST::new(id,pos)))
             )
             .or(lambda_parser()),
             many(try(
                 skip_many_parser()
-                .with(char('.'))
-                .with(skip_many_parser())
-                .with(( position(), num_parser().or(id_parser()) ))
+                .with(
+                    char('.')
+                    .with(skip_many_parser())
+                    .with(( position(), num_parser().or(id_parser()).map(TermSuffix::Property) ))
+                    .or(( position(), record_update_parser().map(TermSuffix::RecordUpdate) ))
+                )
             ))
-        ).map(|(expr,prop ):(_,Vec<_>)|{
-            prop.into_iter().fold(expr,|acc,(pos,name) |
-                ast::ExprAST::create_property_ast(name ,acc, pos)
+        ).map(|(expr,suffix ):(_,Vec<_>)|{
+            suffix.into_iter().fold(expr,|acc,(pos,suffix) |
+                match suffix {
+                    TermSuffix::Property(name) => ast::ExprAST::create_property_ast(name ,acc, pos),
+                    TermSuffix::RecordUpdate(params) => ast::ExprAST::create_record_update_ast(acc, params, pos),
+                }
             )
         })
     }
@@ -346,6 +602,19 @@ parser! {
     }
 }
 
+//<panic>
+parser! {
+    fn panic_parser['a]()(MyStream<'a>)->ast::ExprAST
+    {
+        (
+            position(),
+            string("panic")
+            .skip(not_followed_by(alpha_num().or(char('_'))))
+        )
+        .map(|(pos,_)|ast::ExprAST::PanicAST(ast::PanicAST{message:"explicit panic".to_string(),pos}))
+    }
+}
+
 //<if>
 parser! {
     fn if_parser['a]()(MyStream<'a>)->ast::ExprAST
@@ -425,14 +694,15 @@ parser! {
                     .skip(char(']'))
                 ),
             ),
-            lambda_params_parser()
+            lambda_pattern_params_parser()
             .skip(string("->"))
             .skip(skip_many_parser()),
             expr_parser()
         )
-        .map(move|(pos,env,params,body)|
-            ast::ExprAST::create_lambda_ast(env.unwrap_or(vec![]),params,body,pos)
-        )
+        .map(move|(pos,env,params,body)|{
+            let (params,patterns)=ast::PatternAST::split_params(params);
+            ast::ExprAST::LambdaAST(Box::new(ast::LambdaAST{env,params,patterns,body,pos}))
+        })
     }
 }
 
@@ -455,6 +725,22 @@ parser! {
     }
 }
 
+parser! {
+    fn lambda_pattern_params_parser['a]()(MyStream<'a>)->Vec<ast::PatternAST>
+    {
+        skip_many_parser()
+        .with(
+            try(
+                sep_by(
+                    pattern_parser(),
+                    try((skip_many_parser(),char(','),skip_many_parser()))
+                )
+            )
+            .skip(skip_many_parser())
+        )
+    }
+}
+
 //<dec_func>
 parser! {
    fn dec_func_parser['a]()(MyStream<'a>) ->ast::DecFuncAST
@@ -473,7 +759,9 @@ parser! {
                 name: name,
                 ty: ty,
                 extern_flag: false,
-                pos
+                export_flag: false,
+                pos,
+                doc: None,
             }
         })
     }
@@ -492,3 +780,17 @@ parser! {
         })
     }
 }
+
+//<export_dec_func>
+parser! {
+   fn export_dec_func_parser['a]()(MyStream<'a>) ->ast::DecFuncAST
+    {
+        string("export")
+        .with(skip_many_parser())
+        .with(dec_func_parser())
+        .map(|mut x|{
+          x.export_flag=true;
+          x
+        })
+    }
+}
@@ -1,5 +1,5 @@
-use combine::char::{newline, space, tab, string};
-use combine::{many, many1,any,value};
+use combine::char::{char, newline, space, tab, string};
+use combine::{many, many1,any,value,optional};
 use combine::parser::item::none_of;
 use combine::parser::combinator::{try, not_followed_by};
 use super::parser::MyStream;
@@ -34,6 +34,35 @@ parser! {
     }
 }
 
+//<doc_skip_many>
+//skip_manyと同じだが、///の行を集めて返す
+parser! {
+    pub fn doc_skip_many_parser['a]()(MyStream<'a>)->Option<String>
+
+    {
+        many::<Vec<_>,_>(
+            doc_comment_parser().map(Some)
+            .or(skip_parser().map(|_|None))
+        ).map(|lines|{
+            let lines=lines.into_iter().filter_map(|x|x).collect::<Vec<_>>();
+            if lines.len()==0 { None } else { Some(lines.join("\n")) }
+        })
+    }
+}
+
+//<doc_comment>
+//////で始まる行は普通のコメントとして扱う
+parser! {
+    pub fn doc_comment_parser['a]()(MyStream<'a>)->String
+    {
+        (
+            try(string("///").skip(not_followed_by(char('/')))),
+            optional(char(' ')),
+            many::<String,_>( none_of(vec!['\n'].into_iter()))
+        ).map(|(_,_,line)|line.trim_end().to_string())
+    }
+}
+
 //<comment>
 parser! {
     pub fn comment_parser['a]()(MyStream<'a>)->()
@@ -2,10 +2,10 @@ use super::super::types::types::*;
 use super::super::ir::ast::*;
 use super::parser::{MyStream, id_parser,upper_id_parser};
 use super::skipper::skip_many_parser;
-use combine::char::{char, string};
+use combine::char::{char, digit, string};
 use combine::parser::char::{alpha_num, lower};
 use combine::parser::combinator::try;
-use combine::{many,optional,sep_end_by};
+use combine::{many,many1,optional,sep_end_by};
 
 //<ty_term>
 parser! {
@@ -17,6 +17,7 @@ parser! {
         ).map(|(x,xs):(char,String)|TypeAST::TypeVarName(x.to_string()+&xs)))
        .or(try(ty_paren_parser()))
        .or(ty_tuple_parser().map(|tuple|TypeAST::TupleTypeAST(Box::new(tuple))))
+       .or(ty_row_parser().map(|row|TypeAST::RowTypeAST(Box::new(row))))
        .or(upper_id_parser().map(TypeAST::IdTypeAST))
     }
 }
@@ -136,4 +137,43 @@ parser! {
             ty_term_with_func_parser()
         )
     }
-}
\ No newline at end of file
+}
+//<ty_row>
+parser! {
+    fn ty_row_parser['a]()(MyStream<'a>)->RowTypeAST{
+        char('{')
+        .with(skip_many_parser())
+        .with((
+            sep_end_by(
+                ty_row_part_parser(),
+                try(
+                    skip_many_parser()
+                    .with(char(','))
+                    .skip(skip_many_parser())
+                )
+            )
+                .skip(skip_many_parser()),
+            optional(
+                char('|')
+                .with(skip_many_parser())
+                .with(id_parser())
+                .skip(skip_many_parser())
+            )
+        ))
+        .skip(char('}'))
+        .map(|(elements_ty,row_name)|RowTypeAST{elements_ty,row_name})
+    }
+}
+
+//<ty_row_part>
+parser! {
+    fn ty_row_part_parser['a]()(MyStream<'a>)->(String,TypeAST){
+        (
+            id_parser().or(many1(digit()))
+                .skip(skip_many_parser())
+                .skip(char(':'))
+                .skip(skip_many_parser()),
+            ty_term_with_func_parser()
+        )
+    }
+}
@@ -39,7 +39,7 @@ impl Binding {
     fn get_expr_binding_group(self, expr_mir: &ExprMir) -> Binding {
         use self::ExprMir::*;
         match expr_mir {
-            BoolMir(_) | NumMir(_) | VariableMir(_) => self,
+            BoolMir(_) | NumMir(_) | VariableMir(_) | PanicMir(_) => self,
             OpMir(x) => {
                 let binding = self.get_expr_binding_group(&x.r_expr);
                 binding.get_expr_binding_group(&x.l_expr)
@@ -69,7 +69,19 @@ impl Binding {
                 })
             }
             LambdaMir(x) => self.get_func_binding_group(&x.func_name),
-            NamePropertyMir(x) => self.get_expr_binding_group(&x.expr)
+            NamePropertyMir(x) => self.get_expr_binding_group(&x.expr),
+            PatternCheckMir(x) => {
+                let binding = self.get_expr_binding_group(&x.body);
+                x.checks.iter().fold(binding, |acc, (x, _, _)| {
+                    acc.get_expr_binding_group(x)
+                })
+            }
+            RecordUpdateMir(x) => {
+                let binding = self.get_expr_binding_group(&x.expr);
+                x.params.iter().fold(binding, |acc, (_, x)| {
+                    acc.get_expr_binding_group(x)
+                })
+            }
         }
     }
 }
@@ -83,7 +95,11 @@ fn binding_group_test() {
             name: name.to_string(),
             body: ExprMir::NumMir(NumMir::new("5".to_string(), SourcePosition::new())),
             params_len: 0,
+            param_names: vec![],
+            env_len: 0,
+            arity: None,
             pos: SourcePosition::new(),
+            top_name: name.to_string(),
         }
     }
     fn create_nest_func_mir(name: &str, f_name: &str) -> ImplicitFunc {
@@ -92,7 +108,11 @@ fn binding_group_test() {
                 name: name.to_string(),
                 body: ExprMir::create_global_variable_mir(f_name.to_string(), SourcePosition::new(), TypeId::new(0)),
                 params_len: 0,
+                param_names: vec![],
+                env_len: 0,
+                arity: None,
                 pos: SourcePosition::new(),
+                top_name: name.to_string(),
             }
         }
     }
@@ -0,0 +1,38 @@
+use super::super::error::Error;
+use super::super::types::*;
+use super::mir::*;
+use super::type_inference::assump_env::AssumpEnv;
+
+impl ProgramMir {
+    //公開する関数の単相な型を決めて、C ABIで表せる型か検査する
+    pub fn resolve_export_funcs(&mut self, assump: &AssumpEnv) -> Result<(), Error> {
+        for export_func in self.export_func_list.iter_mut() {
+            let scheme = assump.global_get(&export_func.name).unwrap();
+            export_func.ty = Some(self.ty_info.type_look_up(&scheme.get_qual().t, true));
+            let is_c_abi = match export_func.split_ty() {
+                Some((param_types, ret_type)) =>
+                    param_types.iter().chain(Some(&ret_type)).all(is_c_abi_type),
+                None => false
+            };
+            if !is_c_abi {
+                return Err(Error::new(
+                    export_func.pos,
+                    "exported function must have a monomorphic type made of Int32, Bool, tuples and structs",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_c_abi_type(ty: &Type) -> bool {
+    match ty {
+        Type::TCon { name } => name == "Int32" || name == "Bool",
+        Type::TupleType(x) => x.element_tys.iter().all(is_c_abi_type),
+        Type::StructType(x) => match x.ty {
+            StructInternalType::TupleType(ref x) => x.element_tys.iter().all(is_c_abi_type),
+            StructInternalType::RecordType(ref x) => x.element_tys.iter().all(|(_, ty)| is_c_abi_type(ty)),
+        },
+        Type::TyVar(_) | Type::TGen(_, _) | Type::LambdaType(_) => false,
+    }
+}
@@ -17,6 +17,7 @@ impl ProgramHir {
         let mut var_table = VariableTable::new(self.get_global_var_names());
 
         let mut program_mir = ProgramMir::empty();
+        program_mir.warning_list = self.warning_list;
         let struct_list = &self.struct_list;
         let mut dec_func_list = self.dec_func_list;
         program_mir = self.def_func_list
@@ -28,8 +29,12 @@ impl ProgramHir {
         program_mir = self.ex_dec_func_list
             .into_iter()
             .fold(Ok(program_mir), |acc, (_, x)| {
-                Ok(x.to_mir(acc?, struct_list))
+                x.to_mir(acc?, struct_list)
             })?;
+        //定義のない関数は公開できない
+        if let Some((_, x)) = dec_func_list.iter().find(|(_, x)| x.export_flag) {
+            return Err(Error::new(x.pos, "exported function is not defined"));
+        }
         Ok(program_mir)
     }
     //グローバル変数の名前一覧を取得（関数名しかないけど）
@@ -61,17 +66,40 @@ impl DefFuncAST {
         } else {
             self.params.len()
         };
-        var_table.in_nest(self.params.into_iter().map(|x| x.id));
+        let param_names: Vec<String> = self.params.into_iter().map(|x| x.id).collect();
+        var_table.in_nest(param_names.clone());
+        let first_lambda = *lambda_count;
         let func_ir = FuncMir {
-            name: self.name,
             body: self.body.to_mir(&mut program_ir, struct_list, var_table, lambda_count)?,
             params_len,
+            param_names,
+            env_len: 0,
+            arity: None,
             pos: self.pos,
+            top_name: self.name.clone(),
+            name: self.name,
         };
+        //本体で作ったラムダ式の関数に、定義した関数の名前を記録する
+        for idx in first_lambda..*lambda_count {
+            program_ir.implicit_func_list.get_mut(&format!("#{}", idx)).unwrap().func.top_name = func_ir.name.clone();
+        }
         match dec_func_list.remove(&func_ir.name) {
             Some(x) => {
+                if x.export_flag {
+                    if x.name == "main" {
+                        return Err(Error::new(x.pos, "main can not be exported"));
+                    }
+                    program_ir.export_func_list.push(ExportFuncMir {
+                        name: x.name.clone(),
+                        arity: x.ty.arity(),
+                        pos: x.pos,
+                        ty: None,
+                    });
+                }
                 let mut ty_var_table = TypeVariableTable::new();
-                let func_q = x.ty.to_ty(struct_list, &mut ty_var_table, &mut program_ir.ty_info);
+                let pos = x.pos;
+                let func_q = x.ty.to_ty(struct_list, &mut ty_var_table, &mut program_ir.ty_info)
+                    .map_err(|msg| Error::new(pos, &msg))?;
                 program_ir.explicit_func_list.push(ExplicitFunc {
                     func: func_ir,
                     scheme: Scheme::Forall { qual: Qual { ps: func_q.ps, t: Type::create_func_type2(func_q.t) }, tgen_count: 0 },
@@ -85,13 +113,15 @@ impl DefFuncAST {
 }
 
 impl DecFuncAST {
-    fn to_mir(self, mut program_ir: ProgramMir, struct_list: &HashMap<String, DecStructHir>) -> ProgramMir {
+    fn to_mir(self, mut program_ir: ProgramMir, struct_list: &HashMap<String, DecStructHir>) -> AstToIrResult<ProgramMir> {
         let mut ty_var_table = TypeVariableTable::new();
+        let pos = self.pos;
         let dec_func_ir = DecFuncMir {
             name: self.name,
             extern_flag: self.extern_flag,
             pos: self.pos,
-            ty: self.ty.to_ty(struct_list, &mut ty_var_table, &mut program_ir.ty_info),
+            ty: self.ty.to_ty(struct_list, &mut ty_var_table, &mut program_ir.ty_info)
+                .map_err(|msg| Error::new(pos, &msg))?,
         };
         match self.extern_flag {
             true => {
@@ -99,86 +129,123 @@ impl DecFuncAST {
             }
             _ => panic!("error!"),
         }
-        program_ir
+        Ok(program_ir)
     }
 }
 
 impl FuncTypeAST {
-    fn to_ty(self, struct_list: &HashMap<String, DecStructHir>, ty_var_table: &mut TypeVariableTable, ty_info: &mut TypeInfo) -> Qual<FuncType> {
-        let ret_q = self.ret_ty.to_ty(struct_list, ty_var_table, ty_info);
-        let param_qs: Vec<_> = self.params_ty.into_iter()
-            .map(|x| x.to_ty(struct_list, ty_var_table, ty_info)).collect();
-        let (pss, param_ts) = Qual::split(param_qs);
-        let mut ps = pss.into_iter().fold(HashMap::new(), |mut acc, ps| {
-            acc.extend(ps.into_iter());
-            acc
-        });
-        ps.extend(ret_q.ps.into_iter());
-        Qual {
+    //矢印の数
+    fn arity(&self) -> usize {
+        match self.ret_ty {
+            TypeAST::FuncTypeAST(ref x) => 1 + x.arity(),
+            _ => 1
+        }
+    }
+
+    fn to_ty(self, struct_list: &HashMap<String, DecStructHir>, ty_var_table: &mut TypeVariableTable, ty_info: &mut TypeInfo) -> Result<Qual<FuncType>, String> {
+        let ret_q = self.ret_ty.to_ty(struct_list, ty_var_table, ty_info)?;
+        let param_qs = self.params_ty.into_iter()
+            .map(|x| x.to_ty(struct_list, ty_var_table, ty_info))
+            .collect::<Result<Vec<_>, String>>()?;
+        let (mut pss, param_ts) = Qual::split(param_qs);
+        //同じ型変数への制約はまとめる
+        pss.push(ret_q.ps);
+        let ps = ty_info.predss_merge_unify(pss)?;
+        Ok(Qual {
             t: FuncType {
                 ret_type: ret_q.t,
                 param_types: param_ts,
             },
-            ps: Preds(ps),
-        }
+            ps,
+        })
     }
 }
 
 impl TupleTypeAST {
-    fn to_ty(self, struct_list: &HashMap<String, DecStructHir>, ty_var_table: &mut TypeVariableTable, ty_info: &mut TypeInfo) -> TupleType {
-        let element_qs: Vec<_> = self.elements_ty.into_iter()
-            .map(|x| x.to_ty(struct_list, ty_var_table, ty_info)).collect();
-        let (_pss, element_ts) = Qual::split(element_qs);
-        TupleType {
-            element_tys: element_ts
-        }
+    fn to_ty(self, struct_list: &HashMap<String, DecStructHir>, ty_var_table: &mut TypeVariableTable, ty_info: &mut TypeInfo) -> Result<Qual<TupleType>, String> {
+        let element_qs = self.elements_ty.into_iter()
+            .map(|x| x.to_ty(struct_list, ty_var_table, ty_info))
+            .collect::<Result<Vec<_>, String>>()?;
+        let (pss, element_ts) = Qual::split(element_qs);
+        Ok(Qual {
+            t: TupleType {
+                element_tys: element_ts
+            },
+            ps: ty_info.predss_merge_unify(pss)?,
+        })
     }
 }
 
 impl RecordTypeAST {
-    fn to_ty(self, struct_list: &HashMap<String, DecStructHir>, ty_var_table: &mut TypeVariableTable, ty_info: &mut TypeInfo) -> RecordType {
+    fn to_ty(self, struct_list: &HashMap<String, DecStructHir>, ty_var_table: &mut TypeVariableTable, ty_info: &mut TypeInfo) -> Result<RecordType, String> {
         let (names, element_qs): (Vec<_>, Vec<_>) = self.elements_ty.into_iter()
             .map(|(name, type_ast)|
                 (name, type_ast.to_ty(struct_list, ty_var_table, ty_info))
             ).unzip();
+        let element_qs = element_qs.into_iter().collect::<Result<Vec<_>, String>>()?;
         let (_pss, element_ts) = Qual::split(element_qs);
-        RecordType {
+        Ok(RecordType {
             element_tys: names.into_iter().zip(element_ts.into_iter()).collect()
+        })
+    }
+}
+
+impl RowTypeAST {
+    //残りのフィールドを表す型変数に、フィールドを持つという制約をかける
+    fn to_ty(self, struct_list: &HashMap<String, DecStructHir>, ty_var_table: &mut TypeVariableTable, ty_info: &mut TypeInfo) -> Result<Qual<Type>, String> {
+        let row_ty = match self.row_name {
+            Some(row_name) => ty_var_table.get_ty(row_name, ty_info),
+            None => ty_info.no_name_get(),
+        };
+        let mut pss = vec![];
+        for (name, type_ast) in self.elements_ty {
+            let element_q = type_ast.to_ty(struct_list, ty_var_table, ty_info)?;
+            pss.push(element_q.ps);
+            let items = match name.parse::<u32>() {
+                Ok(index) => ImplItems::with_index_property(index, element_q.t),
+                Err(_) => ImplItems::with_name_property(name, element_q.t),
+            };
+            let mut ps = Preds::new();
+            ps.insert(row_ty.clone(), Pred { ty: row_ty.clone(), cond: Condition::Items(Box::new(items)) });
+            pss.push(ps);
         }
+        Ok(Qual { t: row_ty, ps: ty_info.predss_merge_unify(pss)? })
     }
 }
 
 impl StructTypeAST {
-    fn to_ty(self, struct_list: &HashMap<String, DecStructHir>, ty_var_table: &mut TypeVariableTable, ty_info: &mut TypeInfo) -> StructType {
+    fn to_ty(self, struct_list: &HashMap<String, DecStructHir>, ty_var_table: &mut TypeVariableTable, ty_info: &mut TypeInfo) -> Result<StructType, String> {
         let internal_ty =
             match self.ty {
-                StructInternalTypeAST::TupleTypeAST(x) => StructInternalType::TupleType(x.to_ty(struct_list, ty_var_table, ty_info)),
-                StructInternalTypeAST::RecordTypeAST(x) => StructInternalType::RecordType(x.to_ty(struct_list, ty_var_table, ty_info))
+                StructInternalTypeAST::TupleTypeAST(x) => StructInternalType::TupleType(x.to_ty(struct_list, ty_var_table, ty_info)?.t),
+                StructInternalTypeAST::RecordTypeAST(x) => StructInternalType::RecordType(x.to_ty(struct_list, ty_var_table, ty_info)?)
             };
-        StructType { ty: internal_ty, name: self.name }
+        Ok(StructType { ty: internal_ty, name: self.name })
     }
 }
 
 impl TypeAST {
-    fn to_ty(self, struct_list: &HashMap<String, DecStructHir>, ty_var_table: &mut TypeVariableTable, ty_info: &mut TypeInfo) -> Qual<Type> {
+    fn to_ty(self, struct_list: &HashMap<String, DecStructHir>, ty_var_table: &mut TypeVariableTable, ty_info: &mut TypeInfo) -> Result<Qual<Type>, String> {
         match self {
-            TypeAST::Type(x) => Qual::new(x),
+            TypeAST::Type(x) => Ok(Qual::new(x)),
             TypeAST::FuncTypeAST(x) => {
                 let ty_id = ty_info.fresh_type_id();
-                let func_q = x.to_ty(struct_list, ty_var_table, ty_info);
+                let func_q = x.to_ty(struct_list, ty_var_table, ty_info)?;
                 let cond = Condition::Call(Box::new(func_q.t));
                 let mut ps = func_q.ps;
                 ps.insert(Type::TyVar(ty_id.clone()), Pred { ty: Type::TyVar(ty_id.clone()), cond });
-                Qual { t: Type::TyVar(ty_id), ps }
+                Ok(Qual { t: Type::TyVar(ty_id), ps })
             }
-            TypeAST::TupleTypeAST(x) => Qual::new(Type::TupleType(
-                Box::new(x.to_ty(struct_list, ty_var_table, ty_info))
-            )),
-            TypeAST::TypeVarName(ty_name) => Qual::new(ty_var_table.get_ty(ty_name, ty_info)),
-            TypeAST::StructTypeAST(x) => Qual::new(Type::StructType(Box::new(x.to_ty(struct_list, ty_var_table, ty_info)))),
+            TypeAST::TupleTypeAST(x) => {
+                let tuple_q = x.to_ty(struct_list, ty_var_table, ty_info)?;
+                Ok(Qual { t: Type::TupleType(Box::new(tuple_q.t)), ps: tuple_q.ps })
+            }
+            TypeAST::TypeVarName(ty_name) => Ok(Qual::new(ty_var_table.get_ty(ty_name, ty_info))),
+            TypeAST::StructTypeAST(x) => Ok(Qual::new(Type::StructType(Box::new(x.to_ty(struct_list, ty_var_table, ty_info)?)))),
+            TypeAST::RowTypeAST(x) => x.to_ty(struct_list, ty_var_table, ty_info),
             TypeAST::IdTypeAST(id) => {
                 if struct_list.contains_key(&id) {
-                    Qual::new(Type::StructType(Box::new((&struct_list[&id]).ty.clone().to_ty(struct_list, ty_var_table, ty_info))))
+                    Ok(Qual::new(Type::StructType(Box::new((&struct_list[&id]).ty.clone().to_ty(struct_list, ty_var_table, ty_info)?))))
                 } else {
                     panic!("実装めんどいな")
                 }
@@ -198,6 +265,7 @@ impl ExprAST {
         match self {
             ExprAST::NumAST(x) => Ok(ExprMir::NumMir(x)),
             ExprAST::BoolAST(x) => Ok(ExprMir::BoolMir(x)),
+            ExprAST::PanicAST(x) => Ok(ExprMir::PanicMir(PanicMir { message: x.message, pos: x.pos, ty_id: program_ir.ty_info.fresh_type_id() })),
             ExprAST::IfAST(x) => x.to_mir(program_ir, struct_list, var_table, lambda_count),
             ExprAST::OpAST(x) => x.to_mir(program_ir, struct_list, var_table, lambda_count),
             ExprAST::VariableAST(x) => {
@@ -209,11 +277,14 @@ impl ExprAST {
             ExprAST::ParenAST(x) => x.expr.to_mir(program_ir, struct_list, var_table, lambda_count),
             ExprAST::FuncCallAST(x) => x.to_mir(program_ir, struct_list, var_table, lambda_count),
             ExprAST::NamedParamsConstructorCallAST(_) => panic!("bug!!"),
+            ExprAST::RecordUpdateAST(x) => x.to_mir(program_ir, struct_list, var_table, lambda_count),
+            ExprAST::MatchAST(_) => panic!("bug!!"),
             ExprAST::TupleAST(x) => x.to_mir(program_ir, struct_list, var_table, lambda_count),
             ExprAST::TupleStructAST(x) => x.to_mir(program_ir, struct_list, var_table, lambda_count),
             ExprAST::LambdaAST(x) => x.to_mir(program_ir, struct_list, var_table, lambda_count),
             ExprAST::IndexPropertyAST(x) => x.to_mir(program_ir, struct_list, var_table, lambda_count),
             ExprAST::NamePropertyAST(x) => x.to_mir(program_ir, struct_list, var_table, lambda_count),
+            ExprAST::PatternCheckAST(x) => x.to_mir(program_ir, struct_list, var_table, lambda_count),
         }
     }
 }
@@ -248,6 +319,7 @@ impl OpAST {
             self.op,
             self.l_expr.to_mir(program_ir, struct_list, var_table, lambda_count)?,
             self.r_expr.to_mir(program_ir, struct_list, var_table, lambda_count)?,
+            self.pos,
         ))
     }
 }
@@ -300,13 +372,15 @@ impl TupleStructAST {
         lambda_count: &mut usize,
     ) -> AstToIrResult<ExprMir> {
         let mut ty_var_table = TypeVariableTable::new();
+        let pos = self.tuple.pos;
         Ok(ExprMir::create_tuple_struct_mir(
             self.tuple.elements
                 .into_iter()
                 .map(|x| x.to_mir(program_ir, struct_list, var_table, lambda_count))
                 .collect::<AstToIrResult<Vec<ExprMir>>>()?,
-            self.tuple.pos,
-            self.ty.to_ty(struct_list, &mut ty_var_table, &mut program_ir.ty_info),
+            pos,
+            self.ty.to_ty(struct_list, &mut ty_var_table, &mut program_ir.ty_info)
+                .map_err(|msg| Error::new(pos, &msg))?,
             program_ir.ty_info.fresh_type_id(),
         ))
     }
@@ -320,8 +394,8 @@ impl LambdaAST {
         var_table: &mut VariableTable,
         lambda_count: &mut usize,
     ) -> AstToIrResult<ExprMir> {
-        let env = self
-            .env
+        let env = self.capture_variables(program_ir, var_table)?;
+        let env_mir = env
             .iter()
             .map(
                 |x| match var_table.get_variable_ir(x.clone(), &mut program_ir.ty_info) {
@@ -334,10 +408,12 @@ impl LambdaAST {
         } else {
             self.params.len()
         };
-        let params_len = params_len + self.env.len();
-        let env_id_iter = self.env.iter().map(|x| x.id.clone());
+        let env_len = env.len();
+        let params_len = params_len + env_len;
+        let env_id_iter = env.into_iter().map(|x| x.id);
         let params_id_iter = self.params.into_iter().map(|x| x.id);
-        var_table.in_nest(&mut env_id_iter.chain(params_id_iter));
+        let param_names: Vec<String> = env_id_iter.chain(params_id_iter).collect();
+        var_table.in_nest(param_names.clone());
         let body = self.body.to_mir(program_ir, struct_list, var_table, lambda_count)?;
         var_table.out_nest();
         *lambda_count += 1;
@@ -345,13 +421,17 @@ impl LambdaAST {
         program_ir.implicit_func_list.insert(lambda_name.clone(), ImplicitFunc {
             func: FuncMir {
                 params_len,
+                param_names,
+                env_len,
+                arity: None,
                 body,
                 pos: self.pos,
                 name: lambda_name.clone(),
+                top_name: String::new(),
             }
         });
         Ok(ExprMir::LambdaMir(Box::new(LambdaMir {
-            env,
+            env: env_mir,
             func_name: lambda_name,
             ty_id: program_ir.ty_info.fresh_type_id(),
             func_id: program_ir.ty_info.fresh_type_id(),
@@ -359,6 +439,36 @@ impl LambdaAST {
             params_len,
         })))
     }
+
+    //本体の自由変数のうち外側のローカル変数をキャプチャする
+    //明示的な指定が過不足していれば警告して自由変数に合わせる
+    fn capture_variables(&self, program_ir: &mut ProgramMir, var_table: &VariableTable) -> AstToIrResult<Vec<VariableAST>> {
+        let mut free = vec![];
+        self.body.free_variables(&mut self.params.iter().map(|x| x.id.clone()).collect::<Vec<_>>(), &mut free);
+        let local_var_names = var_table.local_var_names();
+        if let Some(ref env) = self.env {
+            for x in env {
+                if !local_var_names.contains(&x.id) {
+                    return Err(Error::new(x.pos, "Lambda capture not Local Variable"));
+                }
+                if !free.contains(&x.id) {
+                    program_ir.warning_list.push(Error::new(x.pos, &format!("captured variable `{}` is not used", x.id)));
+                }
+            }
+        }
+        let mut captures: Vec<&String> = vec![];
+        for name in local_var_names {
+            if free.contains(name) && !captures.contains(&name) {
+                captures.push(name);
+            }
+        }
+        if let Some(ref env) = self.env {
+            for name in captures.iter().filter(|name| !env.iter().any(|x| &x.id == **name)) {
+                program_ir.warning_list.push(Error::new(self.pos, &format!("variable `{}` is captured but not listed", name)));
+            }
+        }
+        Ok(captures.into_iter().map(|id| VariableAST::new(id.clone(), self.pos)).collect())
+    }
 }
 
 impl IndexPropertyAST {
@@ -399,6 +509,64 @@ impl NamePropertyAST {
     }
 }
 
+//更新する構造体は型推論で決まるので、ここではフィールド名のまま残す
+impl RecordUpdateAST {
+    fn to_mir(
+        self,
+        program_ir: &mut ProgramMir,
+        struct_list: &HashMap<String, DecStructHir>,
+        var_table: &mut VariableTable,
+        lambda_count: &mut usize,
+    ) -> AstToIrResult<ExprMir> {
+        let expr = self.expr.to_mir(program_ir, struct_list, var_table, lambda_count)?;
+        let params = self.params.into_iter()
+            .map(|(name, e)| Ok((name, e.to_mir(program_ir, struct_list, var_table, lambda_count)?)))
+            .collect::<AstToIrResult<Vec<_>>>()?;
+        Ok(
+            ExprMir::create_record_update_mir(
+                expr,
+                params,
+                self.pos,
+                program_ir.ty_info.fresh_type_id(),
+            )
+        )
+    }
+}
+
+//パターンの形を型にする。タプルの要素の型は決めない
+impl PatternCheckAST {
+    fn to_mir(
+        self,
+        program_ir: &mut ProgramMir,
+        struct_list: &HashMap<String, DecStructHir>,
+        var_table: &mut VariableTable,
+        lambda_count: &mut usize,
+    ) -> AstToIrResult<ExprMir> {
+        let pos = self.pos;
+        let checks = self.checks.into_iter()
+            .map(|(expr, shape)| {
+                let ty = match shape {
+                    PatternShapeAST::TupleAST(len) => Type::TupleType(Box::new(TupleType {
+                        element_tys: (0..len).map(|_| program_ir.ty_info.no_name_get()).collect()
+                    })),
+                    PatternShapeAST::RecordAST(ref name) => Type::StructType(Box::new(
+                        struct_list[name].ty.clone().to_ty(struct_list, &mut TypeVariableTable::new(), &mut program_ir.ty_info)
+                            .map_err(|msg| Error::new(pos, &msg))?
+                    )),
+                };
+                Ok((expr.to_mir(program_ir, struct_list, var_table, lambda_count)?, shape, ty))
+            })
+            .collect::<AstToIrResult<Vec<_>>>()?;
+        Ok(
+            ExprMir::create_pattern_check_mir(
+                checks,
+                self.body.to_mir(program_ir, struct_list, var_table, lambda_count)?,
+                pos,
+            )
+        )
+    }
+}
+
 #[test]
 fn ast_to_ir_test() {
     use super::type_env::TypeInfo;
@@ -411,11 +579,13 @@ fn ast_to_ir_test() {
                 VariableAST::new("a".to_string(), SourcePosition { column: 0, line: 0 }),
                 VariableAST::new("b".to_string(), SourcePosition { column: 0, line: 0 }),
             ],
+            patterns: vec![],
             body: ExprAST::create_variable_ast(
                 "b".to_string(),
                 SourcePosition { column: 0, line: 0 },
             ),
             pos: SourcePosition { column: 0, line: 0 },
+            doc: None,
         })],
     };
     use indexmap::IndexMap;
@@ -425,14 +595,20 @@ fn ast_to_ir_test() {
             name: "main".to_string(),
             body: ExprMir::create_variable_mir(0, SourcePosition { line: 0, column: 0 }, TypeId::new(0)),
             params_len: 2,
+            param_names: vec![],
+            env_len: 0,
+            arity: None,
             pos: SourcePosition { column: 0, line: 0 },
+            top_name: "main".to_string(),
         }
     });
     let ir = ProgramMir {
         explicit_func_list: vec![],
         implicit_func_list: func_list,
         ex_dec_func_list: vec![],
+        export_func_list: vec![],
         ty_info: TypeInfo::new(),
+        warning_list: vec![],
     };
     let ir2 = ast.to_hir().unwrap().to_mir().unwrap();
     assert_eq!(ir2.implicit_func_list, ir.implicit_func_list);
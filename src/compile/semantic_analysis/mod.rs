@@ -4,17 +4,31 @@ pub mod variable_table;
 pub mod type_variable_table;
 pub mod type_inference;
 pub mod binding_group;
+pub mod export_func;
 
 pub use self::type_inference::type_env;
 use super::ir::mir;
 use super::semantic_analysis::type_inference::assump_env::AssumpEnv;
 use super::ir::ast;
+use super::ir::hir;
 use super::error::Error;
 use self::binding_group::Binding;
 
 pub fn analysis(ast: ast::ProgramAST) -> Result<(mir::ProgramMir, AssumpEnv), Error> {
-    let mut ir = ast.ast_transformer()?.to_mir()?;
+    analysis_hir(ast.to_hir()?)
+}
+
+//main関数のないライブラリとして解析する
+pub fn analysis_lib(ast: ast::ProgramAST) -> Result<(mir::ProgramMir, AssumpEnv), Error> {
+    analysis_hir(ast.to_lib_hir()?)
+}
+
+//HIRを解析する
+pub fn analysis_hir(hir: hir::ProgramHir) -> Result<(mir::ProgramMir, AssumpEnv), Error> {
+    let mut ir = hir.hir_transformer()?.to_mir()?;
     ir.implicit_func_list = Binding::create_binding_group(ir.implicit_func_list);
-    println!("\nType Inference \n");
-    ir.ty_get()
+    debug_println!("\nType Inference \n");
+    let (mut ir, assump) = ir.ty_get()?;
+    ir.resolve_export_funcs(&assump)?;
+    Ok((ir, assump))
 }
@@ -3,6 +3,7 @@ use super::super::types::*;
 use super::type_env::*;
 use super::mir::*;
 use super::type_inference::assump_env::AssumpEnv;
+use super::super::ir::ast::PatternShapeAST;
 
 type TyCheckResult<T> = Result<T, Error>;
 
@@ -36,25 +37,32 @@ impl ProgramMir {
                 .chain(self.ex_dec_func_list.iter().map(|x| (
                     x.name.clone(),
                     Qual {
-                        t: Type::LambdaType(Box::new(LambdaType { env_ty: None, func_ty: x.ty.t.clone() })),
+                        t: Type::LambdaType(Box::new(LambdaType { func_ty: x.ty.t.clone() })),
                         ps: x.ty.ps.clone(),
                     }
                 )))
-                .for_each(|(name, qual)| assump.global_set(name, Scheme::quantify(qual.t.get_lambda_ty().func_ty.param_types.tv_list(), qual)));
+                .for_each(|(name, qual)| {
+                    let tv_list = qual.ps.reachable_tv_list(qual.t.get_lambda_ty().func_ty.param_types.tv_list());
+                    assump.global_set(name, Scheme::quantify(tv_list, qual))
+                });
         }
         //関数宣言の型チェック
         let (assump, _) =
             ty_get_all(self.implicit_func_list.iter().map(|(_, x)| x), &mut self.ty_info, assump)?;
         //関数定義の型チェック
-        let (assump, _) =
+        let (mut assump, _) =
             ty_get_all(self.explicit_func_list.iter().map(|x| x), &mut self.ty_info, assump)?;
-        // main関数の型をチェック
-        let main_func_q = assump.global_get(&"main".to_string()).unwrap().clone().fresh_inst(&mut self.ty_info);
-        let main_func_ty = Type::create_func_type(vec![Type::create_tuple_type(vec![])], Type::create_int32());
-        self.ty_info.qual_unify(main_func_q, Qual::new(main_func_ty)).map_err(|msg| Error::new(self.get_func_mir(&"main".to_string()).unwrap().pos, &msg))?;
+        // main関数の型をチェック(ライブラリならmainはない)
+        if let Some(main_scheme) = assump.global_get(&"main".to_string()).cloned() {
+            let main_func_q = main_scheme.fresh_inst(&mut self.ty_info);
+            let main_func_ty = Type::create_func_type(vec![Type::create_tuple_type(vec![])], Type::create_int32());
+            self.ty_info.qual_unify(main_func_q, Qual::new(main_func_ty.clone())).map_err(|msg| Error::new(self.get_func_mir(&"main".to_string()).unwrap().pos, &msg))?;
+            //-check -typesなどで見えるmainの型も単一化した()->Int32にする
+            assump.global_set("main".to_string(), Scheme::to_scheme(Qual::new(main_func_ty)));
+        }
 
-        println!("\nAssump List \n");
-        println!("{:?}", assump);
+        debug_println!("\nAssump List \n");
+        debug_println!("{:?}", assump);
         Ok((self, assump))
     }
 }
@@ -74,7 +82,8 @@ impl<'a> TypeGet for &'a ExplicitFunc {
             Scheme::Forall { qual, .. } => {
                 let (mut assump, ty) = (&self.func).ty_get(ty_info, assump)?;
 
-                let ty = Scheme::quantify(ty.t.get_lambda_ty().func_ty.param_types.tv_list(), ty);
+                let tv_list = ty.ps.reachable_tv_list(ty.t.get_lambda_ty().func_ty.param_types.tv_list());
+                let ty = Scheme::quantify(tv_list, ty);
                 let mut ty = ty.get_qual().clone().apply(&ty_info.0, true);
                 let mut qual = qual.clone().apply(&ty_info.0, true);
                 ty.ps = ty_info.0.preds_simply(ty.ps, ty.t.tv_list());
@@ -95,7 +104,7 @@ impl<'a> TypeGet for &'a DecFuncMir {
         let q = ty_info.qual_unify(
             func_q,
             Qual {
-                t: Type::LambdaType(Box::new(LambdaType { env_ty: None, func_ty: func_type_q.t })),
+                t: Type::LambdaType(Box::new(LambdaType { func_ty: func_type_q.t })),
                 ps: func_type_q.ps,
             },
         ).map_err(|msg| Error::new(self.pos, &msg))?;
@@ -150,7 +159,10 @@ impl<'a> TypeGet for &'a ExprMir {
             ExprMir::TupleStructMir(x) => x.ty_get(ty_info, assump),
             ExprMir::IndexPropertyMir(x) => x.ty_get(ty_info, assump),
             ExprMir::NamePropertyMir(x) => x.ty_get(ty_info, assump),
-            ExprMir::LambdaMir(x) => x.ty_get(ty_info, assump)
+            ExprMir::RecordUpdateMir(x) => x.ty_get(ty_info, assump),
+            ExprMir::PatternCheckMir(x) => x.ty_get(ty_info, assump),
+            ExprMir::LambdaMir(x) => x.ty_get(ty_info, assump),
+            ExprMir::PanicMir(x) => x.ty_get(ty_info, assump),
         }
     }
 }
@@ -167,6 +179,13 @@ impl TypeGet for BoolMir {
     }
 }
 
+//panicは戻らないのでどの型にもなれる
+impl TypeGet for PanicMir {
+    fn ty_get(&self, _ty_info: &mut TypeInfo, assump: AssumpEnv) -> TyCheckResult<(AssumpEnv, Qual<Type>)> {
+        Ok((assump, Qual::new(Type::TyVar(self.ty_id.clone()))))
+    }
+}
+
 impl TypeGet for IfMir {
     fn ty_get(&self, ty_info: &mut TypeInfo, assump: AssumpEnv) -> TyCheckResult<(AssumpEnv, Qual<Type>)> {
         let (assump, cond_ty) = (&self.cond).ty_get(ty_info, assump)?;
@@ -327,19 +346,10 @@ impl TypeGet for LambdaMir {
             Type::LambdaType(x) => x.func_ty,
             _ => panic!("error!")
         };
-        let mut lambda_q = if envs_ty.len() == 0 {
-            Qual {
-                ps: func_q.ps,
-                t: Type::LambdaType(Box::new(LambdaType {
-                    func_ty: func_ty2.clone(),
-                    env_ty: None,
-                })),
-            }
-        } else {
-            Qual {
-                ps: func_q.ps,
-                t: Type::create_lambda_type(envs_ty, func_ty2.clone()),
-            }
+        //ラムダ式の値の型には環境の引数を含めない
+        let mut lambda_q = Qual {
+            ps: func_q.ps,
+            t: Type::create_func_type(func_ty2.param_types.into_iter().skip(envs_ty.len()).collect(), func_ty2.ret_type),
         };
         let ps = ty_info.preds_merge_unify(ps, lambda_q.ps)
             .map_err(|msg| Error::new(self.pos, &msg))?;
@@ -380,4 +390,48 @@ impl TypeGet for NamePropertyMir {
         q.t = property_ty;
         Ok((assump, q))
     }
+}
+
+//更新するフィールドを全て持つ型の値を受け取り、同じ型の値を返す
+impl TypeGet for RecordUpdateMir {
+    fn ty_get(&self, ty_info: &mut TypeInfo, assump: AssumpEnv) -> TyCheckResult<(AssumpEnv, Qual<Type>)> {
+        let (assump, expr_q) = (&self.expr).ty_get(ty_info, assump)?;
+        let (assump, params_qs) =
+            ty_get_all(self.params.iter().map(|(_, x)| x), ty_info, assump)?;
+        let (pss, params_ty) = Qual::split(params_qs);
+        let ps = ty_info.predss_merge_unify(pss)
+            .map_err(|msg| Error::new(self.pos, &msg))?;
+        let mut q = self.params.iter().zip(params_ty)
+            .fold(Ok(expr_q), |acc, ((name, _), ty)| {
+                ty_info.qual_condition_add_unify(acc?, Condition::Items(Box::new(ImplItems::with_name_property(name.clone(), ty))))
+            }).map_err(|msg| Error::new(self.pos, &msg))?;
+        q.ps = ty_info.preds_merge_unify(q.ps, ps)
+            .map_err(|msg| Error::new(self.pos, &msg))?;
+        let q = ty_info.qual_unify(q, Qual::new(Type::TyVar(self.ty_id.clone())))
+            .map_err(|msg| Error::new(self.pos, &msg))?;
+        Ok((assump, q))
+    }
+}
+
+//引数の型をパターンの形に固定してから本体の型を求める
+impl TypeGet for PatternCheckMir {
+    fn ty_get(&self, ty_info: &mut TypeInfo, assump: AssumpEnv) -> TyCheckResult<(AssumpEnv, Qual<Type>)> {
+        let mut assump = assump;
+        let mut pss = vec![];
+        for (expr, shape, ty) in self.checks.iter() {
+            let (new_assump, q) = expr.ty_get(ty_info, assump)?;
+            assump = new_assump;
+            let q = ty_info.qual_unify(q, Qual::new(ty.clone()))
+                .map_err(|_| Error::new(expr.get_pos(), &match shape {
+                    PatternShapeAST::TupleAST(len) => format!("pattern arity mismatch: expected a tuple of {} elements", len),
+                    PatternShapeAST::RecordAST(name) => format!("pattern type mismatch: expected `{}`", name),
+                }))?;
+            pss.push(q.ps);
+        }
+        let (assump, body_q) = (&self.body).ty_get(ty_info, assump)?;
+        pss.push(body_q.ps);
+        let ps = ty_info.predss_merge_unify(pss)
+            .map_err(|msg| Error::new(self.pos, &msg))?;
+        Ok((assump, Qual { ps, t: body_q.t }))
+    }
 }
\ No newline at end of file
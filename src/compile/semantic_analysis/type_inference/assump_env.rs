@@ -42,12 +42,20 @@ impl AssumpEnv {
         self.env[0].get(symbol)
     }
 
+    //トップレベルの名前と型スキームの一覧を名前順で取得する
+    //#を含む名前はコンパイラが作った関数なので除く
+    pub fn global_list(&self) -> Vec<(&String, &Scheme)> {
+        let mut list = self.env[0].iter().filter(|(name, _)| !name.contains('#')).collect::<Vec<_>>();
+        list.sort_by(|(a, _), (b, _)| a.cmp(b));
+        list
+    }
+
     pub fn get(&self, symbol: &String) -> Option<&Scheme> {
         self.env[self.nest].get(symbol)
     }
 
     pub fn global_set(&mut self, symbol: String, q: Scheme) {
-        println!("global_set {} {:?}", symbol, q);
+        debug_println!("global_set {} {:?}", symbol, q);
         self.env[0].insert(symbol, q);
     }
 
@@ -19,10 +19,7 @@ pub fn occurs_check(hash_map: &TypeSubstituteHashMap, ty: &Type, ty_id: &TypeId)
         Type::TupleType(x) => x.occurs_check(hash_map, ty_id),
         Type::LambdaType(x) => {
             let x = &**x;
-            x.env_ty.as_ref().unwrap_or(&TupleType { element_tys: vec![] })
-                .element_tys.iter().any(|e| occurs_check(hash_map, e, ty_id))
-                ||
-                x.func_ty.occurs_check(hash_map, ty_id)
+            x.func_ty.occurs_check(hash_map, ty_id)
                 ||
                 occurs_check(hash_map, &x.func_ty.ret_type, ty_id)
         }
@@ -39,7 +39,7 @@ impl TypeEnv {
                 x
             }
             _ => {
-                println!("{:?}:={:?}", symbol, self.id);
+                debug_println!("{:?}:={:?}", symbol, self.id);
                 let ty = Type::TyVar(TypeId::new(self.id));
                 self.env[0].insert(symbol, ty.clone());
                 self.id += 1;
@@ -56,7 +56,7 @@ impl TypeEnv {
                 x
             }
             _ => {
-                println!("{:?}:={:?}", symbol, self.id);
+                debug_println!("{:?}:={:?}", symbol, self.id);
                 let ty = Type::TyVar(TypeId::new(self.id));
                 self.env[self.nest].insert(symbol, ty.clone());
                 self.id += 1;
@@ -71,7 +71,7 @@ impl TypeEnv {
     }
     pub fn fresh_type_id(&mut self) -> TypeId {
         let ty_id = TypeId::new(self.id);
-        println!("{{no_symbol}}:={:?}", self.id);
+        debug_println!("{{no_symbol}}:={:?}", self.id);
         self.id += 1;
         ty_id
     }
@@ -45,7 +45,6 @@ impl TypeSubstitute {
 
     fn lambda_look_up(&self, ty: &LambdaType, inst_flag: bool) -> Type {
         Type::LambdaType(Box::new(LambdaType {
-            env_ty: ty.env_ty.clone().map(|x| self.tuple_look_up(&x, inst_flag)),
             func_ty: self.func_look_up(&ty.func_ty, inst_flag),
         }))
     }
@@ -41,10 +41,10 @@ impl TypeSubstitute {
     //型変数に型を代入する
     fn safe_insert(&mut self, ty_id: TypeId, insert_ty: Type) {
         if occurs_check(&self.ty_sub, &insert_ty, &ty_id) == false {
-            println!("{:?}=>{:?}", ty_id, insert_ty);
+            debug_println!("{:?}=>{:?}", ty_id, insert_ty);
             self.ty_sub.insert(ty_id, insert_ty);
         } else {
-            println!("occurs! {:?}=>{:?}", ty_id, insert_ty);
+            debug_println!("occurs! {:?}=>{:?}", ty_id, insert_ty);
         }
     }
     fn qual_left(&mut self, q1: Qual<Type>, q2: &Qual<Type>) -> Result<Qual<Type>, String> {
@@ -149,10 +149,7 @@ impl TypeSubstitute {
             LambdaType(x) => {
                 match c {
                     Condition::Call(c) => {
-                        let env_len = x.env_ty.clone().map(|t| t.element_tys.len()).unwrap_or(0);
-                        let mut func_ty = x.func_ty.clone();
-                        func_ty.param_types = func_ty.param_types.clone().into_iter().skip(env_len).collect::<Vec<_>>();
-                        self.fn_unify(func_ty, *c)?;
+                        self.fn_unify(x.func_ty.clone(), *c)?;
                     }
                     Condition::Empty => (),
                     c => { return create_error(&x, &c); }
@@ -240,11 +237,6 @@ impl TypeSubstitute {
 
     //ラムダの単一化処理
     fn lambda_unify(&mut self, ty1: LambdaType, ty2: LambdaType) -> Result<Type, String> {
-        match (ty1.env_ty.clone(), ty2.env_ty) {
-            (Some(x), Some(y)) => { self.tuple_unify(x, y)?; }
-            (None, None) => (),
-            (None, x) | (x, None) => { return create_error(&"void", &x); }
-        }
         self.fn_unify(ty1.func_ty.clone(), ty2.func_ty)?;
         Ok(Type::LambdaType(Box::new(ty1)))
     }
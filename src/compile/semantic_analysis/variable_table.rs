@@ -35,6 +35,13 @@ impl VariableTable {
             }
         }
     }
+    //今のネストで参照できるローカル変数の名前。後から束縛されたものが後ろに並ぶ
+    pub fn local_var_names(&self) -> &[String] {
+        match self.nest_level {
+            0 => &[],
+            n => &self.local_var_names[n - 1]
+        }
+    }
     pub fn in_nest<T>(&mut self, iter: T)
         where T: IntoIterator<Item=String> {
         if self.local_var_names.len() <= self.nest_level {
@@ -0,0 +1,36 @@
+extern crate inkwell;
+
+use self::inkwell::{module, targets};
+use super::compile_option::CompileOption;
+
+//オプションで指定されたターゲットのマシンを作る。指定がなければホスト向け
+pub fn create_target_machine(option: &CompileOption) -> Result<targets::TargetMachine, String> {
+    let triple = match option.target {
+        Some(ref triple) => {
+            //クロスコンパイルの時だけ全ターゲットを初期化する
+            targets::Target::initialize_all(&targets::InitializationConfig::default());
+            triple.to_string()
+        }
+        None => targets::TargetMachine::get_default_triple().to_string(),
+    };
+    let target = targets::Target::from_triple(&triple)
+        .map_err(|err| format!("unknown target `{}`: {}", triple, err.to_string()))?;
+    let cpu = option.target_cpu.as_ref().map(|x| x as &str).unwrap_or("generic");
+    let features = option.target_features.join(",");
+    //wasmには位置独立コードがない
+    let reloc_mode = if option.is_wasm() { targets::RelocMode::Static } else { targets::RelocMode::PIC };
+    target.create_target_machine(
+        &triple,
+        cpu,
+        &features,
+        option.opt_level.to_llvm_opt_level(),
+        reloc_mode,
+        targets::CodeModel::Default,
+    ).ok_or(format!("failed to create target machine for `{}` (cpu: {}, features: {})", triple, cpu, features))
+}
+
+//モジュールにターゲットのトリプルとデータレイアウトを設定する
+pub fn set_module_target(module: &module::Module, target_machine: &targets::TargetMachine) {
+    module.set_triple(&target_machine.get_triple());
+    module.set_data_layout(&target_machine.get_target_data().get_data_layout());
+}
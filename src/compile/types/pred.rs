@@ -40,7 +40,7 @@ impl Pred {
     }
 }
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::collections::hash_map::{Values, Iter};
 
 #[derive(Clone, PartialEq, Debug)]
@@ -129,4 +129,19 @@ impl Preds {
     pub fn get(&self, ty: &Type) -> Option<&Pred> {
         self.0.get(ty)
     }
+    //tv_listの型変数にかかった制約を辿って、制約の中に出てくる型変数も加える
+    //{x: a | r}のrを量化するならaも量化する
+    pub fn reachable_tv_list(&self, mut tv_list: HashSet<TypeId>) -> HashSet<TypeId> {
+        loop {
+            let len = tv_list.len();
+            for (ty, p) in self.0.iter() {
+                if ty.tv_list().iter().any(|ty_id| tv_list.contains(ty_id)) {
+                    tv_list.extend(p.cond.tv_list());
+                }
+            }
+            if len == tv_list.len() {
+                return tv_list;
+            }
+        }
+    }
 }
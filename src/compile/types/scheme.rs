@@ -25,6 +25,9 @@ impl Scheme {
         use super::super::semantic_analysis::type_inference::type_substitute::TypeSubstitute;
         let mut ty_sub = TypeSubstitute::new();
         let mut n: usize = 0;
+        //表示名が安定するように型変数の番号順に量化する
+        let mut ty_id_list = ty_id_list.into_iter().collect::<Vec<_>>();
+        ty_id_list.sort_by_key(|ty_id| ty_id.get_id());
         for ty_id in ty_id_list {
             ty_sub.ty_sub.insert(ty_id, Type::TGen(n, ty_id));
             n += 1;
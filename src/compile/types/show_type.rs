@@ -12,6 +12,15 @@ pub trait ShowType {
     fn show(&self) -> String;
 }
 
+//量化された型変数の表示名 a,b,c...
+pub fn tgen_name(n: usize) -> String {
+    if n < 26 {
+        ((b'a' + n as u8) as char).to_string()
+    } else {
+        format!("t{}", n)
+    }
+}
+
 pub fn type_error<T, A: ShowType, B: ShowType>(expect: &A, actual: &B) -> Result<T, String> {
     Err(format!("type error!\nexpect: {}\nactual: {}", expect.show(), actual.show()))
 }
@@ -20,7 +29,7 @@ impl ShowType for Type {
     fn show(&self) -> String {
         match self {
             Type::TCon { name } => name.clone(),
-            Type::TGen(n, ty_id) => format!("TGen {} {:?}", n, ty_id),
+            Type::TGen(n, _) => tgen_name(*n),
             Type::TupleType(x) => x.show(),
             Type::TyVar(ty_id) => ty_id.get_id().to_string(),
             Type::LambdaType(x) => x.show(),
@@ -43,7 +52,9 @@ impl ShowType for TupleType {
         "(".to_string()
             + &self.element_tys
             .iter()
-            .fold("".to_string(), |acc, x| acc + &x.show() + ",")
+            .map(|x| x.show())
+            .collect::<Vec<_>>()
+            .join(",")
             + ")"
     }
 }
@@ -70,10 +81,7 @@ impl ShowType for StructType {
 
 impl ShowType for LambdaType {
     fn show(&self) -> String {
-        "Lambda( env:".to_string()
-            + &self.env_ty.clone().map(|x| x.show()).unwrap_or("void".to_string())
-            + ","
-            + "func:" + &self.func_ty.show() + ")"
+        "(".to_string() + &self.func_ty.show() + ")"
     }
 }
 
@@ -85,4 +93,100 @@ impl ShowType for ImplItems {
             acc + &format!(" name {}::{:?},", name, ty)
         }) + "}"
     }
-}
\ No newline at end of file
+}
+impl ShowType for Condition {
+    fn show(&self) -> String {
+        match self {
+            Condition::Call(x) => "(".to_string() + &x.show() + ")",
+            Condition::Items(x) => x.show(),
+            Condition::Empty => "_".to_string(),
+        }
+    }
+}
+
+impl ShowType for Pred {
+    fn show(&self) -> String {
+        self.ty.show() + ": " + &self.cond.show()
+    }
+}
+
+impl ShowType for Preds {
+    fn show(&self) -> String {
+        let mut ps = self.0.values().map(|p| p.show()).collect::<Vec<_>>();
+        ps.sort();
+        ps.join(", ")
+    }
+}
+
+impl ShowType for Qual<Type> {
+    fn show(&self) -> String {
+        let mut shown = vec![];
+        let t = match self.t {
+            Type::LambdaType(ref x) => show_func_with_preds(&x.func_ty, &self.ps, &mut shown),
+            ref t => show_with_preds(t, &self.ps, &mut shown)
+        };
+        let mut rest = self.ps.0.iter()
+            .filter(|(ty, _)| !shown.contains(ty))
+            .map(|(_, p)| p.show())
+            .collect::<Vec<_>>();
+        if rest.is_empty() {
+            t
+        } else {
+            rest.sort();
+            t + " where " + &rest.join(", ")
+        }
+    }
+}
+
+//制約のかかった型変数を制約の形で表示する
+//フィールドの制約は{x:a|r}、呼び出しの制約は(a->b)になる
+fn show_with_preds(ty: &Type, ps: &Preds, shown: &mut Vec<Type>) -> String {
+    match ty {
+        Type::TyVar(_) | Type::TGen(_, _) if !shown.contains(ty) => {
+            match ps.get(ty).map(|p| &p.cond) {
+                Some(Condition::Items(x)) => {
+                    shown.push(ty.clone());
+                    let mut index_properties = x.get_index_properties().collect::<Vec<_>>();
+                    index_properties.sort_by_key(|(index, _)| **index);
+                    let mut name_properties = x.get_name_properties().collect::<Vec<_>>();
+                    name_properties.sort_by(|(name1, _), (name2, _)| name1.cmp(name2));
+                    let fields = index_properties.into_iter()
+                        .map(|(index, ty)| (index.to_string(), ty))
+                        .chain(name_properties.into_iter().map(|(name, ty)| (name.clone(), ty)))
+                        .map(|(name, ty)| name + ":" + &show_with_preds(ty, ps, shown))
+                        .collect::<Vec<_>>();
+                    "{".to_string() + &fields.join(",") + "|" + &ty.show() + "}"
+                }
+                Some(Condition::Call(x)) => {
+                    shown.push(ty.clone());
+                    "(".to_string() + &show_func_with_preds(x, ps, shown) + ")"
+                }
+                _ => ty.show()
+            }
+        }
+        Type::TupleType(x) => {
+            "(".to_string()
+                + &x.element_tys
+                .iter()
+                .map(|x| show_with_preds(x, ps, shown))
+                .collect::<Vec<_>>()
+                .join(",")
+                + ")"
+        }
+        Type::LambdaType(x) => "(".to_string() + &show_func_with_preds(&x.func_ty, ps, shown) + ")",
+        ty => ty.show()
+    }
+}
+
+fn show_func_with_preds(ty: &FuncType, ps: &Preds, shown: &mut Vec<Type>) -> String {
+    ty.param_types
+        .iter()
+        .fold("".to_string(), |acc, x| acc + &show_with_preds(x, ps, shown) + "->")
+        + &show_with_preds(&ty.ret_type, ps, shown)
+}
+
+impl ShowType for Scheme {
+    fn show(&self) -> String {
+        self.get_qual().show()
+    }
+}
@@ -53,13 +53,6 @@ impl Instantiate for Type {
             }
             LambdaType(lambda_ty) => {
                 let mut lambda_ty = *lambda_ty;
-                lambda_ty.env_ty = match lambda_ty.env_ty {
-                    Some(mut tuple_ty) => {
-                        tuple_ty.element_tys = tuple_ty.element_tys.inst(fresh_types);
-                        Some(tuple_ty)
-                    }
-                    None => None
-                };
                 lambda_ty.func_ty.param_types = lambda_ty.func_ty.param_types.inst(fresh_types);
                 lambda_ty.func_ty.ret_type = lambda_ty.func_ty.ret_type.inst(fresh_types);
                 LambdaType(Box::new(lambda_ty))
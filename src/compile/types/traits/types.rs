@@ -66,14 +66,7 @@ impl Types for Type {
                     StructInternalType::RecordType(record_ty) => record_ty.element_tys.tv_list()
                 }
             }
-            LambdaType(lambda_ty) => {
-                let mut tv = match &lambda_ty.env_ty {
-                    Some(tuple_ty) => tuple_ty.element_tys.tv_list(),
-                    None => HashSet::new()
-                };
-                tv.extend(lambda_ty.func_ty.tv_list());
-                tv
-            }
+            LambdaType(lambda_ty) => lambda_ty.func_ty.tv_list(),
         }
     }
 
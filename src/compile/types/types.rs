@@ -29,23 +29,35 @@ impl Type {
         Type::TCon { name: "Bool".to_string() }
     }
     pub fn create_func_type(param_types: Vec<Type>, ret_type: Type) -> Type {
-        Type::LambdaType(Box::new(LambdaType { env_ty: None, func_ty: FuncType { param_types, ret_type } }))
+        Type::LambdaType(Box::new(LambdaType { func_ty: FuncType { param_types, ret_type } }))
     }
     pub fn create_func_type2(func_ty: FuncType) -> Type {
-        Type::LambdaType(Box::new(LambdaType { env_ty: None, func_ty }))
+        Type::LambdaType(Box::new(LambdaType { func_ty }))
     }
     pub fn create_tuple_type(element_tys: Vec<Type>) -> Type {
         Type::TupleType(Box::new(TupleType { element_tys }))
     }
-    pub fn create_lambda_type(env_tys: Vec<Type>, func_ty: FuncType) -> Type {
-        Type::LambdaType(Box::new(LambdaType { env_ty: Some(TupleType { element_tys: env_tys }), func_ty }))
-    }
     pub fn get_lambda_ty(&self) -> &LambdaType {
         match self {
             Type::LambdaType(ty) => ty,
             _ => panic!("not LabdaType")
         }
     }
+    //カリー化された関数の型を、先頭からarity個の引数の型と戻り値の型に分ける
+    pub fn split_curried(&self, arity: usize) -> Option<(Vec<Type>, Type)> {
+        let mut ty = self.clone();
+        let mut param_types = vec![];
+        for _ in 0..arity {
+            ty = match ty {
+                Type::LambdaType(ref x) if x.func_ty.param_types.len() == 1 => {
+                    param_types.push(x.func_ty.param_types[0].clone());
+                    x.func_ty.ret_type.clone()
+                }
+                _ => return None
+            };
+        }
+        Some((param_types, ty))
+    }
     pub fn get_tuple_ty(&self) -> &TupleType {
         match self {
             Type::TupleType(ty) => ty,
@@ -145,8 +157,8 @@ impl TupleTypeBase for RecordType {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+//関数の値の型。キャプチャした環境は型に含めず、どの関数の値も同じ表現になる
 pub struct LambdaType {
-    pub env_ty: Option<TupleType>,
     pub func_ty: FuncType,
 }
 
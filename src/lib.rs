@@ -3,6 +3,11 @@
 extern crate combine;
 extern crate indexmap;
 
+#[macro_use]
+mod macros;
+
 pub mod cmd_args;
 pub mod compile;
 pub mod hello;
+pub mod lsp;
+pub mod repl;
@@ -0,0 +1,98 @@
+// LLVM 6のC APIはコンパイルユニットとファイルしか作れないので、
+// 関数や変数のデバッグ情報はC++のDIBuilderを直接使って作る
+#include "llvm-c/Core.h"
+#include "llvm/IR/DIBuilder.h"
+#include "llvm/IR/DebugInfoMetadata.h"
+#include "llvm/IR/Function.h"
+#include "llvm/IR/IRBuilder.h"
+#include "llvm/IR/Instructions.h"
+#include "llvm/IR/Module.h"
+
+using namespace llvm;
+
+struct RuscallDIBuilder {
+    DIBuilder builder;
+    DIFile *file;
+
+    RuscallDIBuilder(Module &module) : builder(module), file(nullptr) {}
+};
+
+extern "C" {
+
+// functionの属するモジュールにコンパイルユニットを作る
+RuscallDIBuilder *ruscall_di_builder_create(LLVMValueRef function, const char *file_name, const char *directory, bool optimized) {
+    Module *module = unwrap<Function>(function)->getParent();
+    module->addModuleFlag(Module::Warning, "Debug Info Version", DEBUG_METADATA_VERSION);
+    module->addModuleFlag(Module::Warning, "Dwarf Version", 4);
+    RuscallDIBuilder *di = new RuscallDIBuilder(*module);
+    di->file = di->builder.createFile(file_name, directory);
+    // gdbやlldbで整数や構造体をそのまま表示できるようにCとして記述する
+    di->builder.createCompileUnit(dwarf::DW_LANG_C, di->file, "ruscall", optimized, "", 0);
+    return di;
+}
+
+void ruscall_di_builder_finalize(RuscallDIBuilder *di) {
+    di->builder.finalize();
+    delete di;
+}
+
+DIType *ruscall_di_basic_type(RuscallDIBuilder *di, const char *name, uint64_t size_in_bits, unsigned encoding) {
+    return di->builder.createBasicType(name, size_in_bits, encoding);
+}
+
+DIType *ruscall_di_pointer_type(RuscallDIBuilder *di, const char *name, uint64_t size_in_bits) {
+    return di->builder.createPointerType(nullptr, size_in_bits, 0, None, name);
+}
+
+// パディングのない構造体。メンバーは宣言順に詰めて並ぶ
+DIType *ruscall_di_struct_type(RuscallDIBuilder *di, const char *name, uint64_t size_in_bits,
+                               const char **member_names, DIType **member_types, const uint64_t *member_sizes, unsigned member_count) {
+    SmallVector<Metadata *, 8> members;
+    uint64_t offset = 0;
+    for (unsigned i = 0; i < member_count; i++) {
+        members.push_back(di->builder.createMemberType(di->file, member_names[i], di->file, 0, member_sizes[i], 8, offset,
+                                                       DINode::FlagZero, member_types[i]));
+        offset += member_sizes[i];
+    }
+    return di->builder.createStructType(di->file, name, di->file, 0, size_in_bits, 8, DINode::FlagZero, nullptr,
+                                        di->builder.getOrCreateArray(members));
+}
+
+// 関数のサブプログラムを作ってfunctionに付ける
+DISubprogram *ruscall_di_create_function(RuscallDIBuilder *di, LLVMValueRef function, const char *name, unsigned line,
+                                         DIType **types, unsigned type_count, bool optimized) {
+    Function *func = unwrap<Function>(function);
+    SmallVector<Metadata *, 8> elements(types, types + type_count);
+    DISubroutineType *ty = di->builder.createSubroutineType(di->builder.getOrCreateTypeArray(elements));
+    DISubprogram *subprogram = di->builder.createFunction(
+        di->file, name, func->getName(), di->file, line, ty, func->hasLocalLinkage(), true, line,
+        DINode::FlagPrototyped, optimized);
+    func->setSubprogram(subprogram);
+    return subprogram;
+}
+
+// 引数をスタックに置いてprintできるようにする
+// arg_indexはLLVMの関数での位置、arg_noはソース上の何番目の引数か(1から)
+void ruscall_di_declare_parameter(RuscallDIBuilder *di, DISubprogram *scope, LLVMValueRef function, unsigned arg_index,
+                                  unsigned arg_no, const char *name, DIType *ty, unsigned line) {
+    Function *func = unwrap<Function>(function);
+    Argument *arg = func->arg_begin() + arg_index;
+    BasicBlock &entry = func->getEntryBlock();
+    IRBuilder<> builder(&entry, entry.getFirstInsertionPt());
+    DILocation *loc = DILocation::get(func->getContext(), line, 0, scope);
+    builder.SetCurrentDebugLocation(loc);
+    AllocaInst *slot = builder.CreateAlloca(arg->getType(), nullptr, name);
+    StoreInst *store = builder.CreateStore(arg, slot);
+    DILocalVariable *var = di->builder.createParameterVariable(scope, name, arg_no, di->file, line, ty, true);
+    di->builder.insertDeclare(slot, var, di->builder.createExpression(), loc, store->getNextNode());
+}
+
+// 位置がまだ付いていない命令にだけ位置を付ける
+void ruscall_di_set_location(LLVMValueRef instruction, DISubprogram *scope, unsigned line, unsigned column) {
+    Instruction *inst = unwrap<Instruction>(instruction);
+    if (!inst->getDebugLoc()) {
+        inst->setDebugLoc(DILocation::get(inst->getContext(), line, column, scope));
+    }
+}
+
+}
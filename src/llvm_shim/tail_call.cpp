@@ -0,0 +1,33 @@
+// LLVM 6のC APIは末尾呼び出しの印をtailかどうかでしか付けられないので、musttailはC++で付ける
+#include "llvm-c/Core.h"
+#include "llvm/IR/Function.h"
+#include "llvm/IR/Instructions.h"
+
+using namespace llvm;
+
+extern "C" {
+
+// 呼び出し元と同じ型と呼び出し規約の関数の呼び出しはmusttailにして、スタックを使わないことを保証する
+// 型の違う関数の呼び出しはtailにして、できる時だけ最適化に任せる
+void ruscall_set_tail_call(LLVMValueRef call) {
+    CallInst *inst = unwrap<CallInst>(call);
+    Function *caller = inst->getFunction();
+    bool must_tail = inst->getFunctionType() == caller->getFunctionType()
+        && inst->getCallingConv() == caller->getCallingConv();
+    inst->setTailCallKind(must_tail ? CallInst::TCK_MustTail : CallInst::TCK_Tail);
+}
+
+// musttailに対応していないターゲットのためにtailに戻す
+void ruscall_relax_must_tail_calls(LLVMValueRef function) {
+    for (BasicBlock &block : *unwrap<Function>(function)) {
+        for (Instruction &inst : block) {
+            if (CallInst *call = dyn_cast<CallInst>(&inst)) {
+                if (call->isMustTailCall()) {
+                    call->setTailCallKind(CallInst::TCK_Tail);
+                }
+            }
+        }
+    }
+}
+
+}
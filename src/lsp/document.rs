@@ -0,0 +1,407 @@
+use super::super::compile::doc::show_type_ast;
+use super::super::compile::error::Error;
+use super::super::compile::ir::ast::*;
+use super::super::compile::ir::mir::*;
+use super::super::compile::parser;
+use super::super::compile::semantic_analysis;
+use super::super::compile::semantic_analysis::type_env::TypeInfo;
+use super::super::compile::types::*;
+use super::json::{object, Json};
+use combine::stream::state::SourcePosition;
+use std::panic::{self, AssertUnwindSafe};
+
+//CompletionItemKind
+const FUNCTION_KIND: usize = 3;
+const FIELD_KIND: usize = 5;
+const STRUCT_KIND: usize = 22;
+
+//DiagnosticSeverity
+const ERROR_SEVERITY: usize = 1;
+const WARNING_SEVERITY: usize = 2;
+
+struct Diagnostic {
+    pos: SourcePosition,
+    msg: String,
+    severity: usize,
+}
+
+impl Diagnostic {
+    fn new(err: &Error, severity: usize) -> Diagnostic {
+        Diagnostic { pos: err.pos(), msg: err.msg().trim().to_string(), severity }
+    }
+}
+
+//定義へのジャンプ先
+struct Definition {
+    name: String,
+    pos: SourcePosition,
+    is_struct: bool,
+}
+
+//ホバーで表示する式の型
+struct HoverEntry {
+    pos: SourcePosition,
+    //グローバル変数とフィールドの名前。ローカル変数はNone
+    name: Option<String>,
+    ty: String,
+}
+
+//最後に解析できた時の情報
+//構文エラーの間も補完やジャンプができるように、解析に失敗しても残しておく
+struct Symbols {
+    definitions: Vec<Definition>,
+    //(構造体名, フィールド名, 型)
+    fields: Vec<(String, String, String)>,
+    //トップレベルの名前と型スキーム
+    schemes: Vec<(String, String)>,
+    hover_list: Vec<HoverEntry>,
+}
+
+//エディタで開かれているファイル
+pub struct Document {
+    text: String,
+    //レイアウト規則の拡張子か
+    layout: bool,
+    diagnostics: Vec<Diagnostic>,
+    symbols: Symbols,
+}
+
+impl Document {
+    pub fn new(uri: &str, text: String) -> Document {
+        let mut document = Document {
+            text,
+            layout: uri.ends_with(parser::LAYOUT_EXTENSION),
+            diagnostics: vec![],
+            symbols: Symbols { definitions: vec![], fields: vec![], schemes: vec![], hover_list: vec![] },
+        };
+        document.analyze();
+        document
+    }
+
+    pub fn update(&mut self, text: String) {
+        self.text = text;
+        self.analyze();
+    }
+
+    fn analyze(&mut self) {
+        let src = if self.layout && !parser::has_layout_pragma(&self.text) {
+            parser::layout(&self.text)
+        } else {
+            parser::resolve_layout_pragma(&self.text)
+        };
+        let ast = match parser::parse(&src) {
+            Ok((ast, _)) => ast,
+            Err(err) => {
+                self.diagnostics = vec![Diagnostic::new(&Error::from_parse_error(err), ERROR_SEVERITY)];
+                return;
+            }
+        };
+        self.symbols.definitions = definitions(&ast);
+        self.symbols.fields = fields(&ast);
+        //型検査の途中でpanicしてもサーバーは止めない
+        match panic::catch_unwind(AssertUnwindSafe(|| semantic_analysis::analysis_lib(ast))) {
+            Ok(Ok((program_mir, assump))) => {
+                self.diagnostics = program_mir.warning_list
+                    .iter()
+                    .map(|x| Diagnostic::new(x, WARNING_SEVERITY))
+                    .collect();
+                self.symbols.schemes = assump.global_list()
+                    .into_iter()
+                    .map(|(name, scheme)| (name.clone(), scheme.show()))
+                    .collect();
+                self.symbols.hover_list = hover_list(&program_mir);
+            }
+            Ok(Err(err)) => {
+                self.diagnostics = vec![Diagnostic::new(&err, ERROR_SEVERITY)];
+                self.symbols.hover_list = vec![];
+            }
+            Err(_) => {
+                let err = Error::new(SourcePosition::new(), "internal compiler error");
+                self.diagnostics = vec![Diagnostic::new(&err, ERROR_SEVERITY)];
+                self.symbols.hover_list = vec![];
+            }
+        }
+    }
+
+    //Diagnostic[]
+    pub fn diagnostics(&self) -> Json {
+        self.diagnostics
+            .iter()
+            .map(|x| {
+                let end = word_at(&self.text, x.pos)
+                    .filter(|(start, _)| *start == x.pos)
+                    .map(|(start, word)| SourcePosition { column: start.column + word.chars().count() as i32, ..start })
+                    .unwrap_or(SourcePosition { column: x.pos.column + 1, ..x.pos });
+                object(vec![
+                    ("range", range(&self.text, x.pos, end)),
+                    ("severity", x.severity.into()),
+                    ("source", "ruscall".into()),
+                    ("message", x.msg.clone().into()),
+                ])
+            })
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    //Hover | null
+    pub fn hover(&self, position: &Json) -> Json {
+        let (start, word) = match word_at(&self.text, from_lsp_pos(&self.text, position)) {
+            Some(x) => x,
+            None => return Json::Null,
+        };
+        let suffix = "#".to_string() + &word;
+        let ty = self.symbols.hover_list
+            .iter()
+            .find(|x| x.pos == start && x.name.as_ref().map(|name| name == &word || name.ends_with(&suffix)).unwrap_or(true))
+            .map(|x| x.ty.clone())
+            .or_else(|| self.scheme(&word))
+            .or_else(|| self.scheme_ends_with(&suffix))
+            .or_else(|| if after_dot(&self.text, start) {
+                self.symbols.fields.iter().find(|(_, name, _)| name == &word).map(|(_, _, ty)| ty.clone())
+            } else {
+                None
+            });
+        match ty {
+            Some(ty) => object(vec![
+                (
+                    "contents",
+                    object(vec![
+                        ("kind", "markdown".into()),
+                        ("value", format!("```\n{} :: {}\n```", word, ty).into()),
+                    ]),
+                ),
+                ("range", range(&self.text, start, SourcePosition { column: start.column + word.chars().count() as i32, ..start })),
+            ]),
+            None => Json::Null,
+        }
+    }
+
+    //Location | null
+    pub fn definition(&self, uri: &str, position: &Json) -> Json {
+        let word = match word_at(&self.text, from_lsp_pos(&self.text, position)) {
+            Some((_, word)) => word,
+            None => return Json::Null,
+        };
+        match self.symbols.definitions.iter().find(|x| x.name == word) {
+            Some(x) => {
+                //構造体の位置はstructキーワードなので名前を探す
+                let start = if x.is_struct { find_name(&self.text, x.pos, &x.name) } else { x.pos };
+                let end = SourcePosition { column: start.column + x.name.chars().count() as i32, ..start };
+                object(vec![("uri", uri.into()), ("range", range(&self.text, start, end))])
+            }
+            None => Json::Null,
+        }
+    }
+
+    //CompletionItem[]
+    pub fn completion(&self, position: &Json) -> Json {
+        let pos = from_lsp_pos(&self.text, position);
+        //入力途中の識別子の前が.ならフィールドを補完する
+        let line = line_chars(&self.text, pos.line);
+        let mut idx = ((pos.column - 1).max(0) as usize).min(line.len());
+        while idx > 0 && is_id_char(line[idx - 1]) {
+            idx -= 1;
+        }
+        if idx > 0 && line[idx - 1] == '.' {
+            return self.symbols.fields
+                .iter()
+                .map(|(struct_name, name, ty)| completion_item(name, FIELD_KIND, &format!("{}.{}: {}", struct_name, name, ty)))
+                .collect::<Vec<_>>()
+                .into();
+        }
+        let structs = self.symbols.definitions.iter().filter(|x| x.is_struct).map(|x| &x.name).collect::<Vec<_>>();
+        let mut items = structs
+            .iter()
+            .map(|name| completion_item(name, STRUCT_KIND, &("struct ".to_string() + name.as_str())))
+            .collect::<Vec<_>>();
+        //構造体のコンストラクタは構造体として出す
+        items.extend(self.symbols.schemes
+            .iter()
+            .filter(|(name, _)| !structs.contains(&name))
+            .map(|(name, scheme)| completion_item(name, FUNCTION_KIND, scheme)));
+        //型検査に失敗していると型スキームがないので名前だけ出す
+        items.extend(self.symbols.definitions
+            .iter()
+            .filter(|x| !x.is_struct && self.scheme(&x.name).is_none())
+            .map(|x| completion_item(&x.name, FUNCTION_KIND, "")));
+        items.into()
+    }
+
+    fn scheme(&self, name: &str) -> Option<String> {
+        self.symbols.schemes.iter().find(|(x, _)| x == name).map(|(_, scheme)| scheme.clone())
+    }
+
+    //whereの局所関数はf#gのような名前になっている
+    fn scheme_ends_with(&self, suffix: &str) -> Option<String> {
+        self.symbols.schemes.iter().find(|(x, _)| x.ends_with(suffix)).map(|(_, scheme)| scheme.clone())
+    }
+}
+
+fn completion_item(label: &str, kind: usize, detail: &str) -> Json {
+    object(vec![("label", label.into()), ("kind", kind.into()), ("detail", detail.into())])
+}
+
+//関数の定義を優先して、extern宣言だけの関数は宣言に飛ぶ
+fn definitions(ast: &ProgramAST) -> Vec<Definition> {
+    let mut list: Vec<Definition> = vec![];
+    for stmt in ast.stmt_list.iter() {
+        let (name, pos, is_struct) = match stmt {
+            StmtAST::DefFuncAST(x) => (&x.name, x.pos, false),
+            StmtAST::DecStructAST(x) => (&x.ty.name, x.pos, true),
+            _ => continue,
+        };
+        if list.iter().all(|x| &x.name != name) {
+            list.push(Definition { name: name.clone(), pos, is_struct });
+        }
+    }
+    for stmt in ast.stmt_list.iter() {
+        if let StmtAST::DecFuncAST(x) = stmt {
+            if list.iter().all(|y| y.name != x.name) {
+                list.push(Definition { name: x.name.clone(), pos: x.pos, is_struct: false });
+            }
+        }
+    }
+    list
+}
+
+fn fields(ast: &ProgramAST) -> Vec<(String, String, String)> {
+    ast.stmt_list
+        .iter()
+        .filter_map(|stmt| match stmt {
+            StmtAST::DecStructAST(x) => Some(x),
+            _ => None,
+        })
+        .flat_map(|x| match x.ty.ty {
+            StructInternalTypeAST::RecordTypeAST(ref record) => record.elements_ty
+                .iter()
+                .map(|(name, ty)| (x.ty.name.clone(), name.clone(), show_type_ast(ty)))
+                .collect::<Vec<_>>(),
+            StructInternalTypeAST::TupleTypeAST(_) => vec![],
+        })
+        .collect()
+}
+
+//全ての関数の本体から、変数とフィールドの位置と推論された型を集める
+fn hover_list(program_mir: &ProgramMir) -> Vec<HoverEntry> {
+    let mut list = vec![];
+    let funcs = program_mir.implicit_func_list
+        .values()
+        .map(|x| &x.func)
+        .chain(program_mir.explicit_func_list.iter().map(|x| &x.func));
+    for func in funcs {
+        collect_hover(&func.body, &program_mir.ty_info, &mut list);
+    }
+    list
+}
+
+fn collect_hover(expr: &ExprMir, ty_info: &TypeInfo, list: &mut Vec<HoverEntry>) {
+    match expr {
+        ExprMir::OpMir(x) => {
+            collect_hover(&x.l_expr, ty_info, list);
+            collect_hover(&x.r_expr, ty_info, list);
+        }
+        ExprMir::IfMir(x) => {
+            collect_hover(&x.cond, ty_info, list);
+            collect_hover(&x.t_expr, ty_info, list);
+            collect_hover(&x.f_expr, ty_info, list);
+        }
+        ExprMir::TupleMir(x) => x.elements.iter().for_each(|x| collect_hover(x, ty_info, list)),
+        ExprMir::TupleStructMir(x) => x.tuple.elements.iter().for_each(|x| collect_hover(x, ty_info, list)),
+        ExprMir::CallMir(x) => {
+            collect_hover(&x.func, ty_info, list);
+            x.params.iter().for_each(|x| collect_hover(x, ty_info, list));
+        }
+        ExprMir::IndexPropertyMir(x) => collect_hover(&x.expr, ty_info, list),
+        ExprMir::NamePropertyMir(x) => {
+            collect_hover(&x.expr, ty_info, list);
+            list.push(HoverEntry { pos: x.pos, name: Some(x.property_name.clone()), ty: show_ty(ty_info, &x.ty_id) });
+        }
+        //パターンの形を調べる式は引数の位置を指すので本体だけを見る
+        ExprMir::PatternCheckMir(x) => collect_hover(&x.body, ty_info, list),
+        ExprMir::RecordUpdateMir(x) => {
+            collect_hover(&x.expr, ty_info, list);
+            x.params.iter().for_each(|(_, x)| collect_hover(x, ty_info, list));
+        }
+        ExprMir::VariableMir(x) => list.push(HoverEntry { pos: x.pos, name: None, ty: show_ty(ty_info, &x.ty_id) }),
+        ExprMir::GlobalVariableMir(x) => {
+            list.push(HoverEntry { pos: x.pos, name: Some(x.id.clone()), ty: show_ty(ty_info, &x.ty_id) })
+        }
+        //ラムダ式の本体は別の関数になっている
+        ExprMir::LambdaMir(_) | ExprMir::NumMir(_) | ExprMir::BoolMir(_) | ExprMir::PanicMir(_) => (),
+    }
+}
+
+//型変数はa, b..の名前で表示する
+fn show_ty(ty_info: &TypeInfo, ty_id: &TypeId) -> String {
+    let ty = ty_info.look_up(ty_id);
+    Scheme::quantify(ty.tv_list(), Qual::new(ty)).show()
+}
+
+fn is_id_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn line_chars(text: &str, line: i32) -> Vec<char> {
+    text.lines().nth((line - 1).max(0) as usize).unwrap_or("").chars().collect()
+}
+
+//LSPの位置(0始まりの行とUTF-16の列)をソース上の位置(1始まりの行と文字の列)にする
+fn from_lsp_pos(text: &str, position: &Json) -> SourcePosition {
+    let line = position.get("line").as_usize().unwrap_or(0);
+    let character = position.get("character").as_usize().unwrap_or(0);
+    let mut units = 0;
+    let mut column = 1;
+    for c in text.lines().nth(line).unwrap_or("").chars() {
+        if units >= character {
+            break;
+        }
+        units += c.len_utf16();
+        column += 1;
+    }
+    SourcePosition { line: line as i32 + 1, column }
+}
+
+fn to_lsp_pos(text: &str, pos: SourcePosition) -> Json {
+    let character: usize = line_chars(text, pos.line)
+        .iter()
+        .take((pos.column - 1).max(0) as usize)
+        .map(|c| c.len_utf16())
+        .sum();
+    object(vec![("line", ((pos.line - 1).max(0) as usize).into()), ("character", character.into())])
+}
+
+fn range(text: &str, start: SourcePosition, end: SourcePosition) -> Json {
+    object(vec![("start", to_lsp_pos(text, start)), ("end", to_lsp_pos(text, end))])
+}
+
+//カーソルの下の識別子とその始まりの位置。識別子の直後にカーソルがある時もその識別子にする
+fn word_at(text: &str, pos: SourcePosition) -> Option<(SourcePosition, String)> {
+    let line = line_chars(text, pos.line);
+    let mut idx = (pos.column - 1).max(0) as usize;
+    if idx >= line.len() || !is_id_char(line[idx]) {
+        if idx == 0 || idx > line.len() || !is_id_char(line[idx - 1]) {
+            return None;
+        }
+        idx -= 1;
+    }
+    let start = (0..idx + 1).rev().take_while(|x| is_id_char(line[*x])).last().unwrap();
+    let end = (idx..line.len()).take_while(|x| is_id_char(line[*x])).last().unwrap() + 1;
+    let word = line[start..end].iter().collect();
+    Some((SourcePosition { line: pos.line, column: start as i32 + 1 }, word))
+}
+
+fn after_dot(text: &str, start: SourcePosition) -> bool {
+    let idx = (start.column - 1).max(0) as usize;
+    idx > 0 && line_chars(text, start.line).get(idx - 1) == Some(&'.')
+}
+
+//posから後ろで最初に出てくるnameの位置
+fn find_name(text: &str, pos: SourcePosition, name: &str) -> SourcePosition {
+    let line = line_chars(text, pos.line);
+    let name = name.chars().collect::<Vec<_>>();
+    let idx = (pos.column - 1).max(0) as usize;
+    (idx..line.len())
+        .find(|x| line[*x..].starts_with(&name))
+        .map(|x| SourcePosition { column: x as i32 + 1, ..pos })
+        .unwrap_or(pos)
+}
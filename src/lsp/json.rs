@@ -0,0 +1,236 @@
+use combine::char::{char, digit, spaces, string};
+use combine::parser::item::{any, none_of, satisfy};
+use combine::{count, eof, many, many1, optional, sep_by, Parser};
+use std::fmt;
+
+//LSPのメッセージに使うJSON
+//オブジェクトはキーの順番を保つ
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+static NULL: Json = Json::Null;
+
+impl Json {
+    //オブジェクトのメンバー。なければNull
+    pub fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v).unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+    pub fn at(&self, idx: usize) -> &Json {
+        match self {
+            Json::Array(elements) => elements.get(idx).unwrap_or(&NULL),
+            _ => &NULL,
+        }
+    }
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(x) => Some(x),
+            _ => None,
+        }
+    }
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(x) if *x >= 0.0 => Some(*x as usize),
+            _ => None,
+        }
+    }
+    pub fn as_array(&self) -> &[Json] {
+        match self {
+            Json::Array(elements) => elements,
+            _ => &[],
+        }
+    }
+    pub fn is_null(&self) -> bool {
+        *self == Json::Null
+    }
+}
+
+//{"key": value, ...}を作る
+pub fn object(members: Vec<(&str, Json)>) -> Json {
+    Json::Object(members.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+impl<'a> From<&'a str> for Json {
+    fn from(x: &'a str) -> Json {
+        Json::String(x.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(x: String) -> Json {
+        Json::String(x)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(x: bool) -> Json {
+        Json::Bool(x)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(x: usize) -> Json {
+        Json::Number(x as f64)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(x: Vec<Json>) -> Json {
+        Json::Array(x)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(x) => write!(f, "{}", x),
+            //整数はそのまま整数で書く
+            Json::Number(x) if x.fract() == 0.0 && x.abs() < 1e15 => write!(f, "{}", *x as i64),
+            Json::Number(x) => write!(f, "{}", x),
+            Json::String(x) => write_str(f, x),
+            Json::Array(elements) => {
+                write!(f, "[")?;
+                for (idx, x) in elements.iter().enumerate() {
+                    if idx != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", x)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (idx, (k, v)) in members.iter().enumerate() {
+                    if idx != 0 {
+                        write!(f, ",")?;
+                    }
+                    write_str(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_str(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+pub fn parse(s: &str) -> Result<Json, String> {
+    spaces()
+        .with(value_parser())
+        .skip(eof())
+        .parse(s)
+        .map(|(x, _)| x)
+        .map_err(|err| format!("{:?}", err))
+}
+
+//<value>
+parser! {
+    fn value_parser['a]()(&'a str) -> Json
+    {
+        string("null").map(|_|Json::Null)
+        .or(string("true").map(|_|Json::Bool(true)))
+        .or(string("false").map(|_|Json::Bool(false)))
+        .or(number_parser().map(Json::Number))
+        .or(string_parser().map(Json::String))
+        .or(
+            char('[').skip(spaces())
+            .with(sep_by(value_parser(), char(',').skip(spaces())))
+            .skip(char(']'))
+            .map(Json::Array)
+        )
+        .or(
+            char('{').skip(spaces())
+            .with(sep_by(
+                (string_parser().skip(spaces()).skip(char(':')).skip(spaces()), value_parser()),
+                char(',').skip(spaces())
+            ))
+            .skip(char('}'))
+            .map(Json::Object)
+        )
+        .skip(spaces())
+    }
+}
+
+//<number>
+parser! {
+    fn number_parser['a]()(&'a str) -> f64
+    {
+        (
+            optional(char('-')),
+            many1::<String,_>(digit()),
+            optional(char('.').with(many1::<String,_>(digit()))),
+            optional((
+                char('e').or(char('E')),
+                optional(char('+').or(char('-'))),
+                many1::<String,_>(digit())
+            ))
+        ).map(|(sign,int,frac,exp)|{
+            let mut s = sign.map(|_|"-".to_string()).unwrap_or_default() + &int;
+            if let Some(frac) = frac {
+                s = s + "." + &frac;
+            }
+            if let Some((_,exp_sign,exp)) = exp {
+                s = s + "e" + &exp_sign.map(|x:char|x.to_string()).unwrap_or_default() + &exp;
+            }
+            s.parse().unwrap()
+        })
+    }
+}
+
+//<string>
+//\uXXXXはUTF-16の符号単位なので、まとめてからStringにする
+parser! {
+    fn string_parser['a]()(&'a str) -> String
+    {
+        char('"')
+        .with(many::<Vec<Vec<u16>>,_>(
+            char('\\').with(
+                char('u').with(count::<String,_>(4,satisfy(|c:char|c.is_digit(16))))
+                .map(|x|vec![u16::from_str_radix(&x,16).unwrap()])
+                .or(any().map(|c:char|{
+                    let c = match c {
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        c => c,
+                    };
+                    let mut buf = [0;2];
+                    c.encode_utf16(&mut buf).to_vec()
+                }))
+            )
+            .or(none_of("\"\\".chars()).map(|c:char|{
+                let mut buf = [0;2];
+                c.encode_utf16(&mut buf).to_vec()
+            }))
+        ))
+        .skip(char('"'))
+        .map(|units|String::from_utf16_lossy(&units.concat()))
+    }
+}
@@ -0,0 +1,190 @@
+//標準入出力でやり取りするLanguage Server
+pub mod json;
+mod document;
+
+use self::document::Document;
+use self::json::{object, Json};
+use super::compile;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+//JSON-RPCのエラーコード
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+
+pub fn run() -> i32 {
+    //標準出力はプロトコルに使うのでデバッグ出力を止める
+    compile::set_verbose(false);
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    match serve(&mut stdin.lock(), &mut stdout.lock()) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+//exitを受け取るまでメッセージを処理し、終了コードを返す
+pub fn serve<R: BufRead, W: Write>(input: &mut R, output: &mut W) -> io::Result<i32> {
+    let mut server = Server::new();
+    while let Some(msg) = read_message(input)? {
+        let responses = match json::parse(&msg) {
+            Ok(msg) => server.handle(&msg),
+            Err(err) => vec![error_response(Json::Null, PARSE_ERROR, &err)],
+        };
+        for x in responses {
+            write_message(output, &x)?;
+        }
+        if let Some(code) = server.exit_code {
+            return Ok(code);
+        }
+    }
+    //exitなしで入力が終わった
+    Ok(1)
+}
+
+//Content-Lengthヘッダーで区切られたメッセージを読む
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let mut header = line.splitn(2, ':');
+        if header.next().map(|x| x.eq_ignore_ascii_case("Content-Length")).unwrap_or(false) {
+            length = header.next().and_then(|x| x.trim().parse::<usize>().ok());
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header"))?;
+    let mut buf = vec![0; length];
+    input.read_exact(&mut buf)?;
+    Ok(Some(String::from_utf8_lossy(&buf).into_owned()))
+}
+
+fn write_message<W: Write>(output: &mut W, msg: &Json) -> io::Result<()> {
+    let body = msg.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+fn response(id: Json, result: Json) -> Json {
+    object(vec![("jsonrpc", "2.0".into()), ("id", id), ("result", result)])
+}
+
+fn error_response(id: Json, code: i32, msg: &str) -> Json {
+    object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("id", id),
+        ("error", object(vec![("code", Json::Number(code as f64)), ("message", msg.into())])),
+    ])
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    object(vec![("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)])
+}
+
+struct Server {
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+    exit_code: Option<i32>,
+}
+
+impl Server {
+    fn new() -> Server {
+        Server { documents: HashMap::new(), shutdown: false, exit_code: None }
+    }
+
+    //1つのメッセージを処理して、返すレスポンスと通知を返す
+    fn handle(&mut self, msg: &Json) -> Vec<Json> {
+        let id = msg.get("id").clone();
+        let params = msg.get("params");
+        let method = match msg.get("method").as_str() {
+            Some(method) => method,
+            //クライアントからのレスポンスは使わない
+            None => return vec![],
+        };
+        if method == "exit" {
+            self.exit_code = Some(if self.shutdown { 0 } else { 1 });
+            return vec![];
+        }
+        if self.shutdown {
+            return if id.is_null() { vec![] } else { vec![error_response(id, INVALID_REQUEST, "server is shut down")] };
+        }
+        let uri = params.get("textDocument").get("uri").as_str().unwrap_or("").to_string();
+        let result = match method {
+            "initialize" => object(vec![
+                (
+                    "capabilities",
+                    object(vec![
+                        ("textDocumentSync", 1usize.into()),
+                        ("hoverProvider", true.into()),
+                        ("definitionProvider", true.into()),
+                        ("completionProvider", object(vec![("triggerCharacters", Json::Array(vec![".".into()]))])),
+                    ]),
+                ),
+                ("serverInfo", object(vec![("name", "ruscall".into())])),
+            ]),
+            "shutdown" => {
+                self.shutdown = true;
+                Json::Null
+            }
+            "textDocument/didOpen" => {
+                let text = params.get("textDocument").get("text").as_str().unwrap_or("").to_string();
+                self.documents.insert(uri.clone(), Document::new(&uri, text));
+                return vec![self.publish_diagnostics(&uri)];
+            }
+            "textDocument/didChange" => {
+                //全文の同期なので最後の変更が今の内容
+                let text = params.get("contentChanges").as_array().last().and_then(|x| x.get("text").as_str());
+                match (self.documents.get_mut(&uri), text) {
+                    (Some(document), Some(text)) => document.update(text.to_string()),
+                    _ => return vec![],
+                }
+                return vec![self.publish_diagnostics(&uri)];
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![notification(
+                    "textDocument/publishDiagnostics",
+                    object(vec![("uri", uri.into()), ("diagnostics", Json::Array(vec![]))]),
+                )];
+            }
+            "textDocument/hover" => self.documents
+                .get(&uri)
+                .map(|x| x.hover(params.get("position")))
+                .unwrap_or(Json::Null),
+            "textDocument/definition" => self.documents
+                .get(&uri)
+                .map(|x| x.definition(&uri, params.get("position")))
+                .unwrap_or(Json::Null),
+            "textDocument/completion" => self.documents
+                .get(&uri)
+                .map(|x| x.completion(params.get("position")))
+                .unwrap_or(Json::Array(vec![])),
+            _ if id.is_null() => return vec![],
+            _ => return vec![error_response(id, METHOD_NOT_FOUND, &format!("method not found: {}", method))],
+        };
+        //通知にはレスポンスを返さない
+        if id.is_null() {
+            vec![]
+        } else {
+            vec![response(id, result)]
+        }
+    }
+
+    fn publish_diagnostics(&self, uri: &str) -> Json {
+        let diagnostics = self.documents.get(uri).map(|x| x.diagnostics()).unwrap_or(Json::Array(vec![]));
+        notification(
+            "textDocument/publishDiagnostics",
+            object(vec![("uri", uri.into()), ("diagnostics", diagnostics)]),
+        )
+    }
+}
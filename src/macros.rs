@@ -0,0 +1,9 @@
+//コンパイラ内部のデバッグ出力
+//compile::set_verbose(false)で抑制できる
+macro_rules! debug_println {
+    ($($arg:tt)*) => {
+        if $crate::compile::is_verbose() {
+            println!($($arg)*);
+        }
+    };
+}
@@ -2,9 +2,13 @@
 #[macro_use]
 extern crate combine;
 extern  crate indexmap;
+#[macro_use]
+mod macros;
 pub mod cmd_args;
 pub mod compile;
 pub mod hello;
+pub mod lsp;
+pub mod repl;
 
 fn main() {
     cmd_args::parse_cmd_args(cmd_args::get_cmd_args()).run();
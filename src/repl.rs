@@ -0,0 +1,227 @@
+use super::compile;
+use super::compile::debug_info::DebugInfo;
+use super::compile::error::Error;
+use super::compile::ir::ast::*;
+use super::compile::ir::hir::ProgramHir;
+use super::compile::semantic_analysis::analysis_hir;
+use super::compile::semantic_analysis::type_inference::assump_env::AssumpEnv;
+use super::compile::types::*;
+use combine::stream::state::SourcePosition;
+use std::io::{self, BufRead, Write};
+
+//式の評価結果を束縛する関数名
+const IT: &str = "it";
+
+//対話環境
+pub struct Repl {
+    //これまでに入力された宣言
+    program_hir: ProgramHir,
+    //宣言を解析した時の型環境
+    assump: Option<AssumpEnv>,
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl { program_hir: ProgramHir::new(), assump: None }
+    }
+
+    pub fn run(&mut self) {
+        println!("Ruscall REPL  (:help for help)");
+        let stdin = io::stdin();
+        loop {
+            print!("> ");
+            io::stdout().flush().unwrap();
+            let mut line = String::new();
+            match stdin.lock().read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => (),
+            }
+            match line.trim() {
+                ":quit" | ":q" => break,
+                line => match self.eval_line(line) {
+                    Ok(Some(output)) => println!("{}", output),
+                    Ok(None) => (),
+                    Err(err) => eprintln!("{}", err),
+                }
+            }
+        }
+    }
+
+    //1行を評価して表示する内容を返す
+    pub fn eval_line(&mut self, line: &str) -> Result<Option<String>, String> {
+        let line = line.trim();
+        if line.starts_with(":type ") {
+            let expr = parse_expr(&line[":type ".len()..])?;
+            //宣言済みの関数なら制約も含めた型スキームを表示する
+            if let ExprAST::VariableAST(ref x) = expr {
+                if let Some(scheme) = self.assump.as_ref().and_then(|assump| assump.global_get(&x.id)) {
+                    return Ok(Some(scheme.show()));
+                }
+            }
+            let ty = self.infer_expr(expr)?;
+            return Ok(Some(ty.show()));
+        }
+        if line.starts_with(":load ") {
+            let file_name = line[":load ".len()..].trim();
+            let src = compile::src_file_to_str(file_name).map_err(|err| format!("{}: {}", file_name, err))?;
+            let src = compile::parser::resolve_layout_pragma(&src);
+            let stmt_list = compile::parser::parse(&src)
+                .map_err(|err| Error::from_parse_error(err).to_string())?.0.stmt_list;
+            return self.declare(stmt_list);
+        }
+        match line {
+            "" => Ok(None),
+            ":reset" => {
+                *self = Repl::new();
+                Ok(None)
+            }
+            ":help" => Ok(Some(
+                ":type [EXPR]  show the type of expression\n\
+                 :load [FILE]  load declarations from source file\n\
+                 :reset        forget all declarations\n\
+                 :quit         exit".to_string()
+            )),
+            line if line.ends_with(";") => {
+                let stmt_list = compile::parser::parse(line)
+                    .map_err(|err| Error::from_parse_error(err).to_string())?.0.stmt_list;
+                self.declare(stmt_list)
+            }
+            line => match parse_expr(line) {
+                Ok(expr) => self.eval_expr(expr).map(Some),
+                Err(err) => {
+                    //セミコロンを省略した宣言として解釈してみる
+                    match compile::parser::parse(&(line.to_string() + ";")) {
+                        Ok((program, _)) => self.declare(program.stmt_list),
+                        Err(_) => Err(err)
+                    }
+                }
+            }
+        }
+    }
+
+    //宣言を追加して、追加された関数の型を返す
+    fn declare(&mut self, stmt_list: Vec<StmtAST>) -> Result<Option<String>, String> {
+        let mut program_hir = self.program_hir.clone();
+        let mut names = vec![];
+        let mut last_func_name = None;
+        for stmt in stmt_list {
+            //同じ行で続けて定義した関数は節として追加する
+            let is_clause = match stmt {
+                StmtAST::DefFuncAST(ref x) if last_func_name.as_ref() == Some(&x.name) => true,
+                StmtAST::DefFuncAST(ref x) if names.contains(&x.name) => {
+                    return Err(Error::new(x.pos, "Duplicate function").to_string());
+                }
+                _ => false,
+            };
+            match stmt {
+                StmtAST::DefFuncAST(ref x) => {
+                    if is_clause == false { names.push(x.name.clone()); }
+                    last_func_name = Some(x.name.clone());
+                }
+                StmtAST::NoneAST => (),
+                _ => last_func_name = None,
+            }
+            if is_clause {
+                program_hir.add_stmt(stmt).map_err(|err| err.to_string())?;
+            } else {
+                program_hir.replace_stmt(stmt).map_err(|err| err.to_string())?;
+            }
+        }
+        let (_, assump) = analysis_hir(with_main(program_hir.clone(), None))
+            .map_err(|err| err.to_string())?;
+        let output = names.iter()
+            .filter_map(|name| assump.global_get(name).map(|scheme| format!("{} :: {}", name, scheme.show())))
+            .collect::<Vec<_>>();
+        self.program_hir = program_hir;
+        self.assump = Some(assump);
+        Ok(if output.len() == 0 { None } else { Some(output.join("\n")) })
+    }
+
+    //式の型を推論する
+    fn infer_expr(&self, expr: ExprAST) -> Result<Type, String> {
+        let program_hir = with_main(with_it(self.program_hir.clone(), expr), None);
+        let (_, assump) = analysis_hir(program_hir).map_err(|err| err.to_string())?;
+        let scheme = assump.global_get(&IT.to_string()).unwrap();
+        Ok(scheme.get_qual().t.get_lambda_ty().func_ty.ret_type.clone())
+    }
+
+    //式をJITで評価して、値と型を返す
+    fn eval_expr(&self, expr: ExprAST) -> Result<String, String> {
+        let pos = expr.get_pos();
+        let ty = self.infer_expr(expr.clone())?;
+        let call_it = ExprAST::create_func_call_ast(
+            ExprAST::create_variable_ast(IT.to_string(), pos),
+            ExprAST::create_tuple_ast(vec![], pos),
+        );
+        let main_body = match ty {
+            Type::TCon { ref name } if name == "Int32" => call_it,
+            Type::TCon { ref name } if name == "Bool" => ExprAST::create_if_ast(
+                call_it,
+                ExprAST::create_num_ast("1".to_string(), pos),
+                ExprAST::create_num_ast("0".to_string(), pos),
+                pos,
+            ),
+            //値を表示できない型は型だけを表示する
+            _ => return Ok(format!("{} :: {}", IT, ty.show()))
+        };
+        let program_hir = with_main(with_it(self.program_hir.clone(), expr), Some(main_body));
+        let (program_ir, assump) = analysis_hir(program_hir).map_err(|err| err.to_string())?;
+        let code_gen_result = program_ir.code_gen("repl", assump, DebugInfo::disabled("repl"));
+        let value = compile::jit::run_main(&code_gen_result.module)?;
+        let value = match ty {
+            Type::TCon { ref name } if name == "Bool" => (value != 0).to_string(),
+            _ => value.to_string()
+        };
+        Ok(format!("{} :: {}", value, ty.show()))
+    }
+}
+
+impl ProgramHir {
+    //同じ名前の宣言があれば置き換えて文を登録する
+    fn replace_stmt(&mut self, stmt: StmtAST) -> Result<(), Error> {
+        match stmt {
+            StmtAST::DefFuncAST(ref x) => { self.def_func_list.remove(&x.name); }
+            StmtAST::DecFuncAST(ref x) => {
+                self.dec_func_list.remove(&x.name);
+                self.ex_dec_func_list.remove(&x.name);
+            }
+            StmtAST::DecStructAST(ref x) => { self.struct_list.remove(&x.ty.name); }
+            StmtAST::InfixAST(_) | StmtAST::NoneAST => ()
+        }
+        self.add_stmt(stmt)
+    }
+}
+
+fn parse_expr(src: &str) -> Result<ExprAST, String> {
+    compile::parser::parse_expr(src)
+        .map(|(expr, _)| expr)
+        .map_err(|err| Error::from_parse_error(err).to_string())
+}
+
+fn with_it(mut program_hir: ProgramHir, expr: ExprAST) -> ProgramHir {
+    let pos = expr.get_pos();
+    program_hir.def_func_list.insert(IT.to_string(), DefFuncAST {
+        name: IT.to_string(),
+        params: vec![],
+        patterns: vec![],
+        body: expr,
+        pos,
+        doc: None,
+    });
+    program_hir
+}
+
+//main関数を置き換える。指定がなければ0を返すmain関数にする
+fn with_main(mut program_hir: ProgramHir, body: Option<ExprAST>) -> ProgramHir {
+    let pos = SourcePosition::new();
+    program_hir.dec_func_list.remove("main");
+    program_hir.def_func_list.insert("main".to_string(), DefFuncAST {
+        name: "main".to_string(),
+        params: vec![],
+        patterns: vec![],
+        body: body.unwrap_or(ExprAST::create_num_ast("0".to_string(), pos)),
+        pos,
+        doc: None,
+    });
+    program_hir
+}
@@ -0,0 +1,49 @@
+#include <setjmp.h>
+#include <stddef.h>
+#include <stdlib.h>
+
+//パニックした時の終了コード
+#define RUSCALL_PANIC_EXIT_CODE 101
+
+#if defined(_MSC_VER)
+#define RUSCALL_THREAD_LOCAL __declspec(thread)
+#else
+#define RUSCALL_THREAD_LOCAL _Thread_local
+#endif
+
+void ruscall_report_panic(const char *message, const char *file, int line, int column);
+void *ruscall_env_mark(void);
+void ruscall_env_release(void *mark);
+
+//実行中のruscall_jit_call_mainに戻るための場所
+static RUSCALL_THREAD_LOCAL jmp_buf *panic_jmp = NULL;
+
+//JITしたmain関数を呼び出す。パニックしたら0を、正常に終われば1を返して戻り値をresultに書く
+//mainからパニックまでの間はRuscallの関数だけなので、longjmpで飛び越えても後始末は要らない
+//パニックした時は呼び出し中の環境が解放されずに残るので、mainを呼ぶ前の位置まで戻す
+int ruscall_jit_call_main(int (*main_func)(void), int *result)
+{
+    jmp_buf buf;
+    jmp_buf *prev = panic_jmp;
+    void *mark = ruscall_env_mark();
+    panic_jmp = &buf;
+    if (setjmp(buf) != 0) {
+        panic_jmp = prev;
+        ruscall_env_release(mark);
+        return 0;
+    }
+    *result = main_func();
+    panic_jmp = prev;
+    ruscall_env_release(mark);
+    return 1;
+}
+
+//JITで実行したコードのパニック。ホストを終了させずにruscall_jit_call_mainに戻る
+void ruscall_jit_panic(const char *message, const char *file, int line, int column)
+{
+    ruscall_report_panic(message, file, line, column);
+    if (panic_jmp == NULL) {
+        exit(RUSCALL_PANIC_EXIT_CODE);
+    }
+    longjmp(*panic_jmp, 1);
+}
@@ -1,4 +1,19 @@
+#include <stdint.h>
 #include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#if defined(__GLIBC__)
+#include <execinfo.h>
+#endif
+
+//パニックした時の終了コード
+#define RUSCALL_PANIC_EXIT_CODE 101
+
+#if defined(_MSC_VER)
+#define RUSCALL_THREAD_LOCAL __declspec(thread)
+#else
+#define RUSCALL_THREAD_LOCAL _Thread_local
+#endif
 
 int print(int a)
 {
@@ -10,4 +25,142 @@ int scan(int a){
     int b;
     scanf("%d",&b);
     return b;
-}
\ No newline at end of file
+}
+
+#if defined(__GLIBC__)
+//backtrace_symbolsの "path(_R4factFI32EI32+0x1a) [0x...]" からRuscallの関数名を取り出す
+//ラムダ式の関数(fact#lambda0)や引数が揃った時の関数(fact#direct)は定義した関数の名前にする
+static int ruscall_frame_name(const char *symbol, char *name, size_t size)
+{
+    const char *begin = strchr(symbol, '(');
+    if (begin == NULL) {
+        return 0;
+    }
+    begin++;
+    if (strncmp(begin, "main+", 5) == 0) {
+        snprintf(name, size, "main");
+        return 1;
+    }
+    if (strncmp(begin, "_R", 2) != 0) {
+        return 0;
+    }
+    char *end;
+    long len = strtol(begin + 2, &end, 10);
+    if (len <= 0 || end == begin + 2 || (long)strlen(end) < len) {
+        return 0;
+    }
+    const char *hash = memchr(end, '#', len);
+    if (hash != NULL) {
+        len = hash - end;
+    }
+    snprintf(name, size, "%.*s", (int)len, end);
+    return 1;
+}
+
+static void print_backtrace(void)
+{
+    void *frames[64];
+    int count = backtrace(frames, 64);
+    char **symbols = backtrace_symbols(frames, count);
+    if (symbols == NULL) {
+        return;
+    }
+    fprintf(stderr, "stack backtrace:\n");
+    int index = 0;
+    for (int i = 0; i < count; i++) {
+        char name[256];
+        if (ruscall_frame_name(symbols[i], name, sizeof(name))) {
+            fprintf(stderr, "%4d: %s\n", index++, name);
+        }
+    }
+    free(symbols);
+}
+#endif
+
+//Ruscallの実行時エラーの位置とメッセージを表示する
+//環境変数RUSCALL_BACKTRACEが0以外ならRuscallの関数のバックトレースも表示する
+void ruscall_report_panic(const char *message, const char *file, int line, int column)
+{
+    fflush(stdout);
+    fprintf(stderr, "panic at %s:%d:%d: %s\n", file, line, column, message);
+#if defined(__GLIBC__)
+    const char *backtrace_env = getenv("RUSCALL_BACKTRACE");
+    if (backtrace_env != NULL && strcmp(backtrace_env, "0") != 0) {
+        print_backtrace();
+    } else {
+        fprintf(stderr, "note: run with `RUSCALL_BACKTRACE=1` to display a backtrace\n");
+    }
+#endif
+}
+
+//Ruscallの実行時エラー。表示して終了する
+void ruscall_panic(const char *message, const char *file, int line, int column)
+{
+    ruscall_report_panic(message, file, line, column);
+    exit(RUSCALL_PANIC_EXIT_CODE);
+}
+
+//クロージャの環境を確保する領域のチャンク
+typedef struct ruscall_env_chunk {
+    struct ruscall_env_chunk *prev;
+    char *top;
+    char *end;
+    char data[];
+} ruscall_env_chunk;
+
+#define RUSCALL_ENV_CHUNK_SIZE (64 * 1024)
+
+//今使っているチャンク。古いチャンクはprevで辿れる
+static RUSCALL_THREAD_LOCAL ruscall_env_chunk *env_chunk = NULL;
+//解放したチャンクを1つだけ取っておく。ループでチャンクの境界をまたぐたびにmallocしないようにする
+static RUSCALL_THREAD_LOCAL ruscall_env_chunk *env_spare = NULL;
+
+//クロージャの環境を確保する。確保できなければNULLを返す
+void *ruscall_env_alloc(uint32_t size)
+{
+    size_t aligned = ((size_t)size + 7) & ~(size_t)7;
+    if (env_chunk == NULL || (size_t)(env_chunk->end - env_chunk->top) < aligned) {
+        ruscall_env_chunk *chunk;
+        if (env_spare != NULL && aligned <= RUSCALL_ENV_CHUNK_SIZE) {
+            chunk = env_spare;
+            env_spare = NULL;
+        } else {
+            size_t cap = aligned > RUSCALL_ENV_CHUNK_SIZE ? aligned : RUSCALL_ENV_CHUNK_SIZE;
+            chunk = malloc(sizeof(ruscall_env_chunk) + cap);
+            if (chunk == NULL) {
+                return NULL;
+            }
+            chunk->end = chunk->data + cap;
+        }
+        chunk->prev = env_chunk;
+        chunk->top = chunk->data;
+        env_chunk = chunk;
+    }
+    void *ptr = env_chunk->top;
+    env_chunk->top += aligned;
+    return ptr;
+}
+
+//今の領域の位置。ruscall_env_releaseに渡すと、この後に確保した環境をまとめて解放する
+void *ruscall_env_mark(void)
+{
+    return env_chunk == NULL ? NULL : env_chunk->top;
+}
+
+//markより後に確保した環境を解放する
+void ruscall_env_release(void *mark)
+{
+    uintptr_t pos = (uintptr_t)mark;
+    while (env_chunk != NULL && !((uintptr_t)env_chunk->data <= pos && pos <= (uintptr_t)env_chunk->end)) {
+        ruscall_env_chunk *prev = env_chunk->prev;
+        if (env_spare == NULL && env_chunk->end - env_chunk->data == RUSCALL_ENV_CHUNK_SIZE) {
+            env_spare = env_chunk;
+        } else {
+            free(env_chunk);
+        }
+        env_chunk = prev;
+    }
+    if (env_chunk != NULL) {
+        env_chunk->top = mark;
+    }
+}
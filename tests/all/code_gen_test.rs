@@ -5,6 +5,7 @@ use self::inkwell::OptimizationLevel;
 use self::inkwell::execution_engine::JitFunction;
 use self::ruscall::compile::parse;
 use self::ruscall::compile::code_gen::CodeGenResult;
+use self::ruscall::compile::debug_info::DebugInfo;
 use super::moc_llvm_module::create_moc_llvm_module;
 
 type MainFunc = unsafe extern "C" fn() -> i32;
@@ -15,7 +16,7 @@ macro_rules! helper {
         fn $file_name(){
             match parse(include_str!(concat!("test_data/", stringify!($file_name), ".rsc"))) {
                 Ok((ir,assump)) => {
-                    let CodeGenResult{ module , .. } = ir.code_gen(stringify!($file_name),assump);
+                    let CodeGenResult{ module , .. } = ir.code_gen(stringify!($file_name),assump,DebugInfo::disabled(concat!(stringify!($file_name), ".rsc")));
                     let execution_engine = module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
                     assert!(execution_engine.add_module(&create_moc_llvm_module()).is_ok(),"add_module error!");
                     unsafe {
@@ -48,4 +49,44 @@ helper!(tuple_property,14);
 helper!(record_property,20);
 helper!(record_name_property,11);
 helper!(quantify,25);
-helper!(explicit_quantify,7);
\ No newline at end of file
+helper!(explicit_quantify,7);
+helper!(tail_call,1000000);
+helper!(auto_capture,7);
+helper!(closure_value,13);
+helper!(multi_arg_tail_call,1000000);
+helper!(closure_loop,10000000);
+helper!(record_update,17);
+helper!(record_update_shared_field,25);
+helper!(row_type,14);
+helper!(pattern_params,29);
+helper!(multi_clause,50);
+helper!(where_clause,26);
+helper!(layout,21);
+helper!(mutual_recursion,1);
+
+//公開した関数をマングリングされていない名前でC ABIのまま呼び出す
+#[test]
+fn export_call() {
+    use self::ruscall::compile::jit::create_execution_engine;
+    use self::ruscall::compile::parse_lib;
+    #[repr(C)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+    type AddFunc = unsafe extern "C" fn(i32, i32) -> i32;
+    type MoveFunc = unsafe extern "C" fn(*const Point, i32, *mut Point);
+    let (ir, assump) = parse_lib(include_str!("test_data/export.rsc")).unwrap();
+    let CodeGenResult { module, .. } = ir.code_gen("export", assump, DebugInfo::disabled("export.rsc"));
+    assert!(module.get_function("add").is_some());
+    assert!(module.get_function("move").is_some());
+    let execution_engine = create_execution_engine(&module).unwrap();
+    unsafe {
+        let add: JitFunction<AddFunc> = execution_engine.get_function("add").unwrap();
+        assert_eq!(add.call(2, 3), 5);
+        let move_func: JitFunction<MoveFunc> = execution_engine.get_function("move").unwrap();
+        let mut ret = Point { x: 0, y: 0 };
+        move_func.call(&Point { x: 1, y: 2 }, 10, &mut ret);
+        assert_eq!((ret.x, ret.y), (11, 2));
+    }
+}
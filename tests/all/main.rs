@@ -1,3 +1,4 @@
 pub mod moc_llvm_module;
 pub mod code_gen_test;
-pub mod test;
\ No newline at end of file
+pub mod test;
+pub mod wasm_parser;
@@ -8,6 +8,8 @@ fn to_str_vec(args: Vec<&str>) -> Vec<String> {
 #[test]
 fn parse_cmd_args_test() {
     use self::ruscall::cmd_args::*;
+    use self::ruscall::compile::compile_option::*;
+    use self::ruscall::compile::linker::*;
     assert_eq!(
         parse_cmd_args(to_str_vec(vec!["my.exe"])),
         CmdArgsKind::Help
@@ -28,14 +30,146 @@ fn parse_cmd_args_test() {
         parse_cmd_args(to_str_vec(vec!["my.exe", "-hello"])),
         CmdArgsKind::Hello
     );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-repl"])),
+        CmdArgsKind::Repl
+    );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-lsp"])),
+        CmdArgsKind::Lsp
+    );
     assert_eq!(
         parse_cmd_args(to_str_vec(vec!["my.exe", "-build", "hoge"])),
-        CmdArgsKind::Compile("hoge".to_string(), "hoge".to_string())
+        CmdArgsKind::Compile("hoge".to_string(), None, CompileOption::new())
     );
     assert_eq!(
         parse_cmd_args(to_str_vec(vec!["my.exe", "-build", "hoge", "fuga"])),
-        CmdArgsKind::Compile("hoge".to_string(), "fuga".to_string())
+        CmdArgsKind::Compile("hoge".to_string(), Some("fuga".to_string()), CompileOption::new())
+    );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-build", "hoge", "-O2"])),
+        CmdArgsKind::Compile("hoge".to_string(), None, CompileOption {
+            opt_level: OptLevel::O2,
+            ..CompileOption::new()
+        })
+    );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-build", "-Os", "hoge", "fuga"])),
+        CmdArgsKind::Compile("hoge".to_string(), Some("fuga".to_string()), CompileOption {
+            opt_level: OptLevel::Os,
+            ..CompileOption::new()
+        })
+    );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-build", "hoge", "-O9"])),
+        CmdArgsKind::Error
+    );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-build", "hoge", "--linker=clang", "-static"])),
+        CmdArgsKind::Compile("hoge".to_string(), None, CompileOption {
+            linker: Some(LinkerKind::Clang),
+            link_mode: LinkMode::Static,
+            ..CompileOption::new()
+        })
+    );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-build", "hoge", "--linker=foo"])),
+        CmdArgsKind::Error
+    );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-build", "hoge", "fuga.ll", "--emit=llvm-ir", "--save-temps", "-g"])),
+        CmdArgsKind::Compile("hoge".to_string(), Some("fuga.ll".to_string()), CompileOption {
+            emit: EmitKind::LlvmIr,
+            save_temps: true,
+            debug_info: true,
+            ..CompileOption::new()
+        })
+    );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec![
+            "my.exe", "-build", "hoge", "--emit=obj", "--target", "aarch64-unknown-linux-gnu",
+            "--target-cpu", "cortex-a53", "--target-feature", "+neon", "--target-feature=+crc,-fp-armv8"
+        ])),
+        CmdArgsKind::Compile("hoge".to_string(), None, CompileOption {
+            emit: EmitKind::Obj,
+            target: Some("aarch64-unknown-linux-gnu".to_string()),
+            target_cpu: Some("cortex-a53".to_string()),
+            target_features: to_str_vec(vec!["+neon", "+crc", "-fp-armv8"]),
+            ..CompileOption::new()
+        })
+    );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-build", "hoge", "--target"])),
+        CmdArgsKind::Error
+    );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-build", "hoge", "--crate-type", "cdylib"])),
+        CmdArgsKind::Compile("hoge".to_string(), None, CompileOption {
+            emit: EmitKind::Cdylib,
+            ..CompileOption::new()
+        })
+    );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-check", "hoge"])),
+        CmdArgsKind::Check("hoge".to_string(), false)
+    );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-check", "hoge", "-types"])),
+        CmdArgsKind::Check("hoge".to_string(), true)
     );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-doc", "hoge", "-html"])),
+        CmdArgsKind::Doc("hoge".to_string(), self::ruscall::compile::doc::DocFormat::Html)
+    );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-fmt", "--check", "hoge", "fuga"])),
+        CmdArgsKind::Fmt(to_str_vec(vec!["hoge", "fuga"]), true)
+    );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-run", "hoge"])),
+        CmdArgsKind::Run("hoge".to_string(), vec![])
+    );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-run", "hoge", "1", "2"])),
+        CmdArgsKind::Run("hoge".to_string(), to_str_vec(vec!["1", "2"]))
+    );
+    assert_eq!(
+        parse_cmd_args(to_str_vec(vec!["my.exe", "-demangle", "_R1fFI32EB"])),
+        CmdArgsKind::Demangle(to_str_vec(vec!["_R1fFI32EB"]))
+    );
+}
+
+#[test]
+fn mangling_test() {
+    use self::ruscall::compile::mangling::*;
+    use self::ruscall::compile::types::*;
+    let int32 = Type::create_int32();
+    let add_ty = Type::create_func_type(
+        vec![int32.clone()],
+        Type::create_func_type(vec![int32.clone()], int32.clone()),
+    );
+    assert_eq!(mangle(&"add".to_string(), &add_ty), "_R3addFI32EFI32EI32");
+    assert_eq!(demangle("_R3addFI32EFI32EI32"), Some("add::Int32->(Int32->Int32)".to_string()));
+    let swap_ty = Type::create_func_type(
+        vec![Type::create_tuple_type(vec![int32.clone(), Type::create_bool()])],
+        Type::create_tuple_type(vec![Type::create_bool(), int32.clone()]),
+    );
+    assert_eq!(mangle(&"#2".to_string(), &swap_ty), "_R2#2FTI32BEETBI32E");
+    assert_eq!(demangle("_R2#2FTI32BEETBI32E"), Some("#2::(Int32,Bool)->(Bool,Int32)".to_string()));
+    assert_eq!(demangle("_R3addFI32"), None);
+    assert_eq!(demangle("main"), None);
+    assert_eq!(demangle_text("call _R1fFI32EB@PLT; foo_R1x"), "call f::Int32->Bool@PLT; foo_R1x");
+}
+
+#[test]
+fn show_row_type_test() {
+    use self::ruscall::compile::types::*;
+    let row = Type::TGen(0, TypeId::new(0));
+    let x = Type::TGen(1, TypeId::new(1));
+    let mut ps = Preds::new();
+    ps.insert(row.clone(), Pred { ty: row.clone(), cond: Condition::Items(Box::new(ImplItems::with_name_property("x".to_string(), x.clone()))) });
+    let get_x = Qual { ps, t: Type::create_func_type(vec![row], x) };
+    assert_eq!(get_x.show(), "{x:b|a}->b");
 }
 
 use self::ruscall::compile::ir::ast;
@@ -46,6 +180,7 @@ fn create_infixl_ast(op: &str, priority: i8) -> ast::StmtAST {
         op: op.to_string(),
         ty: ast::InfixType::Left,
         priority: ast::Priority(priority),
+        doc: None,
     })
 }
 
@@ -54,6 +189,7 @@ fn create_infixr_ast(op: &str, priority: i8) -> ast::StmtAST {
         op: op.to_string(),
         ty: ast::InfixType::Right,
         priority: ast::Priority(priority),
+        doc: None,
     })
 }
 
@@ -82,4 +218,399 @@ fn infix_parse_test() {
             ],
         }
     );
-}
\ No newline at end of file
+}
+#[test]
+fn check_test() {
+    use self::ruscall::compile::check_from_str;
+    use self::ruscall::compile::types::ShowType;
+    let assump = check_from_str(include_str!("test_data/explicit_quantify.rsc")).unwrap();
+    assert_eq!(assump.global_get(&"id".to_string()).unwrap().show(), "a->a");
+    assert_eq!(assump.global_get(&"main".to_string()).unwrap().show(), "()->Int32");
+    assert!(check_from_str("main = (1,2).2;").is_err());
+}
+
+#[test]
+fn record_update_test() {
+    use self::ruscall::compile::check_from_str;
+    let structs = "struct Point{ x:Int32, y:Int32 };struct Point3{ x:Int32, y:Int32, z:Int32 };";
+    //同じフィールドを持つ構造体があっても、更新する値の型から構造体が決まる
+    assert!(check_from_str(&format!("{}main = (f (Point3{{x=1,y=2,z=3}})).z;f p = p {{ x = 5 }};", structs)).is_ok());
+    //型が持たないフィールドは更新できない
+    let err = check_from_str(&format!("{}main = (f (Point{{x=1,y=2}})).x;f p = p {{ z = 5 }};", structs)).unwrap_err();
+    assert!(err.contains("not found property"), "{}", err);
+    let err = check_from_str(&format!("{}main = (f (Point{{x=1,y=2}})).x;f p = p {{ x = 5, x = 6 }};", structs)).unwrap_err();
+    assert!(err.contains("record field `x` is updated twice"), "{}", err);
+}
+
+#[test]
+fn pattern_params_test() {
+    use self::ruscall::compile::check_from_str;
+    assert!(check_from_str("main = f (1,2);f (a, b) = a;").is_ok());
+    //パターンと要素数が違う
+    assert!(check_from_str("main = f (1,2,3);f (a, b) = a;").is_err());
+    assert!(check_from_str("main = f (1,2);f (a, a) = a;").is_err());
+    //同じ引数に要素数の違うタプルのパターンを書いた
+    let err = check_from_str("main = f (1,2);f (a, b) = a;f (a, b, c) = a;").unwrap_err();
+    assert!(err.contains("pattern arity mismatch: expected a tuple of 3 elements"), "{}", err);
+    let err = check_from_str("struct Point{ x:Int32, y:Int32 };main = f (Point{x=1,y=2});f Point{x = (a, b)} = a;").unwrap_err();
+    assert!(err.contains("pattern arity mismatch: expected a tuple of 2 elements"), "{}", err);
+}
+
+#[test]
+fn multi_clause_test() {
+    use self::ruscall::compile::check_from_str;
+    assert!(check_from_str("main = f 1;f 0 = 1;f n = n;").is_ok());
+    //節ごとに引数の数が違う
+    assert!(check_from_str("main = f 1;f 0 = 1;f n m = n;").is_err());
+    assert!(check_from_str("main = f 1;f 0 = 1;f true = 1;").is_err());
+    //同じ名前の節は続けて書く
+    assert!(check_from_str("main = f 1;f 0 = 1;g = 2;f n = n;").is_err());
+    assert!(check_from_str("main = f 1;f x = g x where { g 0 = 1; h = 2; g n = n; };").is_err());
+}
+
+#[test]
+fn non_exhaustive_warning_test() {
+    use self::ruscall::compile::parse;
+    let (ir, _) = parse("main = f 1;f 0 = 1;f n = n;").unwrap();
+    assert_eq!(ir.warning_list.len(), 0);
+    let (ir, _) = parse("main = f true;f true = 1;f false = 0;").unwrap();
+    assert_eq!(ir.warning_list.len(), 0);
+    let (ir, _) = parse("main = f 1;f 0 = 1;f 1 = 2;").unwrap();
+    assert_eq!(ir.warning_list.len(), 1);
+    assert!(ir.warning_list[0].to_warning_string().contains("non-exhaustive patterns"));
+    //ガードが成り立たないかもしれない節は数えない
+    let (ir, _) = parse("infixl 4 ==;main = f 1;f n | n == 0 = 1;").unwrap();
+    assert_eq!(ir.warning_list.len(), 1);
+    let (ir, _) = parse("main = f (1, true);f (0, b) = 1;f (n, true) = 2;").unwrap();
+    assert_eq!(ir.warning_list.len(), 1);
+}
+
+#[test]
+fn where_clause_test() {
+    use self::ruscall::compile::{check_from_str, run_from_str};
+    assert!(check_from_str("main = f 1;f x = g x where { g y = y; };").is_ok());
+    //局所関数は外から見えない
+    assert!(check_from_str("main = g 1;f x = g x where { g y = y; };").is_err());
+    //キャプチャする変数が使う場所で隠れていても外側の値を渡す
+    assert_eq!(run_from_str("main = f 1;f x = (\\x -> g x) 2 where { g y = x; };"), Ok(1));
+    assert_eq!(run_from_str("infixl 1 +;main = f 1;f x = g 5 where { g x = h x; h y = x + y; };"), Ok(6));
+    //持ち上げた関数は型の一覧に出さない
+    let assump = check_from_str("main = f 1;f x = g x where { g y = x; };").unwrap();
+    let names = assump.global_list().into_iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["f".to_string(), "main".to_string()]);
+}
+
+#[test]
+fn layout_test() {
+    use self::ruscall::compile::parser::layout;
+    assert_eq!(layout("f x =\n    x\nmain = f 1\n"), "f x =\n    x;\nmain = f 1;\n");
+    assert_eq!(layout("f x = g x where g y = y\nmain = f 1"), "f x = g x where{g y = y; };\nmain = f 1;");
+}
+
+#[test]
+fn doc_test() {
+    use self::ruscall::compile::doc::DocFormat;
+    use self::ruscall::compile::doc_from_str;
+    use self::ruscall::compile::parser::parse;
+    let mut infix = create_infixl_ast("+", 4);
+    if let ast::StmtAST::InfixAST(ref mut x) = infix {
+        x.doc = Some("足し算\n左結合".to_string());
+    }
+    //////は普通のコメント
+    assert_eq!(parse("//// hoge\n/// 足し算\n///左結合\ninfixl 4 +;").unwrap().0.stmt_list, vec![infix]);
+    let doc = doc_from_str("infixl 4 +;\n/// 2倍にする\nf x = x + x;\nstruct Point {x: Int32, y: Int32};", DocFormat::Markdown).unwrap();
+    assert!(doc.contains("### `f`\n\n```\nf :: Int32->Int32\n```\n\n2倍にする\n"));
+    assert!(doc.contains("- `x: Int32`\n- `y: Int32`\n"));
+    assert!(doc.contains("```\ninfixl 4 +\n```\n"));
+}
+
+#[test]
+fn format_test() {
+    use self::ruscall::compile::formatter::format_src;
+    let src = "infixl 1 +;\n//　足し算\nadd x y=x+y; // 末尾\nstruct Point{ x:Int32, y:Int32 };\n\n\nmain = add (\\(a,b),c->a+c) 1;\nsign x\n  | x == 0 = 0\n  | true = 1;";
+    let formatted = "infixl 1 +;\n//　足し算\nadd x y = x + y; // 末尾\nstruct Point { x: Int32, y: Int32 };\n\nmain = add (\\(a, b), c -> a + c) 1;\nsign x\n    | x == 0 = 0\n    | true = 1;\n";
+    assert_eq!(format_src(src).unwrap(), formatted);
+    assert_eq!(format_src(formatted).unwrap(), formatted);
+    //ガードは書いた通りに残す
+    let guards = "sign x\n    | x == 0 = 0\n    | otherwise = 1;\nf x\n    | true = x;\n";
+    assert_eq!(format_src(guards).unwrap(), guards);
+    //式の中のコメントはその位置に残す
+    let src = "main = f 1 /* 引数 */ 2;\nf x y = x + // 足す\n    y;";
+    let formatted = "main = f 1 /* 引数 */ 2;\nf x y =\n    x\n        + // 足す\n        y;\n";
+    assert_eq!(format_src(src).unwrap(), formatted);
+    assert_eq!(format_src(formatted).unwrap(), formatted);
+    assert!(format_src("//#layout\nmain = 1").is_err());
+}
+
+#[test]
+fn lsp_test() {
+    use self::ruscall::lsp::json::{parse, Json};
+    use self::ruscall::lsp::serve;
+    use std::io::Cursor;
+    let src = "infixl 1 +;\nstruct Point {x: Int32, y: Int32};\ndouble x = x + x;\nmain = double (Point { x = 1, y = 2 }).x;";
+    let changed = "infixl 1 +;\nstruct Point {x: Int32, y: Int32};\ndouble x = x + x;\nmain = double (Point { x = 1, y = 2 }).";
+    let document = r#"{"uri":"file:///main.rsc"}"#;
+    let requests = vec![
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#.to_string(),
+        format!(r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"file:///main.rsc","text":{}}}}}}}"#, Json::from(src)),
+        format!(r#"{{"jsonrpc":"2.0","id":2,"method":"textDocument/hover","params":{{"textDocument":{},"position":{{"line":3,"character":8}}}}}}"#, document),
+        format!(r#"{{"jsonrpc":"2.0","id":3,"method":"textDocument/definition","params":{{"textDocument":{},"position":{{"line":3,"character":8}}}}}}"#, document),
+        format!(r#"{{"jsonrpc":"2.0","method":"textDocument/didChange","params":{{"textDocument":{},"contentChanges":[{{"text":{}}}]}}}}"#, document, Json::from(changed)),
+        format!(r#"{{"jsonrpc":"2.0","id":4,"method":"textDocument/completion","params":{{"textDocument":{},"position":{{"line":3,"character":39}}}}}}"#, document),
+        r#"{"jsonrpc":"2.0","id":5,"method":"shutdown"}"#.to_string(),
+        r#"{"jsonrpc":"2.0","method":"exit"}"#.to_string(),
+    ];
+    let input = requests.iter().map(|x| format!("Content-Length: {}\r\n\r\n{}", x.len(), x)).collect::<String>();
+    let mut output = vec![];
+    assert_eq!(serve(&mut Cursor::new(input), &mut output).unwrap(), 0);
+    let output = String::from_utf8(output).unwrap();
+    let responses = output
+        .split("Content-Length: ")
+        .skip(1)
+        .map(|x| parse(&x[x.find("\r\n\r\n").unwrap() + 4..]).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(responses.len(), 7);
+    assert_eq!(responses[0].get("result").get("capabilities").get("hoverProvider"), &Json::Bool(true));
+    assert_eq!(responses[1].get("params").get("diagnostics"), &Json::Array(vec![]));
+    let hover = responses[2].get("result").get("contents").get("value").as_str().unwrap();
+    assert!(hover.contains("double :: Int32->Int32"));
+    assert_eq!(responses[3].get("result").get("range").get("start"), &parse(r#"{"line":2,"character":0}"#).unwrap());
+    //構文エラーになっても前に解析できた構造体のフィールドを補完する
+    assert_eq!(responses[4].get("params").get("diagnostics").at(0).get("severity").as_usize(), Some(1));
+    let labels = responses[5].get("result").as_array().iter().filter_map(|x| x.get("label").as_str()).collect::<Vec<_>>();
+    assert_eq!(labels, vec!["x", "y"]);
+    assert!(responses[6].get("result").is_null());
+}
+
+#[test]
+fn capture_warning_test() {
+    use self::ruscall::compile::parse;
+    let (ir, _) = parse("infixl 1 +;main = f 1 2;f x y = (\\[x] z -> x + z) y;").unwrap();
+    assert_eq!(ir.warning_list.len(), 0);
+    let (ir, _) = parse("infixl 1 +;main = f 1 2;f x y = (\\[y] z -> x + z) 3;").unwrap();
+    //yは使われていなくて、xが足りない
+    assert_eq!(ir.warning_list.len(), 2);
+}
+
+#[test]
+fn run_test() {
+    use self::ruscall::compile::jit::PANIC_ERROR;
+    use self::ruscall::compile::run_from_str;
+    assert_eq!(run_from_str(include_str!("test_data/fact.rsc")), Ok(120));
+    //パニックしてもホストのプロセスは終了しない
+    assert_eq!(run_from_str(include_str!("test_data/panic.rsc")), Err(PANIC_ERROR.to_string()));
+    assert_eq!(run_from_str(include_str!("test_data/fact.rsc")), Ok(120));
+}
+
+#[test]
+fn cross_compile_test() {
+    use self::ruscall::compile::compile_from_str;
+    use self::ruscall::compile::compile_option::*;
+    use std::{env, fs};
+    let output = env::temp_dir().join("ruscall_cross_compile_test.o");
+    let option = CompileOption {
+        emit: EmitKind::Obj,
+        target: Some("aarch64-unknown-linux-gnu".to_string()),
+        ..CompileOption::new()
+    };
+    assert_eq!(compile_from_str("main = 0;", output.to_str().unwrap(), &option), Ok(()));
+    let obj = fs::read(&output).unwrap();
+    fs::remove_file(&output).unwrap();
+    //ELFヘッダのe_machineがAArch64(183)になっている
+    assert_eq!(&obj[0..4], b"\x7fELF");
+    assert_eq!(obj[18], 183);
+}
+
+#[test]
+fn panic_test() {
+    use self::ruscall::compile::compile_from_str;
+    use self::ruscall::compile::compile_option::*;
+    use std::{env, fs, process::Command};
+    let output = env::temp_dir().join("ruscall_panic_test.out");
+    assert_eq!(compile_from_str(include_str!("test_data/panic.rsc"), output.to_str().unwrap(), &CompileOption::new()), Ok(()));
+    let result = Command::new(&output).env("RUSCALL_BACKTRACE", "1").output().unwrap();
+    fs::remove_file(&output).unwrap();
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert_eq!(result.status.code(), Some(101));
+    assert!(stderr.contains("panic at main.rsc:6:13: attempt to divide by zero"), "{}", stderr);
+    //パニックしたRuscallの関数がバックトレースに表示される
+    if cfg!(target_env = "gnu") {
+        let backtrace = &stderr[stderr.find("stack backtrace:\n").expect(&stderr)..];
+        assert!(backtrace.contains("   0: div\n"), "{}", stderr);
+        //部分適用でラムダ式の関数から呼ばれても定義した関数の名前を表示する
+        let src = "infixl 2 /;main = apply (div 10) 0;apply f x = f x;div x y = x / y;";
+        assert_eq!(compile_from_str(src, output.to_str().unwrap(), &CompileOption::new()), Ok(()));
+        let result = Command::new(&output).env("RUSCALL_BACKTRACE", "1").output().unwrap();
+        fs::remove_file(&output).unwrap();
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        let backtrace = &stderr[stderr.find("stack backtrace:\n").expect(&stderr)..];
+        assert!(backtrace.contains("   0: div\n"), "{}", stderr);
+        assert!(!backtrace.contains('#'), "{}", stderr);
+    }
+}
+
+//クロージャを作り続けるループもメモリを制限した中で最後まで動く
+#[test]
+#[cfg(unix)]
+fn closure_loop_memory_test() {
+    use self::ruscall::compile::compile_from_str;
+    use self::ruscall::compile::compile_option::*;
+    use std::{env, fs, process::Command};
+    let output = env::temp_dir().join("ruscall_closure_loop_memory_test.out");
+    assert_eq!(compile_from_str(include_str!("test_data/closure_loop.rsc"), output.to_str().unwrap(), &CompileOption::new()), Ok(()));
+    //環境を解放しなければ1000万個の環境で数百MBになる
+    let result = Command::new("sh")
+        .arg("-c")
+        .arg("ulimit -v 65536 && exec \"$0\"")
+        .arg(&output)
+        .output()
+        .unwrap();
+    fs::remove_file(&output).unwrap();
+    //終了コードはmainの戻り値の下位8ビット
+    assert_eq!(result.status.code(), Some(10000000 & 0xff), "{}", String::from_utf8_lossy(&result.stderr));
+}
+
+#[test]
+fn debug_info_test() {
+    use self::ruscall::compile::compile_from_str;
+    use self::ruscall::compile::compile_option::*;
+    use std::{env, fs};
+    let output = env::temp_dir().join("ruscall_debug_info_test.ll");
+    let option = CompileOption { emit: EmitKind::LlvmIr, debug_info: true, ..CompileOption::new() };
+    assert_eq!(compile_from_str(include_str!("test_data/fact.rsc"), output.to_str().unwrap(), &option), Ok(()));
+    let ir = fs::read_to_string(&output).unwrap();
+    fs::remove_file(&output).unwrap();
+    assert!(ir.contains("!DICompileUnit(language: DW_LANG_C"));
+    assert!(ir.contains("!DISubprogram(name: \"fact\""));
+    assert!(ir.contains("!DILocalVariable(name: \"x\", arg: 1"));
+    assert!(ir.contains("call void @llvm.dbg.declare"));
+}
+
+#[test]
+fn emit_test() {
+    use self::ruscall::compile::compile_from_str;
+    use self::ruscall::compile::compile_option::*;
+    use std::{env, fs, process};
+    let list_dir = |dir: &std::path::Path| {
+        let mut names = fs::read_dir(dir).unwrap().map(|x| x.unwrap().file_name().into_string().unwrap()).collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+    for &emit in [EmitKind::LlvmIr, EmitKind::LlvmBc, EmitKind::Asm, EmitKind::Obj, EmitKind::StaticLib, EmitKind::Exe].iter() {
+        for &save_temps in [false, true].iter() {
+            let dir = env::temp_dir().join(format!("ruscall_emit_test_{:?}_{}", emit, save_temps));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let output = dir.join("out.x");
+            let option = CompileOption { emit, save_temps, ..CompileOption::new() };
+            assert_eq!(compile_from_str(include_str!("test_data/fact.rsc"), output.to_str().unwrap(), &option), Ok(()));
+            //指定した名前のファイルだけができて、中間ファイルは--save-tempsの時だけ隣に残る
+            let expected = match (emit, save_temps) {
+                (EmitKind::StaticLib, true) | (EmitKind::Exe, true) => vec!["out.x", "out.x.bc", "out.x.obj"],
+                _ => vec!["out.x"],
+            };
+            assert_eq!(list_dir(&dir), expected, "{:?}", emit);
+            assert!(!env::temp_dir().join(format!("ruscall-{}-out.x", process::id())).exists());
+            fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+}
+
+#[test]
+fn opt_level_test() {
+    use self::ruscall::compile::compile_from_str;
+    use self::ruscall::compile::compile_option::*;
+    use std::{env, fs};
+    let output = env::temp_dir().join("ruscall_opt_level_test.ll");
+    let emit_ir = |opt_level| {
+        //-gの引数のallocaが最適化で消えるかを見る
+        let option = CompileOption { emit: EmitKind::LlvmIr, debug_info: true, opt_level, ..CompileOption::new() };
+        assert_eq!(compile_from_str(include_str!("test_data/fact.rsc"), output.to_str().unwrap(), &option), Ok(()));
+        let ir = fs::read_to_string(&output).unwrap();
+        fs::remove_file(&output).unwrap();
+        ir
+    };
+    assert!(emit_ir(OptLevel::O0).contains("alloca"));
+    assert!(!emit_ir(OptLevel::O2).contains("alloca"));
+}
+
+#[test]
+fn direct_call_test() {
+    use self::ruscall::compile::compile_from_str;
+    use self::ruscall::compile::compile_option::*;
+    use std::{env, fs};
+    let output = env::temp_dir().join("ruscall_direct_call_test.ll");
+    let option = CompileOption { emit: EmitKind::LlvmIr, ..CompileOption::new() };
+    assert_eq!(compile_from_str(include_str!("test_data/direct_call.rsc"), output.to_str().unwrap(), &option), Ok(()));
+    let ir = fs::read_to_string(&output).unwrap();
+    fs::remove_file(&output).unwrap();
+    //引数が揃った呼び出しは途中のクロージャを作らずに1回で直接呼ぶ
+    let main = &ir[ir.find("@main(").unwrap()..];
+    let main = &main[..main.find("\n}\n").unwrap()];
+    assert_eq!(main.matches("call ").count(), 1, "{}", main);
+    assert!(main.contains("@\"_R11add3#directFI32I32I32EI32\"("), "{}", main);
+    assert!(!main.contains("alloca"), "{}", main);
+}
+
+#[test]
+fn export_test() {
+    use self::ruscall::compile::compile_from_str;
+    use self::ruscall::compile::compile_option::*;
+    use std::{env, fs};
+    let output = env::temp_dir().join("ruscall_export_test.o");
+    let header = output.with_extension("h");
+    let option = CompileOption { emit: EmitKind::Obj, ..CompileOption::new() };
+    assert_eq!(compile_from_str(include_str!("test_data/export.rsc"), output.to_str().unwrap(), &option), Ok(()));
+    let header_str = fs::read_to_string(&header).unwrap();
+    fs::remove_file(&output).unwrap();
+    fs::remove_file(&header).unwrap();
+    assert!(header_str.contains("typedef struct Point {\n    int32_t x;\n    int32_t y;\n} Point;"));
+    assert!(header_str.contains("int32_t add(int32_t a0, int32_t a1);"));
+    assert!(header_str.contains("void move(const Point *a0, int32_t a1, Point *ret);"));
+    //多相な関数は公開できない
+    assert!(compile_from_str("export id::a->a;\nid x = x;", output.to_str().unwrap(), &option).is_err());
+}
+
+#[test]
+fn wasm_test() {
+    use self::ruscall::compile::compile_from_str;
+    use self::ruscall::compile::compile_option::*;
+    use super::wasm_parser::{self, FUNC_KIND};
+    use std::{env, fs};
+    let output = env::temp_dir().join("ruscall_wasm_test.wasm");
+    let option = CompileOption {
+        target: Some("wasm32-unknown-unknown".to_string()),
+        ..CompileOption::new()
+    };
+    assert_eq!(compile_from_str(include_str!("test_data/fact.rsc"), output.to_str().unwrap(), &option), Ok(()));
+    let bytes = fs::read(&output).unwrap();
+    fs::remove_file(&output).unwrap();
+    let module = wasm_parser::parse(&bytes).unwrap();
+    //mainがexportされている
+    assert!(module.exports.iter().any(|x| x.name == "main" && x.kind == FUNC_KIND));
+    //ex宣言したprintがenvからimportされている
+    assert!(module.imports.iter().any(|x| x.module == "env" && x.name == "print" && x.kind == FUNC_KIND));
+}
+
+#[test]
+fn repl_test() {
+    use self::ruscall::compile::jit::PANIC_ERROR;
+    use self::ruscall::repl::Repl;
+    let mut repl = Repl::new();
+    assert_eq!(repl.eval_line("infixl 1 +;"), Ok(None));
+    assert_eq!(repl.eval_line("infixl 0 ==;"), Ok(None));
+    assert_eq!(repl.eval_line("add x y = x + y"), Ok(Some("add :: Int32->(Int32->Int32)".to_string())));
+    assert_eq!(repl.eval_line("add 2 3"), Ok(Some("5 :: Int32".to_string())));
+    assert_eq!(repl.eval_line("add 2 3 == 5"), Ok(Some("true :: Bool".to_string())));
+    assert_eq!(repl.eval_line(":type (add 1, true)"), Ok(Some("((Int32->Int32),Bool)".to_string())));
+    assert!(repl.eval_line("add true").is_err());
+    assert_eq!(repl.eval_line(":reset"), Ok(None));
+    assert!(repl.eval_line("add 2 3").is_err());
+    //読めないファイルはエラーを表示して続ける
+    assert!(repl.eval_line(":load missing.rsc").unwrap_err().starts_with("missing.rsc: "));
+    assert_eq!(repl.eval_line("1"), Ok(Some("1 :: Int32".to_string())));
+    //パニックしても対話環境は終了しない
+    assert_eq!(repl.eval_line("infixl 2 /;"), Ok(None));
+    assert_eq!(repl.eval_line("1 / 0"), Err(PANIC_ERROR.to_string()));
+    assert_eq!(repl.eval_line("4 / 2"), Ok(Some("2 :: Int32".to_string())));
+}
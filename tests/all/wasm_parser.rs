@@ -0,0 +1,175 @@
+//テストで出力されたwasmを検証するための最小限のパーサー
+
+#[derive(Debug)]
+pub struct WasmModule {
+    pub types: Vec<FuncType>,
+    pub imports: Vec<Import>,
+    pub functions: Vec<u32>,
+    pub exports: Vec<Export>,
+    pub code_count: u32,
+}
+
+#[derive(Debug)]
+pub struct FuncType {
+    pub params: Vec<u8>,
+    pub results: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct Import {
+    pub module: String,
+    pub name: String,
+    pub kind: u8,
+}
+
+#[derive(Debug)]
+pub struct Export {
+    pub name: String,
+    pub kind: u8,
+    pub index: u32,
+}
+
+pub const FUNC_KIND: u8 = 0;
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn eof(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        let byte = *self.bytes.get(self.pos).ok_or("unexpected end")?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.pos + len > self.bytes.len() {
+            return Err("unexpected end".to_string());
+        }
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn leb_u32(&mut self) -> Result<u32, String> {
+        let mut result = 0u32;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift >= 32 {
+                return Err("leb128 too long".to_string());
+            }
+            result |= ((byte & 0x7f) as u32) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+            shift += 7;
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let len = self.leb_u32()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).map_err(|err| err.to_string())
+    }
+
+    fn vec<T, F: FnMut(&mut Reader<'a>) -> Result<T, String>>(&mut self, mut f: F) -> Result<Vec<T>, String> {
+        let len = self.leb_u32()?;
+        (0..len).map(|_| f(self)).collect()
+    }
+
+    fn limits(&mut self) -> Result<(), String> {
+        let flag = self.u8()?;
+        self.leb_u32()?;
+        if flag & 1 == 1 {
+            self.leb_u32()?;
+        }
+        Ok(())
+    }
+}
+
+//バイト列をパースして、インデックスの範囲などを検証する
+pub fn parse(bytes: &[u8]) -> Result<WasmModule, String> {
+    let mut reader = Reader { bytes, pos: 0 };
+    if reader.bytes(4)? != b"\0asm" {
+        return Err("bad magic".to_string());
+    }
+    if reader.bytes(4)? != [1, 0, 0, 0] {
+        return Err("unsupported version".to_string());
+    }
+    let mut module = WasmModule { types: vec![], imports: vec![], functions: vec![], exports: vec![], code_count: 0 };
+    let mut last_id = 0;
+    while !reader.eof() {
+        let id = reader.u8()?;
+        let size = reader.leb_u32()? as usize;
+        let mut section = Reader { bytes: reader.bytes(size)?, pos: 0 };
+        //カスタムセクション以外は決まった順で1回ずつ
+        if id != 0 {
+            if id <= last_id {
+                return Err(format!("section {} out of order", id));
+            }
+            last_id = id;
+        }
+        match id {
+            1 => module.types = section.vec(|r| {
+                if r.u8()? != 0x60 {
+                    return Err("bad func type".to_string());
+                }
+                let params = r.vec(|r| r.u8())?;
+                let results = r.vec(|r| r.u8())?;
+                Ok(FuncType { params, results })
+            })?,
+            2 => module.imports = section.vec(|r| {
+                let module = r.name()?;
+                let name = r.name()?;
+                let kind = r.u8()?;
+                match kind {
+                    0 => { r.leb_u32()?; }
+                    1 => {
+                        r.u8()?;
+                        r.limits()?;
+                    }
+                    2 => r.limits()?,
+                    3 => { r.bytes(2)?; }
+                    _ => return Err(format!("bad import kind {}", kind)),
+                }
+                Ok(Import { module, name, kind })
+            })?,
+            3 => module.functions = section.vec(|r| r.leb_u32())?,
+            7 => module.exports = section.vec(|r| {
+                let name = r.name()?;
+                let kind = r.u8()?;
+                let index = r.leb_u32()?;
+                Ok(Export { name, kind, index })
+            })?,
+            10 => {
+                module.code_count = section.leb_u32()?;
+                for _ in 0..module.code_count {
+                    let body_size = section.leb_u32()? as usize;
+                    section.bytes(body_size)?;
+                }
+            }
+            0 | 4 | 5 | 6 | 8 | 9 | 11 | 12 => section.pos = size,
+            _ => return Err(format!("unknown section {}", id)),
+        }
+        if section.pos != size {
+            return Err(format!("section {} size mismatch", id));
+        }
+    }
+
+    if module.functions.len() as u32 != module.code_count {
+        return Err("function and code section count mismatch".to_string());
+    }
+    if module.functions.iter().any(|&ty| ty as usize >= module.types.len()) {
+        return Err("type index out of range".to_string());
+    }
+    let func_count = module.imports.iter().filter(|x| x.kind == FUNC_KIND).count() + module.functions.len();
+    if module.exports.iter().any(|x| x.kind == FUNC_KIND && x.index as usize >= func_count) {
+        return Err("export function index out of range".to_string());
+    }
+    Ok(module)
+}